@@ -0,0 +1,13 @@
+CREATE TABLE `speaker_mappings` (
+	`id` text PRIMARY KEY NOT NULL,
+	`campaign_id` text NOT NULL,
+	`speaker_name` text NOT NULL,
+	`player_id` text,
+	`is_dm` integer DEFAULT false NOT NULL,
+	`created_at` text DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	`updated_at` text DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	FOREIGN KEY (`campaign_id`) REFERENCES `campaigns`(`id`) ON UPDATE no action ON DELETE no action,
+	FOREIGN KEY (`player_id`) REFERENCES `players`(`id`) ON UPDATE no action ON DELETE no action
+);
+--> statement-breakpoint
+CREATE UNIQUE INDEX `speaker_mappings_campaign_speaker_unique` ON `speaker_mappings` (`campaign_id`,`speaker_name`);
\ No newline at end of file
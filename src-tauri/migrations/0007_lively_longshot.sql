@@ -0,0 +1,12 @@
+CREATE TABLE `moments` (
+	`id` text PRIMARY KEY NOT NULL,
+	`session_id` text NOT NULL,
+	`start_time` real NOT NULL,
+	`end_time` real NOT NULL,
+	`label` text NOT NULL,
+	`created_at` text DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	`updated_at` text DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	FOREIGN KEY (`session_id`) REFERENCES `sessions`(`id`) ON UPDATE no action ON DELETE no action
+);
+--> statement-breakpoint
+CREATE INDEX `moments_session_id_idx` ON `moments` (`session_id`);
\ No newline at end of file
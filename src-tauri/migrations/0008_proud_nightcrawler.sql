@@ -0,0 +1,14 @@
+CREATE TABLE `chapters` (
+	`id` text PRIMARY KEY NOT NULL,
+	`session_id` text NOT NULL,
+	`start_time` real NOT NULL,
+	`end_time` real NOT NULL,
+	`kind` text NOT NULL,
+	`title` text NOT NULL,
+	`confidence` real,
+	`created_at` text DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	`updated_at` text DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	FOREIGN KEY (`session_id`) REFERENCES `sessions`(`id`) ON UPDATE no action ON DELETE no action
+);
+--> statement-breakpoint
+CREATE INDEX `chapters_session_id_idx` ON `chapters` (`session_id`);
\ No newline at end of file
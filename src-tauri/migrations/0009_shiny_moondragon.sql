@@ -0,0 +1,19 @@
+CREATE TABLE `watched_files` (
+	`id` text PRIMARY KEY NOT NULL,
+	`campaign_id` text NOT NULL,
+	`path` text NOT NULL,
+	`size` integer NOT NULL,
+	`modified_at` real NOT NULL,
+	`last_change_at` real NOT NULL,
+	`duration` real,
+	`status` text NOT NULL,
+	`session_id` text,
+	`created_at` text DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	`updated_at` text DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	FOREIGN KEY (`campaign_id`) REFERENCES `campaigns`(`id`) ON UPDATE no action ON DELETE no action,
+	FOREIGN KEY (`session_id`) REFERENCES `sessions`(`id`) ON UPDATE no action ON DELETE no action
+);
+--> statement-breakpoint
+CREATE UNIQUE INDEX `watched_files_campaign_id_path_unique` ON `watched_files` (`campaign_id`,`path`);--> statement-breakpoint
+ALTER TABLE `campaigns` ADD `watch_folder` text;--> statement-breakpoint
+ALTER TABLE `sessions` ADD `status` text DEFAULT 'ready' NOT NULL;
\ No newline at end of file
@@ -0,0 +1,13 @@
+CREATE TABLE `watched_folders` (
+	`campaign_id` text PRIMARY KEY NOT NULL,
+	`path` text NOT NULL,
+	`watching_since` real NOT NULL,
+	FOREIGN KEY (`campaign_id`) REFERENCES `campaigns`(`id`) ON UPDATE no action ON DELETE no action
+);--> statement-breakpoint
+INSERT INTO `watched_folders` (`campaign_id`, `path`, `watching_since`)
+SELECT `watched_files`.`campaign_id`, `watched_files`.`path`, MIN(`watched_files`.`modified_at`)
+FROM `watched_files`
+JOIN `campaigns` ON `campaigns`.`id` = `watched_files`.`campaign_id` AND `campaigns`.`watch_folder` = `watched_files`.`path`
+WHERE `watched_files`.`status` = 'folder'
+GROUP BY `watched_files`.`campaign_id`;--> statement-breakpoint
+DELETE FROM `watched_files` WHERE `status` = 'folder';
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE `jobs` ADD `run_after` text;
\ No newline at end of file
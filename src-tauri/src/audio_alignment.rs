@@ -0,0 +1,754 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tauri::{command, AppHandle, Manager};
+
+use crate::audio_format::OutputOptions;
+use crate::audio_utils;
+
+const ANALYSIS_SAMPLE_RATE: usize = 16000;
+/// Samples per envelope frame, giving one energy value per millisecond
+const FRAME_SAMPLES: usize = ANALYSIS_SAMPLE_RATE / 1000;
+/// The coarse offset search works on 20 ms frames; the result is then refined
+/// at full resolution
+const COARSE_FACTOR: usize = 20;
+/// Anchors spread across each recording; more than one lets drift be measured
+const ANCHOR_COUNT: usize = 5;
+const ANCHOR_SECS: f64 = 60.0;
+/// Anchors that correlate worse than this are ignored
+const MIN_CONFIDENCE: f64 = 0.3;
+/// Real clock drift is a few hundred ppm at most; anything larger means the
+/// anchors disagree rather than the clocks
+const MAX_DRIFT: f64 = 0.001;
+/// Best-source mode only switches when another source is this much better (dB)
+const SWITCH_MARGIN_DB: f64 = 3.0;
+/// Best-source mode crossfades over this long where it hands over to another
+/// source, rather than cutting with a click
+const CROSSFADE_SECS: f64 = 0.1;
+/// `volume` holds one gain per frame, so gated sources are cut into small
+/// frames for the crossfade to ramp in steps of under 6 ms
+const GATE_FRAME_SAMPLES: u32 = 256;
+const OUTPUT_SAMPLE_RATE: u32 = 44100;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlignmentMode {
+    /// Mix every aligned recording into one track
+    #[default]
+    Mix,
+    /// Use whichever recording sounds cleanest in each time window
+    BestSource,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlignRecordingsRequest {
+    pub session_id: String,
+    /// Overlapping recordings of the same session; the first is the reference
+    /// the others are aligned to
+    pub file_paths: Vec<String>,
+    #[serde(default)]
+    pub mode: AlignmentMode,
+    /// Length of the windows best-source mode chooses between
+    #[serde(default = "default_window_secs")]
+    pub window_secs: f64,
+    /// How far apart the recordings may have started
+    #[serde(default = "default_max_offset_secs")]
+    pub max_offset_secs: f64,
+}
+
+fn default_window_secs() -> f64 {
+    5.0
+}
+
+fn default_max_offset_secs() -> f64 {
+    300.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackAlignment {
+    pub path: String,
+    /// Where this recording starts on the reference timeline; negative when it
+    /// started before the reference
+    pub offset_secs: f64,
+    /// How much faster this recording's clock runs than the reference's, in
+    /// parts per million
+    pub drift_ppm: f64,
+    /// Mean correlation of the anchors the estimate is based on (0 to 1)
+    pub confidence: f64,
+}
+
+/// Stretch of the aligned output taken from one source, in best-source mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceSegment {
+    pub start: f64,
+    pub end: f64,
+    /// Index into `AlignRecordingsRequest::file_paths`
+    pub source: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AlignRecordingsResponse {
+    /// Aligned track, ready to pass to `process_audio_files`
+    pub output_path: String,
+    pub mode: AlignmentMode,
+    pub tracks: Vec<TrackAlignment>,
+    #[serde(default)]
+    pub segments: Vec<SourceSegment>,
+}
+
+/// Placement of one recording on the output timeline
+struct Placement {
+    delay_secs: f64,
+    /// Output duration per second of the recording's own time
+    stretch: f64,
+}
+
+#[command]
+pub async fn align_recordings(
+    app: AppHandle,
+    request: AlignRecordingsRequest,
+) -> Result<AlignRecordingsResponse, String> {
+    audio_utils::run_blocking(move || align(app, request)).await
+}
+
+fn align(
+    app: AppHandle,
+    request: AlignRecordingsRequest,
+) -> Result<AlignRecordingsResponse, String> {
+    if request.file_paths.len() < 2 {
+        return Err("At least two recordings are needed for alignment".to_string());
+    }
+
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?;
+
+    let aligned_dir = app_data_dir
+        .join("sessions")
+        .join(&request.session_id)
+        .join("aligned");
+    std::fs::create_dir_all(&aligned_dir)
+        .map_err(|e| format!("Failed to create aligned directory: {}", e))?;
+
+    let ffmpeg_path = audio_utils::get_ffmpeg_path(&app)?;
+
+    // Decoding dominates, so every recording is decoded at once
+    let envelopes = std::thread::scope(|scope| {
+        let handles: Vec<_> = request
+            .file_paths
+            .iter()
+            .map(|path| scope.spawn(|| energy_envelope(&ffmpeg_path, Path::new(path))))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err("Envelope analysis panicked".to_string()))
+            })
+            .collect::<Result<Vec<_>, String>>()
+    })?;
+
+    let mut tracks = vec![TrackAlignment {
+        path: request.file_paths[0].clone(),
+        offset_secs: 0.0,
+        drift_ppm: 0.0,
+        confidence: 1.0,
+    }];
+    for (path, envelope) in request.file_paths.iter().zip(&envelopes).skip(1) {
+        let (offset_secs, drift, confidence) =
+            estimate_alignment(&envelopes[0], envelope, request.max_offset_secs)
+                .map_err(|e| format!("Could not align {}: {}", path, e))?;
+        tracks.push(TrackAlignment {
+            path: path.clone(),
+            offset_secs,
+            drift_ppm: drift * 1_000_000.0,
+            confidence,
+        });
+    }
+
+    // Shift everything so the earliest recording starts at zero
+    let origin = tracks.iter().map(|t| t.offset_secs).fold(0.0_f64, f64::min);
+    let placements: Vec<Placement> = tracks
+        .iter()
+        .map(|t| Placement {
+            delay_secs: t.offset_secs - origin,
+            stretch: 1.0 + t.drift_ppm / 1_000_000.0,
+        })
+        .collect();
+
+    let segments = match request.mode {
+        AlignmentMode::Mix => Vec::new(),
+        AlignmentMode::BestSource => {
+            choose_sources(&envelopes, &placements, request.window_secs.max(0.5))
+        }
+    };
+
+    let output_path = aligned_dir.join("aligned.wav");
+    let graph_path = aligned_dir.join("graph.txt");
+    std::fs::write(
+        &graph_path,
+        alignment_graph(&placements, request.mode, &segments),
+    )
+    .map_err(|e| format!("Failed to write filter graph: {}", e))?;
+
+    let mut command = Command::new(&ffmpeg_path);
+    for path in &request.file_paths {
+        command.arg("-i").arg(path);
+    }
+    let result = command
+        .arg("-filter_complex_script")
+        .arg(&graph_path)
+        .args(["-map", "[out]"])
+        .args(OutputOptions::intermediate(OUTPUT_SAMPLE_RATE, 2).encode_args())
+        .arg("-y")
+        .arg(&output_path)
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+    let _ = std::fs::remove_file(&graph_path);
+
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(format!("FFmpeg alignment failed: {}", stderr));
+    }
+
+    let response = AlignRecordingsResponse {
+        output_path: output_path.to_string_lossy().to_string(),
+        mode: request.mode,
+        tracks,
+        segments,
+    };
+
+    let report = serde_json::to_string_pretty(&response)
+        .map_err(|e| format!("Failed to serialize alignment: {}", e))?;
+    std::fs::write(aligned_dir.join("alignment.json"), report)
+        .map_err(|e| format!("Failed to write alignment: {}", e))?;
+
+    Ok(response)
+}
+
+/// Streams a recording through FFmpeg as mono PCM and returns its energy in dB
+/// for every millisecond
+fn energy_envelope(ffmpeg_path: &Path, input: &Path) -> Result<Vec<f32>, String> {
+    let mut child = Command::new(ffmpeg_path)
+        .arg("-i")
+        .arg(input)
+        .args(["-vn", "-ac", "1", "-ar"])
+        .arg(ANALYSIS_SAMPLE_RATE.to_string())
+        .args(["-f", "s16le", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    let stdout = child.stdout.take().ok_or("Failed to read FFmpeg output")?;
+    let mut reader = BufReader::new(stdout);
+    let mut frame = [0u8; FRAME_SAMPLES * 2];
+    let mut envelope = Vec::new();
+
+    while reader.read_exact(&mut frame).is_ok() {
+        let energy = frame
+            .chunks_exact(2)
+            .map(|bytes| {
+                let sample = i16::from_le_bytes([bytes[0], bytes[1]]) as f64 / 32768.0;
+                sample * sample
+            })
+            .sum::<f64>()
+            / FRAME_SAMPLES as f64;
+        envelope.push((10.0 * (energy + 1e-10).log10()) as f32);
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+    if !status.success() {
+        return Err(format!("FFmpeg could not decode {:?}", input));
+    }
+    if envelope.is_empty() {
+        return Err(format!("{:?} contains no audio", input));
+    }
+
+    Ok(envelope)
+}
+
+/// Finds where `other` sits relative to `reference` by correlating their
+/// energy envelopes at several anchors, then fits a line through the anchor
+/// offsets. Returns the offset at the start of `other` (seconds), its drift
+/// ratio and the mean anchor correlation.
+fn estimate_alignment(
+    reference: &[f32],
+    other: &[f32],
+    max_offset_secs: f64,
+) -> Result<(f64, f64, f64), String> {
+    let reference_coarse = downsample(reference, COARSE_FACTOR);
+    let other_coarse = downsample(other, COARSE_FACTOR);
+    let coarse_rate = 1000.0 / COARSE_FACTOR as f64;
+
+    let window = ((ANCHOR_SECS * coarse_rate) as usize)
+        .min(other_coarse.len() / 2)
+        .max(1);
+    let max_lag = (max_offset_secs * coarse_rate) as isize;
+    let span = other_coarse.len().saturating_sub(window);
+
+    // (anchor centre in `other`, offset to reference, correlation), in seconds
+    let mut anchors = Vec::new();
+    for k in 0..ANCHOR_COUNT {
+        let start = span * (2 * k + 1) / (2 * ANCHOR_COUNT);
+        let probe = &other_coarse[start..start + window];
+
+        let Some((lag, correlation)) =
+            best_lag(&reference_coarse, probe, start as isize, -max_lag, max_lag)
+        else {
+            continue;
+        };
+        if correlation < MIN_CONFIDENCE {
+            continue;
+        }
+
+        // Refine to the millisecond around the coarse match
+        let fine_start = start * COARSE_FACTOR;
+        let fine_probe = &other[fine_start..(fine_start + window * COARSE_FACTOR).min(other.len())];
+        let centre = lag * COARSE_FACTOR as isize;
+        let fine_lag = best_lag(
+            reference,
+            fine_probe,
+            fine_start as isize,
+            centre - COARSE_FACTOR as isize,
+            centre + COARSE_FACTOR as isize,
+        )
+        .map(|(lag, _)| lag)
+        .unwrap_or(centre);
+
+        anchors.push((
+            (fine_start + fine_probe.len() / 2) as f64 / 1000.0,
+            fine_lag as f64 / 1000.0,
+            correlation,
+        ));
+    }
+
+    if anchors.is_empty() {
+        return Err(format!(
+            "no matching audio found within {} seconds of the reference",
+            max_offset_secs
+        ));
+    }
+
+    let confidence = anchors.iter().map(|a| a.2).sum::<f64>() / anchors.len() as f64;
+    let (offset, drift) = fit_line(&anchors);
+    if drift.abs() > MAX_DRIFT {
+        let mut offsets: Vec<f64> = anchors.iter().map(|a| a.1).collect();
+        offsets.sort_by(f64::total_cmp);
+        return Ok((offsets[offsets.len() / 2], 0.0, confidence));
+    }
+
+    Ok((offset, drift, confidence))
+}
+
+/// Least-squares fit of offset = a + b * time. Returns (a, b).
+fn fit_line(points: &[(f64, f64, f64)]) -> (f64, f64) {
+    let n = points.len() as f64;
+    let mean_t = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_o = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let covariance: f64 = points.iter().map(|p| (p.0 - mean_t) * (p.1 - mean_o)).sum();
+    let variance: f64 = points.iter().map(|p| (p.0 - mean_t).powi(2)).sum();
+
+    if variance < 1e-9 {
+        return (mean_o, 0.0);
+    }
+    let slope = covariance / variance;
+    (mean_o - slope * mean_t, slope)
+}
+
+/// Pearson correlation of `probe` against `reference` positioned at
+/// `base + lag`, for every lag in `min_lag..=max_lag`. Returns the best lag.
+fn best_lag(
+    reference: &[f32],
+    probe: &[f32],
+    base: isize,
+    min_lag: isize,
+    max_lag: isize,
+) -> Option<(isize, f64)> {
+    let n = probe.len() as f64;
+    let probe_mean = probe.iter().map(|&p| p as f64).sum::<f64>() / n;
+    let probe_var = probe
+        .iter()
+        .map(|&p| (p as f64 - probe_mean).powi(2))
+        .sum::<f64>()
+        / n;
+    if probe_var < 1e-9 {
+        return None;
+    }
+    let probe_std = probe_var.sqrt();
+
+    let mut best: Option<(isize, f64)> = None;
+    for lag in min_lag..=max_lag {
+        let position = base + lag;
+        if position < 0 || position as usize + probe.len() > reference.len() {
+            continue;
+        }
+        let segment = &reference[position as usize..position as usize + probe.len()];
+
+        let (mut sum, mut sum_sq, mut sum_product) = (0.0, 0.0, 0.0);
+        for (&r, &p) in segment.iter().zip(probe) {
+            let (r, p) = (r as f64, p as f64);
+            sum += r;
+            sum_sq += r * r;
+            sum_product += r * p;
+        }
+        let mean = sum / n;
+        let var = sum_sq / n - mean * mean;
+        if var < 1e-9 {
+            continue;
+        }
+
+        let correlation = (sum_product / n - mean * probe_mean) / (var.sqrt() * probe_std);
+        if best.is_none_or(|(_, c)| correlation > c) {
+            best = Some((lag, correlation));
+        }
+    }
+
+    best
+}
+
+fn downsample(envelope: &[f32], factor: usize) -> Vec<f32> {
+    envelope
+        .chunks(factor)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect()
+}
+
+/// Picks a source for every window of the output timeline. A source's quality
+/// is the spread between its loud (speech) and quiet (noise floor) frames in
+/// that window, a rough signal-to-noise estimate.
+fn choose_sources(
+    envelopes: &[Vec<f32>],
+    placements: &[Placement],
+    window_secs: f64,
+) -> Vec<SourceSegment> {
+    let duration = envelopes
+        .iter()
+        .zip(placements)
+        .map(|(env, p)| p.delay_secs + env.len() as f64 / 1000.0 * p.stretch)
+        .fold(0.0_f64, f64::max);
+
+    let mut segments: Vec<SourceSegment> = Vec::new();
+    let mut current: Option<usize> = None;
+    let mut start = 0.0;
+
+    while start < duration {
+        let end = (start + window_secs).min(duration);
+
+        let scores: Vec<Option<f64>> = envelopes
+            .iter()
+            .zip(placements)
+            .map(|(env, p)| {
+                let own_start = (start - p.delay_secs) / p.stretch;
+                let own_end = (end - p.delay_secs) / p.stretch;
+                window_quality(env, own_start, own_end)
+            })
+            .collect();
+
+        let best = scores
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| s.map(|s| (i, s)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        let chosen = match (current, best) {
+            (Some(cur), Some((i, score))) => match scores[cur] {
+                Some(cur_score) if score < cur_score + SWITCH_MARGIN_DB => cur,
+                _ => i,
+            },
+            (None, Some((i, _))) => i,
+            (Some(cur), None) => cur,
+            (None, None) => 0,
+        };
+        current = Some(chosen);
+
+        match segments.last_mut() {
+            Some(last) if last.source == chosen => last.end = end,
+            _ => segments.push(SourceSegment {
+                start,
+                end,
+                source: chosen,
+            }),
+        }
+
+        start = end;
+    }
+
+    segments
+}
+
+/// Spread between the 90th and 10th percentile frame energy over a stretch
+/// of a recording's own time. `None` if it covers less than half the stretch.
+fn window_quality(envelope: &[f32], start: f64, end: f64) -> Option<f64> {
+    let wanted = ((end - start) * 1000.0) as usize;
+    let from = (start.max(0.0) * 1000.0) as usize;
+    let to = ((end * 1000.0).max(0.0) as usize).min(envelope.len());
+    if to <= from || (to - from) * 2 < wanted {
+        return None;
+    }
+
+    let mut frames = envelope[from..to].to_vec();
+    frames.sort_by(f32::total_cmp);
+    let len = frames.len();
+    Some((frames[len * 9 / 10] - frames[len / 10]) as f64)
+}
+
+/// Filter graph that stretches and delays every input onto the output
+/// timeline, then mixes them or gates each to its chosen segments
+fn alignment_graph(
+    placements: &[Placement],
+    mode: AlignmentMode,
+    segments: &[SourceSegment],
+) -> String {
+    let mut graph = Vec::new();
+    let mut labels = String::new();
+
+    for (index, placement) in placements.iter().enumerate() {
+        let mut chain = vec![format!(
+            "aformat=sample_fmts=fltp:sample_rates={}:channel_layouts=stereo",
+            OUTPUT_SAMPLE_RATE
+        )];
+        if placement.stretch != 1.0 {
+            chain.push(format!("atempo={:.9}", 1.0 / placement.stretch));
+        }
+        let delay_samples = (placement.delay_secs * OUTPUT_SAMPLE_RATE as f64).round() as u64;
+        if delay_samples > 0 {
+            chain.push(format!("adelay=delays={}S:all=1", delay_samples));
+        }
+
+        if mode == AlignmentMode::BestSource {
+            chain.push(format!("asetnsamples=n={}:p=0", GATE_FRAME_SAMPLES));
+            chain.push(format!(
+                "volume='{}':eval=frame",
+                gain_expression(segments, index)
+            ));
+        }
+
+        graph.push(format!("[{}:a]{}[a{}]", index, chain.join(","), index));
+        labels.push_str(&format!("[a{}]", index));
+    }
+
+    // Mixed sources each carry the same speech, so they are averaged; gated
+    // sources never overlap, so they are summed
+    let normalize = match mode {
+        AlignmentMode::Mix => 1,
+        AlignmentMode::BestSource => 0,
+    };
+    graph.push(format!(
+        "{}amix=inputs={}:duration=longest:normalize={}[out]",
+        labels,
+        placements.len(),
+        normalize
+    ));
+
+    graph.join(";\n")
+}
+
+/// Gain of one source over the output timeline in best-source mode: 1 inside
+/// its segments and 0 outside, with linear ramps centred on every hand-over
+/// so the outgoing and incoming sources always add up to 1. The start and
+/// end of the timeline aren't faded.
+fn gain_expression(segments: &[SourceSegment], source: usize) -> String {
+    let first = segments.first().map_or(0.0, |s| s.start);
+    let last = segments.last().map_or(0.0, |s| s.end);
+    let half = CROSSFADE_SECS / 2.0;
+
+    let terms: Vec<String> = segments
+        .iter()
+        .filter(|s| s.source == source)
+        .map(|s| {
+            let fade_in = s.start > first;
+            let fade_out = s.end < last;
+            let rise = format!("(t-{:.3})", s.start - half);
+            let fall = format!("({:.3}-t)", s.end + half);
+            let ramp = match (fade_in, fade_out) {
+                (true, true) => format!("min({},{})", rise, fall),
+                (true, false) => rise,
+                (false, true) => fall,
+                (false, false) => return "1".to_string(),
+            };
+            format!("clip({}/{:.3},0,1)", ramp, CROSSFADE_SECS)
+        })
+        .collect();
+
+    if terms.is_empty() {
+        "0".to_string()
+    } else {
+        terms.join("+")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Speech-like envelope: a random level in dB held for 50 to 300 ms at a
+    /// time, from a fixed seed so every run sees the same signal
+    fn envelope(len_ms: usize, seed: u64) -> Vec<f32> {
+        let mut state = seed;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as u32
+        };
+
+        let mut envelope = Vec::with_capacity(len_ms);
+        while envelope.len() < len_ms {
+            let level = -60.0 + (next() % 50) as f32;
+            let hold = 50 + (next() % 250) as usize;
+            envelope.extend(std::iter::repeat_n(level, hold));
+        }
+        envelope.truncate(len_ms);
+        envelope
+    }
+
+    /// `reference` as heard by a recording that started `offset_ms` into it
+    /// and whose clock runs `drift` faster
+    fn recording_of(reference: &[f32], offset_ms: f64, drift: f64, len_ms: usize) -> Vec<f32> {
+        (0..len_ms)
+            .map(|i| {
+                let position = (offset_ms + i as f64 * (1.0 + drift)).round() as usize;
+                reference[position.min(reference.len() - 1)]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn best_lag_finds_a_copied_stretch() {
+        let reference = envelope(20_000, 1);
+        let probe = &reference[7_000..9_000];
+
+        let (lag, correlation) = best_lag(&reference, probe, 6_500, -1_000, 1_000).unwrap();
+        assert_eq!(lag, 500);
+        assert!(correlation > 0.999, "correlation {}", correlation);
+    }
+
+    #[test]
+    fn best_lag_skips_lags_past_either_end() {
+        let reference = envelope(5_000, 2);
+        let probe = &reference[0..1_000];
+
+        // Only lags that keep the probe inside the reference are tried
+        let (lag, _) = best_lag(&reference, probe, 0, -500, 500).unwrap();
+        assert_eq!(lag, 0);
+        assert_eq!(best_lag(&reference, probe, 4_500, 0, 100), None);
+    }
+
+    #[test]
+    fn best_lag_ignores_a_flat_probe() {
+        let reference = envelope(5_000, 3);
+        assert_eq!(best_lag(&reference, &[-40.0; 500], 0, 0, 100), None);
+    }
+
+    #[test]
+    fn fit_line_recovers_offset_and_slope() {
+        let points: Vec<(f64, f64, f64)> = [10.0, 100.0, 250.0, 400.0]
+            .iter()
+            .map(|&t| (t, 2.5 + 0.0002 * t, 1.0))
+            .collect();
+        let (offset, slope) = fit_line(&points);
+        assert!((offset - 2.5).abs() < 1e-9);
+        assert!((slope - 0.0002).abs() < 1e-12);
+    }
+
+    #[test]
+    fn fit_line_without_spread_in_time_averages() {
+        let (offset, slope) = fit_line(&[(60.0, 1.0, 1.0), (60.0, 3.0, 1.0)]);
+        assert_eq!((offset, slope), (2.0, 0.0));
+    }
+
+    #[test]
+    fn estimate_alignment_finds_a_known_offset() {
+        let reference = envelope(400_000, 4);
+        let other = recording_of(&reference, 12_345.0, 0.0, 300_000);
+
+        let (offset, drift, confidence) = estimate_alignment(&reference, &other, 30.0).unwrap();
+        assert!((offset - 12.345).abs() < 0.002, "offset {}", offset);
+        assert!(drift.abs() < 5e-6, "drift {}", drift);
+        assert!(confidence > 0.9, "confidence {}", confidence);
+    }
+
+    #[test]
+    fn estimate_alignment_finds_a_recording_that_started_first() {
+        let reference = envelope(400_000, 5);
+        let other = recording_of(&reference, 8_000.0, 0.0, 380_000);
+
+        // `reference` is now the later recording: it starts 8 s into `other`
+        let (offset, _, _) = estimate_alignment(&other, &reference[..300_000], 30.0).unwrap();
+        assert!((offset + 8.0).abs() < 0.002, "offset {}", offset);
+    }
+
+    #[test]
+    fn estimate_alignment_measures_linear_drift() {
+        let reference = envelope(700_000, 6);
+        let other = recording_of(&reference, 5_000.0, 0.0002, 600_000);
+
+        let (offset, drift, _) = estimate_alignment(&reference, &other, 30.0).unwrap();
+        assert!((offset - 5.0).abs() < 0.005, "offset {}", offset);
+        assert!(
+            (drift * 1_000_000.0 - 200.0).abs() < 20.0,
+            "drift {} ppm",
+            drift * 1_000_000.0
+        );
+    }
+
+    #[test]
+    fn estimate_alignment_rejects_silence() {
+        let reference = envelope(100_000, 7);
+        assert!(estimate_alignment(&reference, &[-90.0; 100_000], 30.0).is_err());
+    }
+
+    fn segment(start: f64, end: f64, source: usize) -> SourceSegment {
+        SourceSegment { start, end, source }
+    }
+
+    #[test]
+    fn sources_crossfade_where_they_hand_over() {
+        let segments = [
+            segment(0.0, 10.0, 0),
+            segment(10.0, 25.0, 1),
+            segment(25.0, 40.0, 0),
+        ];
+
+        assert_eq!(
+            gain_expression(&segments, 0),
+            "clip((10.050-t)/0.100,0,1)+clip((t-24.950)/0.100,0,1)"
+        );
+        assert_eq!(
+            gain_expression(&segments, 1),
+            "clip(min((t-9.950),(25.050-t))/0.100,0,1)"
+        );
+        assert_eq!(gain_expression(&segments, 2), "0");
+        assert_eq!(gain_expression(&segments[..1], 0), "1");
+    }
+
+    #[test]
+    fn best_source_graph_gates_in_small_frames() {
+        let placements = [
+            Placement {
+                delay_secs: 0.0,
+                stretch: 1.0,
+            },
+            Placement {
+                delay_secs: 1.5,
+                stretch: 1.0002,
+            },
+        ];
+        let segments = [segment(0.0, 10.0, 0), segment(10.0, 20.0, 1)];
+
+        let graph = alignment_graph(&placements, AlignmentMode::BestSource, &segments);
+        assert!(graph.contains("asetnsamples=n=256:p=0,volume='clip((10.050-t)/0.100,0,1)'"));
+        assert!(graph.contains("adelay=delays=66150S:all=1"));
+        assert!(graph.ends_with("[a0][a1]amix=inputs=2:duration=longest:normalize=0[out]"));
+
+        let mixed = alignment_graph(&placements, AlignmentMode::Mix, &[]);
+        assert!(!mixed.contains("volume"));
+        assert!(mixed.ends_with("normalize=1[out]"));
+    }
+}
@@ -0,0 +1,465 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{command, AppHandle, Manager, State};
+
+const INDEX_FILE: &str = "index.json";
+
+/// Cached intermediates are evicted, least recently used first, once the
+/// cache grows past this size
+const MAX_CACHE_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Cache of per-input processing results under `app_data/audio_cache`, keyed
+/// by a hash of the input's content and the settings it was processed with.
+/// There is one per app, held in managed state, so concurrent runs share its
+/// index instead of overwriting each other's entries.
+pub struct AudioCache {
+    dir: PathBuf,
+    index: Mutex<CacheIndex>,
+    max_bytes: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheIndex {
+    /// Content hashes by input path, so unchanged files aren't re-read
+    #[serde(default)]
+    fingerprints: HashMap<String, Fingerprint>,
+    /// Small results (filter chains, loudness measurements) by cache key
+    #[serde(default)]
+    values: HashMap<String, Value>,
+    /// Cached files by cache key
+    #[serde(default)]
+    files: HashMap<String, CachedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Fingerprint {
+    size: u64,
+    modified_ms: u64,
+    content_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedFile {
+    size: u64,
+    last_used_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AudioCacheInfo {
+    pub entries: usize,
+    pub total_bytes: u64,
+    pub max_bytes: u64,
+}
+
+#[command]
+pub async fn get_audio_cache_info(app: AppHandle) -> Result<AudioCacheInfo, String> {
+    let cache = managed(&app)?;
+    let index = cache.index.lock().unwrap();
+
+    Ok(AudioCacheInfo {
+        entries: index.files.len(),
+        total_bytes: index.files.values().map(|f| f.size).sum(),
+        max_bytes: cache.max_bytes,
+    })
+}
+
+#[command]
+pub async fn clear_audio_cache(app: AppHandle) -> Result<(), String> {
+    let cache = managed(&app)?;
+    let mut index = cache.index.lock().unwrap();
+    if cache.dir.exists() {
+        std::fs::remove_dir_all(&cache.dir)
+            .map_err(|e| format!("Failed to clear audio cache: {}", e))?;
+    }
+    *index = CacheIndex::default();
+    std::fs::create_dir_all(&cache.dir)
+        .map_err(|e| format!("Failed to create audio cache directory: {}", e))
+}
+
+impl AudioCache {
+    pub fn open(app_data_dir: &Path) -> Result<Self, String> {
+        let dir = cache_dir(app_data_dir);
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create audio cache directory: {}", e))?;
+
+        // A missing or unreadable index just means an empty cache
+        let index = std::fs::read_to_string(dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        let cache = Self {
+            dir,
+            index: Mutex::new(index),
+            max_bytes: MAX_CACHE_BYTES,
+        };
+        let mut index = cache.index.lock().unwrap();
+        cache.evict_stale(&mut index);
+        cache.save(&index)?;
+        drop(index);
+        Ok(cache)
+    }
+
+    /// Forgets inputs that were deleted or changed since they were hashed, and
+    /// drops the results and files cached for content no input has anymore
+    fn evict_stale(&self, index: &mut CacheIndex) {
+        index.fingerprints.retain(|path, fingerprint| {
+            std::fs::metadata(path).is_ok_and(|metadata| {
+                metadata.len() == fingerprint.size
+                    && metadata.modified().map(millis_since_epoch).unwrap_or(0)
+                        == fingerprint.modified_ms
+            })
+        });
+
+        let live: HashSet<String> = index
+            .fingerprints
+            .values()
+            .map(|fingerprint| fingerprint.content_hash.clone())
+            .collect();
+        let is_live = |key: &str| key_content_hash(key).is_some_and(|hash| live.contains(hash));
+
+        index.values.retain(|key, _| is_live(key));
+        let stale_files: Vec<String> = index
+            .files
+            .keys()
+            .filter(|key| !is_live(key))
+            .cloned()
+            .collect();
+        for key in stale_files {
+            let _ = std::fs::remove_file(self.dir.join(&key));
+            index.files.remove(&key);
+        }
+    }
+
+    /// SHA-256 of the file's content. Reuses the previous hash when the path,
+    /// size and modification time are unchanged.
+    pub fn content_hash(&self, path: &Path) -> Result<String, String> {
+        let metadata =
+            std::fs::metadata(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        let size = metadata.len();
+        let modified_ms = metadata.modified().map(millis_since_epoch).unwrap_or(0);
+        let path_key = path.to_string_lossy().to_string();
+
+        if let Some(fingerprint) = self.index.lock().unwrap().fingerprints.get(&path_key) {
+            if fingerprint.size == size && fingerprint.modified_ms == modified_ms {
+                return Ok(fingerprint.content_hash.clone());
+            }
+        }
+
+        let mut file =
+            std::fs::File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 1024 * 1024];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        let content_hash = to_hex(&hasher.finalize());
+
+        let mut index = self.index.lock().unwrap();
+        index.fingerprints.insert(
+            path_key,
+            Fingerprint {
+                size,
+                modified_ms,
+                content_hash: content_hash.clone(),
+            },
+        );
+        self.save(&index)?;
+
+        Ok(content_hash)
+    }
+
+    /// Cache key for an input processed with the given settings. It starts
+    /// with the content hash, so entries can be traced back to their input.
+    pub fn key(content_hash: &str, settings: &impl Serialize) -> String {
+        let settings = serde_json::to_string(settings).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(settings.as_bytes());
+        format!("{}_{}", content_hash, to_hex(&hasher.finalize()))
+    }
+
+    pub fn get_value<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let index = self.index.lock().unwrap();
+        index
+            .values
+            .get(key)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+
+    pub fn put_value(&self, key: &str, value: &impl Serialize) -> Result<(), String> {
+        let value = serde_json::to_value(value)
+            .map_err(|e| format!("Failed to serialize cache value: {}", e))?;
+        let mut index = self.index.lock().unwrap();
+        index.values.insert(key.to_string(), value);
+        self.save(&index)
+    }
+
+    pub fn has_file(&self, key: &str) -> bool {
+        self.index.lock().unwrap().files.contains_key(key)
+    }
+
+    /// Links (or copies) a cached file to `dest`. Returns false on a miss.
+    ///
+    /// The index isn't locked while the file is copied, which can take a
+    /// while when the cache is on another filesystem than `dest`.
+    pub fn restore_file(&self, key: &str, dest: &Path) -> bool {
+        if !self.has_file(key) {
+            return false;
+        }
+
+        let cached = self.dir.join(key);
+        let _ = std::fs::remove_file(dest);
+        let restored =
+            std::fs::hard_link(&cached, dest).is_ok() || std::fs::copy(&cached, dest).is_ok();
+
+        let mut index = self.index.lock().unwrap();
+        if !restored {
+            index.files.remove(key);
+            let _ = self.save(&index);
+            return false;
+        }
+
+        // The entry may have been evicted meanwhile, but `dest` is complete
+        if let Some(entry) = index.files.get_mut(key) {
+            entry.last_used_ms = millis_since_epoch(SystemTime::now());
+            let _ = self.save(&index);
+        }
+        true
+    }
+
+    /// Stores a copy of `source` under `key`, then evicts the least recently
+    /// used files until the cache fits within its size cap
+    pub fn store_file(&self, key: &str, source: &Path) -> Result<(), String> {
+        let cached = self.dir.join(key);
+        let size = std::fs::copy(source, &cached)
+            .map_err(|e| format!("Failed to add {:?} to audio cache: {}", source, e))?;
+
+        let mut index = self.index.lock().unwrap();
+        index.files.insert(
+            key.to_string(),
+            CachedFile {
+                size,
+                last_used_ms: millis_since_epoch(SystemTime::now()),
+            },
+        );
+
+        let mut total: u64 = index.files.values().map(|f| f.size).sum();
+        if total > self.max_bytes {
+            let mut by_age: Vec<(String, CachedFile)> = index
+                .files
+                .iter()
+                .map(|(k, f)| (k.clone(), f.clone()))
+                .collect();
+            by_age.sort_by_key(|(_, f)| f.last_used_ms);
+
+            for (old_key, file) in by_age {
+                if total <= self.max_bytes {
+                    break;
+                }
+                let _ = std::fs::remove_file(self.dir.join(&old_key));
+                index.files.remove(&old_key);
+                total = total.saturating_sub(file.size);
+            }
+        }
+
+        self.save(&index)
+    }
+
+    /// Writes the index. Callers hold its lock, so writes within the app never
+    /// interleave; the temporary file is named after the process in case
+    /// another instance shares the directory.
+    fn save(&self, index: &CacheIndex) -> Result<(), String> {
+        let content = serde_json::to_string(index)
+            .map_err(|e| format!("Failed to serialize audio cache index: {}", e))?;
+        let temp_path = self
+            .dir
+            .join(format!("{}.{}.tmp", INDEX_FILE, std::process::id()));
+        std::fs::write(&temp_path, content)
+            .and_then(|_| std::fs::rename(&temp_path, self.dir.join(INDEX_FILE)))
+            .map_err(|e| format!("Failed to write audio cache index: {}", e))
+    }
+}
+
+fn managed(app: &AppHandle) -> Result<State<'_, AudioCache>, String> {
+    app.try_state::<AudioCache>()
+        .ok_or_else(|| "Audio cache unavailable".to_string())
+}
+
+fn cache_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join("audio_cache")
+}
+
+/// Content hash a cache key was made for. Keys from before keys carried it
+/// have none, and are treated as stale.
+fn key_content_hash(key: &str) -> Option<&str> {
+    key.split_once('_').map(|(content_hash, _)| content_hash)
+}
+
+fn millis_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("{}_{}_{}", name, std::process::id(), nanos));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Keeps `last_used_ms` of consecutive cache operations apart
+    fn tick() {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    #[test]
+    fn reopening_evicts_entries_of_deleted_inputs() {
+        let dir = temp_dir("audio_cache_deleted");
+        let kept_input = dir.join("kept.wav");
+        let deleted_input = dir.join("deleted.wav");
+        std::fs::write(&kept_input, b"kept").unwrap();
+        std::fs::write(&deleted_input, b"deleted").unwrap();
+
+        let cache = AudioCache::open(&dir).unwrap();
+        let mut keys = Vec::new();
+        for input in [&kept_input, &deleted_input] {
+            let key = AudioCache::key(&cache.content_hash(input).unwrap(), &"settings");
+            cache.put_value(&key, &1).unwrap();
+            cache.store_file(&key, input).unwrap();
+            keys.push(key);
+        }
+        drop(cache);
+        std::fs::remove_file(&deleted_input).unwrap();
+
+        let cache = AudioCache::open(&dir).unwrap();
+        let kept = cache.get_value::<i32>(&keys[0]);
+        let deleted = cache.get_value::<i32>(&keys[1]);
+        let deleted_file = cache_dir(&dir).join(&keys[1]);
+        let (has_kept, has_deleted) = (cache.has_file(&keys[0]), cache.has_file(&keys[1]));
+        let deleted_file_exists = deleted_file.exists();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(kept, Some(1));
+        assert!(has_kept);
+        assert_eq!(deleted, None);
+        assert!(!has_deleted);
+        assert!(!deleted_file_exists);
+    }
+
+    #[test]
+    fn reopening_evicts_entries_of_changed_inputs_and_old_keys() {
+        let dir = temp_dir("audio_cache_changed");
+        let input = dir.join("input.wav");
+        std::fs::write(&input, b"take one").unwrap();
+
+        let cache = AudioCache::open(&dir).unwrap();
+        let key = AudioCache::key(&cache.content_hash(&input).unwrap(), &"settings");
+        cache.put_value(&key, &1).unwrap();
+        // Keys from before they carried the content hash
+        cache.put_value("0123abcd", &2).unwrap();
+        drop(cache);
+        std::fs::write(&input, b"take two, longer").unwrap();
+
+        let cache = AudioCache::open(&dir).unwrap();
+        let changed = cache.get_value::<i32>(&key);
+        let old = cache.get_value::<i32>("0123abcd");
+        let rehashed = AudioCache::key(&cache.content_hash(&input).unwrap(), &"settings");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(changed, None);
+        assert_eq!(old, None);
+        assert_ne!(rehashed, key);
+    }
+
+    #[test]
+    fn changed_settings_miss_the_cache() {
+        let dir = temp_dir("audio_cache_settings");
+        let input = dir.join("input.wav");
+        std::fs::write(&input, b"audio").unwrap();
+
+        let cache = AudioCache::open(&dir).unwrap();
+        let hash = cache.content_hash(&input).unwrap();
+        let loud = AudioCache::key(&hash, &("normalize", -16.0));
+        let quiet = AudioCache::key(&hash, &("normalize", -23.0));
+        cache.put_value(&loud, &"loud").unwrap();
+        let hit = cache.get_value::<String>(&loud);
+        let miss = cache.get_value::<String>(&quiet);
+        let same_again = AudioCache::key(&hash, &("normalize", -16.0));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(loud.starts_with(&format!("{}_", hash)));
+        assert_ne!(loud, quiet);
+        assert_eq!(same_again, loud);
+        assert_eq!(hit.as_deref(), Some("loud"));
+        assert_eq!(miss, None);
+    }
+
+    #[test]
+    fn least_recently_used_files_are_evicted_past_the_cap() {
+        let dir = temp_dir("audio_cache_lru");
+        let mut cache = AudioCache::open(&dir).unwrap();
+        cache.max_bytes = 10;
+
+        let mut keys = Vec::new();
+        for name in ["a", "b", "c"] {
+            let input = dir.join(format!("{}.wav", name));
+            std::fs::write(&input, b"1234").unwrap();
+            keys.push(AudioCache::key(&cache.content_hash(&input).unwrap(), &name));
+        }
+
+        cache.store_file(&keys[0], &dir.join("a.wav")).unwrap();
+        tick();
+        cache.store_file(&keys[1], &dir.join("b.wav")).unwrap();
+        tick();
+        // Using `a` makes `b` the least recently used
+        let restored = cache.restore_file(&keys[0], &dir.join("restored.wav"));
+        tick();
+        cache.store_file(&keys[2], &dir.join("c.wav")).unwrap();
+
+        let kept: Vec<bool> = keys.iter().map(|key| cache.has_file(key)).collect();
+        let evicted_file_exists = cache_dir(&dir).join(&keys[1]).exists();
+        let restored_content = std::fs::read(dir.join("restored.wav")).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(restored);
+        assert_eq!(restored_content, b"1234");
+        assert_eq!(kept, [true, false, true]);
+        assert!(!evicted_file_exists);
+    }
+
+    #[test]
+    fn restoring_a_missing_entry_is_a_miss() {
+        let dir = temp_dir("audio_cache_miss");
+        let cache = AudioCache::open(&dir).unwrap();
+        let restored = cache.restore_file("unknown_key", &dir.join("out.wav"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(!restored);
+    }
+}
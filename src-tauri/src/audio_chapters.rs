@@ -0,0 +1,436 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tauri::{command, AppHandle, Manager};
+
+use crate::audio_processor;
+use crate::audio_utils;
+use crate::drizzle_proxy;
+
+const DECODE_SAMPLE_RATE: usize = 8000;
+/// Level is measured every 50 ms
+const FRAME_SAMPLES: usize = 400;
+const FRAMES_PER_SECOND: usize = DECODE_SAMPLE_RATE / FRAME_SAMPLES;
+
+/// A frame this far above the noise floor counts as someone talking
+const ACTIVITY_MARGIN_DB: f32 = 10.0;
+/// Breaks are found on activity averaged over this many seconds, so a few
+/// words at the snack table don't split one
+const BREAK_SMOOTHING_SECS: usize = 30;
+/// A stretch is a break when the table is at most this active compared to
+/// the session as a whole
+const BREAK_ACTIVITY_RATIO: f32 = 0.35;
+/// Quiet stretches closer than this are one break
+const BREAK_MERGE_SECS: usize = 30;
+/// Quiet at the very start or end is setup and wrap-up, not a break
+const BREAK_EDGE_SECS: usize = 60;
+
+/// Seconds with less activity than this are a pause between scenes
+const PAUSE_ACTIVITY: f32 = 0.1;
+const MIN_PAUSE_SECS: usize = 3;
+/// How much audio either side of a pause is compared
+const SCENE_CONTEXT_SECS: usize = 120;
+
+#[derive(Debug, Deserialize)]
+pub struct DetectChaptersRequest {
+    pub session_id: String,
+    /// Shortest quiet stretch reported as a break
+    #[serde(default = "default_min_break_secs")]
+    pub min_break_secs: f64,
+    /// Scene changes are at least this far apart, and from any break
+    #[serde(default = "default_min_scene_secs")]
+    pub min_scene_secs: f64,
+    /// Scene changes scoring lower than this (0 to 1) are dropped
+    #[serde(default = "default_min_confidence")]
+    pub min_confidence: f64,
+}
+
+fn default_min_break_secs() -> f64 {
+    180.0
+}
+
+fn default_min_scene_secs() -> f64 {
+    300.0
+}
+
+fn default_min_confidence() -> f64 {
+    0.4
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterKind {
+    Break,
+    Scene,
+}
+
+impl ChapterKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ChapterKind::Break => "break",
+            ChapterKind::Scene => "scene",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectedChapter {
+    pub id: String,
+    pub start: f64,
+    pub end: f64,
+    pub kind: ChapterKind,
+    pub title: String,
+    /// How sure the detector is about the boundary this chapter starts at,
+    /// from 0 to 1
+    pub confidence: f64,
+}
+
+/// A point where one chapter ends and the next begins
+struct Boundary {
+    /// Whole seconds into the session audio
+    second: usize,
+    confidence: f64,
+}
+
+struct Break {
+    start: usize,
+    end: usize,
+    confidence: f64,
+}
+
+/// Proposes chapters for a session's audio from its level over time: long
+/// stretches where the table goes quiet are breaks, and short pauses after
+/// which the energy of the table changes are scene changes. The proposals
+/// replace the session's stored chapters; they are embedded in the audio the
+/// next time it is processed or tagged with `tag_session_audio_files`.
+#[command]
+pub async fn detect_chapters(
+    app: AppHandle,
+    request: DetectChaptersRequest,
+) -> Result<Vec<DetectedChapter>, String> {
+    let session_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?
+        .join("sessions")
+        .join(&request.session_id);
+    let audio_path = audio_processor::session_audio_path(&session_dir);
+
+    let ffmpeg_path = audio_utils::get_ffmpeg_path(&app)?;
+    let (min_break_secs, min_scene_secs, min_confidence) = (
+        request.min_break_secs,
+        request.min_scene_secs,
+        request.min_confidence,
+    );
+    let chapters = audio_utils::run_blocking(move || {
+        let frames = frame_levels(&ffmpeg_path, &audio_path)?;
+        let activity = Activity::measure(&frames);
+        let duration = frames.len() as f64 / FRAMES_PER_SECOND as f64;
+
+        let breaks = find_breaks(&activity, min_break_secs);
+        let scenes = find_scene_changes(&activity, &breaks, min_scene_secs, min_confidence);
+        Ok(build_chapters(&breaks, &scenes, duration))
+    })
+    .await?;
+
+    let pool = drizzle_proxy::pool(&app);
+    store_chapters(&pool, &request.session_id, &chapters).await?;
+
+    Ok(chapters)
+}
+
+/// Level of every 50 ms frame in dBFS
+fn frame_levels(ffmpeg_path: &Path, audio_path: &Path) -> Result<Vec<f32>, String> {
+    let mut child = Command::new(ffmpeg_path)
+        .arg("-i")
+        .arg(audio_path)
+        .args(["-vn", "-ac", "1", "-ar"])
+        .arg(DECODE_SAMPLE_RATE.to_string())
+        .args(["-f", "s16le", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    let stdout = child.stdout.take().ok_or("Failed to read FFmpeg output")?;
+    let mut reader = BufReader::new(stdout);
+    let mut buffer = vec![0u8; FRAME_SAMPLES * 2];
+    let mut levels = Vec::new();
+
+    // A trailing partial frame is dropped
+    while reader.read_exact(&mut buffer).is_ok() {
+        let sum: f64 = buffer
+            .chunks_exact(2)
+            .map(|bytes| {
+                let sample = i16::from_le_bytes([bytes[0], bytes[1]]) as f64 / 32768.0;
+                sample * sample
+            })
+            .sum();
+        let rms = (sum / FRAME_SAMPLES as f64).sqrt();
+        levels.push((20.0 * rms.max(1e-5).log10()) as f32);
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+    if !status.success() {
+        return Err(format!("FFmpeg could not decode {:?}", audio_path));
+    }
+    if levels.is_empty() {
+        return Err("Session audio is empty".to_string());
+    }
+
+    Ok(levels)
+}
+
+/// Per-second summary of how much is being said and how loudly
+struct Activity {
+    /// Share of the second's frames above the speech threshold
+    share: Vec<f32>,
+    /// Mean level of those frames, or the threshold when there are none
+    level: Vec<f32>,
+    /// Median of `share` over the whole session
+    typical_share: f32,
+}
+
+impl Activity {
+    fn measure(frames: &[f32]) -> Self {
+        let mut sorted = frames.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let noise_floor = sorted[sorted.len() / 10];
+        let threshold = noise_floor + ACTIVITY_MARGIN_DB;
+
+        let (share, level): (Vec<f32>, Vec<f32>) = frames
+            .chunks(FRAMES_PER_SECOND)
+            .map(|second| {
+                let active: Vec<f32> = second.iter().copied().filter(|&l| l > threshold).collect();
+                let share = active.len() as f32 / second.len() as f32;
+                let level = if active.is_empty() {
+                    threshold
+                } else {
+                    active.iter().sum::<f32>() / active.len() as f32
+                };
+                (share, level)
+            })
+            .unzip();
+
+        let mut sorted_share = share.clone();
+        sorted_share.sort_by(f32::total_cmp);
+        let typical_share = sorted_share[sorted_share.len() / 2].max(f32::EPSILON);
+
+        Self {
+            share,
+            level,
+            typical_share,
+        }
+    }
+}
+
+fn find_breaks(activity: &Activity, min_break_secs: f64) -> Vec<Break> {
+    let seconds = activity.share.len();
+    let smoothed = moving_average(&activity.share, BREAK_SMOOTHING_SECS);
+    let quiet_below = activity.typical_share * BREAK_ACTIVITY_RATIO;
+
+    // Quiet runs, with short interruptions bridged
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (second, &share) in smoothed.iter().enumerate() {
+        if share >= quiet_below {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if second - run.1 <= BREAK_MERGE_SECS => run.1 = second + 1,
+            _ => runs.push((second, second + 1)),
+        }
+    }
+
+    runs.into_iter()
+        .filter(|&(start, end)| {
+            (end - start) as f64 >= min_break_secs
+                && start >= BREAK_EDGE_SECS
+                && end + BREAK_EDGE_SECS <= seconds
+        })
+        .map(|(start, end)| {
+            let mean = smoothed[start..end].iter().sum::<f32>() / (end - start) as f32;
+            let depth = (1.0 - mean / quiet_below).clamp(0.0, 1.0) as f64;
+            // Ten minutes of quiet is as sure as it gets
+            let length = ((end - start) as f64 / 600.0).min(1.0);
+            Break {
+                start,
+                end,
+                confidence: 0.5 + 0.25 * depth + 0.25 * length,
+            }
+        })
+        .collect()
+}
+
+/// Scores every pause by how long it is and how different the table sounds
+/// before and after it, then keeps the best-scoring ones that are far enough
+/// apart
+fn find_scene_changes(
+    activity: &Activity,
+    breaks: &[Break],
+    min_scene_secs: f64,
+    min_confidence: f64,
+) -> Vec<Boundary> {
+    let seconds = activity.share.len();
+    let min_gap = min_scene_secs.max(0.0) as usize;
+
+    let mut candidates: Vec<Boundary> = Vec::new();
+    let mut second = 0;
+    while second < seconds {
+        if activity.share[second] >= PAUSE_ACTIVITY {
+            second += 1;
+            continue;
+        }
+        let start = second;
+        while second < seconds && activity.share[second] < PAUSE_ACTIVITY {
+            second += 1;
+        }
+        let pause = second - start;
+        if pause < MIN_PAUSE_SECS || start < SCENE_CONTEXT_SECS {
+            continue;
+        }
+        if second + SCENE_CONTEXT_SECS > seconds {
+            break;
+        }
+
+        let before = start - SCENE_CONTEXT_SECS..start;
+        let after = second..second + SCENE_CONTEXT_SECS;
+        let level_change =
+            (mean(&activity.level[after.clone()]) - mean(&activity.level[before.clone()])).abs();
+        let share_change = (mean(&activity.share[after]) - mean(&activity.share[before])).abs();
+
+        // 6 dB or a 30 point swing in how much is said is a clear change
+        let change = ((level_change / 6.0).max(share_change / 0.3)).min(1.0) as f64;
+        let length = (pause as f64 / 10.0).min(1.0);
+        candidates.push(Boundary {
+            second: start + pause / 2,
+            confidence: 0.5 * change + 0.5 * length,
+        });
+    }
+
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+    let mut accepted: Vec<Boundary> = Vec::new();
+    for candidate in candidates {
+        if candidate.confidence < min_confidence {
+            break;
+        }
+        let near_break = breaks
+            .iter()
+            .any(|b| candidate.second + min_gap > b.start && candidate.second < b.end + min_gap);
+        let near_scene = accepted
+            .iter()
+            .any(|scene| candidate.second.abs_diff(scene.second) < min_gap);
+        let near_edge = candidate.second < min_gap || candidate.second + min_gap > seconds;
+        if !near_break && !near_scene && !near_edge {
+            accepted.push(candidate);
+        }
+    }
+
+    accepted.sort_by_key(|scene| scene.second);
+    accepted
+}
+
+/// Splits the audio at every boundary. Breaks become their own chapters and
+/// everything between them is numbered as scenes.
+fn build_chapters(breaks: &[Break], scenes: &[Boundary], duration: f64) -> Vec<DetectedChapter> {
+    // Where each scene starts: the beginning, after each break and at each
+    // scene change
+    let mut starts: Vec<(f64, f64)> = vec![(0.0, 1.0)];
+    starts.extend(breaks.iter().map(|b| (b.end as f64, b.confidence)));
+    starts.extend(scenes.iter().map(|s| (s.second as f64, s.confidence)));
+    starts.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut chapters: Vec<DetectedChapter> = breaks
+        .iter()
+        .enumerate()
+        .map(|(index, b)| DetectedChapter {
+            id: uuid::Uuid::now_v7().to_string(),
+            start: b.start as f64,
+            end: b.end as f64,
+            kind: ChapterKind::Break,
+            title: format!("Break {}", index + 1),
+            confidence: b.confidence,
+        })
+        .collect();
+
+    for (index, &(start, confidence)) in starts.iter().enumerate() {
+        let end = breaks
+            .iter()
+            .map(|b| b.start as f64)
+            .chain(starts.get(index + 1).map(|next| next.0))
+            .filter(|&end| end > start)
+            .fold(duration, f64::min);
+        chapters.push(DetectedChapter {
+            id: uuid::Uuid::now_v7().to_string(),
+            start,
+            end,
+            kind: ChapterKind::Scene,
+            title: format!("Scene {}", index + 1),
+            confidence,
+        });
+    }
+
+    chapters.sort_by(|a, b| a.start.total_cmp(&b.start));
+    chapters
+}
+
+/// Replaces the session's chapters with the new proposals
+async fn store_chapters(
+    pool: &sqlx::SqlitePool,
+    session_id: &str,
+    chapters: &[DetectedChapter],
+) -> Result<(), String> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    sqlx::query("DELETE FROM chapters WHERE session_id = ?")
+        .bind(session_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to clear chapters: {}", e))?;
+
+    for chapter in chapters {
+        sqlx::query(
+            "INSERT INTO chapters (id, session_id, start_time, end_time, kind, title, confidence) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&chapter.id)
+        .bind(session_id)
+        .bind(chapter.start)
+        .bind(chapter.end)
+        .bind(chapter.kind.as_str())
+        .bind(&chapter.title)
+        .bind(chapter.confidence)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to save chapter: {}", e))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to save chapters: {}", e))
+}
+
+fn moving_average(values: &[f32], window: usize) -> Vec<f32> {
+    let half = window / 2;
+    let mut prefix = vec![0.0f64; values.len() + 1];
+    for (index, &value) in values.iter().enumerate() {
+        prefix[index + 1] = prefix[index] + value as f64;
+    }
+    (0..values.len())
+        .map(|index| {
+            let from = index.saturating_sub(half);
+            let to = (index + half + 1).min(values.len());
+            ((prefix[to] - prefix[from]) / (to - from) as f64) as f32
+        })
+        .collect()
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len().max(1) as f32
+}
@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::{command, AppHandle, Manager};
+
+use crate::audio_format::OutputOptions;
+use crate::audio_processor;
+use crate::audio_transcription;
+use crate::audio_utils;
+
+#[derive(Debug, Deserialize)]
+pub struct ClipRequest {
+    pub session_id: String,
+    /// Start of the quote in the session audio, in seconds
+    pub start_secs: f64,
+    pub end_secs: f64,
+    /// Extra audio kept before and after the quote
+    #[serde(default)]
+    pub padding_secs: f64,
+    #[serde(default)]
+    pub fade_in_secs: f64,
+    #[serde(default)]
+    pub fade_out_secs: f64,
+    #[serde(default)]
+    pub output: OutputOptions,
+    /// Where to write the clip. Defaults to `sessions/{id}/clips/{name}.{ext}`.
+    pub output_path: Option<String>,
+    /// File name for clips written to the clips folder
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClipResponse {
+    pub output_path: String,
+    /// Range actually cut from the session audio, padding included
+    pub start_secs: f64,
+    pub end_secs: f64,
+}
+
+/// Cuts a clip out of a session's audio. The archive rendition is used when
+/// there is one, since it is the better-sounding copy of the same timeline.
+#[command]
+pub async fn extract_clip(app: AppHandle, request: ClipRequest) -> Result<ClipResponse, String> {
+    audio_utils::run_blocking(move || cut_clip(app, request)).await
+}
+
+fn cut_clip(app: AppHandle, request: ClipRequest) -> Result<ClipResponse, String> {
+    if request.end_secs <= request.start_secs {
+        return Err("Clip end must be after its start".to_string());
+    }
+
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?;
+
+    let session_dir = app_data_dir.join("sessions").join(&request.session_id);
+    let source = audio_processor::load_manifest(&session_dir)
+        .ok()
+        .and_then(|manifest| manifest.archive_path)
+        .map(PathBuf::from)
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| audio_processor::session_audio_path(&session_dir));
+
+    let ffmpeg_path = audio_utils::get_ffmpeg_path(&app)?;
+    let total = audio_transcription::get_audio_duration(&ffmpeg_path, &source)?;
+
+    let padding = request.padding_secs.max(0.0);
+    let start = (request.start_secs - padding).max(0.0);
+    let end = (request.end_secs + padding).min(total);
+    if end <= start {
+        return Err(format!(
+            "Clip starts after the end of the session audio ({:.1}s)",
+            total
+        ));
+    }
+    let duration = end - start;
+
+    let output_path = match &request.output_path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let clips_dir = session_dir.join("clips");
+            std::fs::create_dir_all(&clips_dir)
+                .map_err(|e| format!("Failed to create clips directory: {}", e))?;
+            let name = request
+                .name
+                .as_deref()
+                .map(sanitize_file_name)
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| format!("clip_{:.0}-{:.0}", start, end));
+            clips_dir.join(format!("{}.{}", name, request.output.format.extension()))
+        }
+    };
+
+    // Fades are measured within the clip, which starts at zero after seeking
+    let mut filters = Vec::new();
+    let fade_in = request.fade_in_secs.clamp(0.0, duration);
+    let fade_out = request.fade_out_secs.clamp(0.0, duration);
+    if fade_in > 0.0 {
+        filters.push(format!("afade=t=in:st=0:d={:.3}", fade_in));
+    }
+    if fade_out > 0.0 {
+        filters.push(format!(
+            "afade=t=out:st={:.3}:d={:.3}",
+            duration - fade_out,
+            fade_out
+        ));
+    }
+
+    extract_audio_segment(
+        &ffmpeg_path,
+        &source,
+        &output_path,
+        start,
+        duration,
+        Some((&filters, &request.output)),
+    )
+    .map_err(|e| format!("Failed to extract clip: {}", e))?;
+
+    Ok(ClipResponse {
+        output_path: output_path.to_string_lossy().to_string(),
+        start_secs: start,
+        end_secs: end,
+    })
+}
+
+/// Cuts `duration` seconds starting at `start_time` out of `input`.
+///
+/// Seeking happens on the input side, so FFmpeg jumps close to the start
+/// without decoding everything before it. With `encode` set the audio is
+/// decoded and re-encoded, which makes the cut sample-accurate and lets
+/// filters run; without it the packets are copied as they are, which is
+/// faster but only accurate to the codec's frame size.
+pub fn extract_audio_segment(
+    ffmpeg_path: &Path,
+    input: &Path,
+    output: &Path,
+    start_time: f64,
+    duration: f64,
+    encode: Option<(&[String], &OutputOptions)>,
+) -> Result<(), String> {
+    if start_time < 0.0 {
+        return Err("Start time cannot be negative".to_string());
+    }
+    if duration <= 0.0 {
+        return Err(format!("Duration must be positive, got: {}", duration));
+    }
+
+    let mut command = Command::new(ffmpeg_path);
+    command
+        .args(["-ss", &format!("{:.3}", start_time)])
+        .arg("-i")
+        .arg(input)
+        .args(["-t", &format!("{:.3}", duration)]);
+
+    match encode {
+        Some((filters, options)) => {
+            if !filters.is_empty() {
+                command.args(["-af", &filters.join(",")]);
+            }
+            command.args(options.encode_args());
+        }
+        None => {
+            command.args(["-vn", "-acodec", "copy", "-avoid_negative_ts", "make_zero"]);
+        }
+    }
+
+    let output = command
+        .arg("-y")
+        .arg(output)
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "FFmpeg extraction failed. Exit code: {:?}. Stderr: {}",
+            output.status.code(),
+            stderr
+        ));
+    }
+    Ok(())
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
@@ -0,0 +1,347 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::process::Command;
+
+/// Optional clean-up applied to every input before it is joined or mixed.
+/// Filters run in field order, with loudness normalization last.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ProcessingProfile {
+    /// Cut rumble, fan and AC hum below this frequency (Hz)
+    pub highpass_hz: Option<u32>,
+    /// Cut hiss above this frequency (Hz)
+    pub lowpass_hz: Option<u32>,
+    /// Noise reduction strength in dB for the FFT denoiser (`afftdn`)
+    pub noise_reduction_db: Option<f64>,
+    /// Gentle compressor that evens out loud and quiet speakers
+    #[serde(default)]
+    pub compressor: bool,
+    /// Two-pass EBU R128 loudness normalization
+    pub loudnorm: Option<LoudnormTarget>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoudnormTarget {
+    /// Integrated loudness target in LUFS
+    #[serde(default = "default_integrated")]
+    pub integrated: f64,
+    /// Maximum true peak in dBTP
+    #[serde(default = "default_true_peak")]
+    pub true_peak: f64,
+    /// Loudness range target in LU
+    #[serde(default = "default_loudness_range")]
+    pub loudness_range: f64,
+}
+
+fn default_integrated() -> f64 {
+    -16.0
+}
+
+fn default_true_peak() -> f64 {
+    -1.5
+}
+
+fn default_loudness_range() -> f64 {
+    11.0
+}
+
+impl Default for LoudnormTarget {
+    fn default() -> Self {
+        Self {
+            integrated: default_integrated(),
+            true_peak: default_true_peak(),
+            loudness_range: default_loudness_range(),
+        }
+    }
+}
+
+impl LoudnormTarget {
+    /// Second, normalizing `loudnorm` pass for an input measured as `stats`
+    fn filter(&self, stats: &LoudnessStats) -> String {
+        format!(
+            "loudnorm=I={}:TP={}:LRA={}:measured_I={}:measured_TP={}:measured_LRA={}:\
+             measured_thresh={}:offset={}:linear=true",
+            self.integrated,
+            self.true_peak,
+            self.loudness_range,
+            stats.input_i,
+            stats.input_tp,
+            stats.input_lra,
+            stats.input_thresh,
+            stats.target_offset
+        )
+    }
+}
+
+/// Loudness statistics reported by FFmpeg's `loudnorm` filter in analysis mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoudnessStats {
+    /// Integrated loudness in LUFS
+    pub input_i: f64,
+    /// True peak in dBTP
+    pub input_tp: f64,
+    /// Loudness range in LU
+    pub input_lra: f64,
+    pub input_thresh: f64,
+    pub target_offset: f64,
+}
+
+impl ProcessingProfile {
+    /// Filters that run before loudness normalization
+    fn cleanup_filters(&self) -> Vec<String> {
+        let mut filters = Vec::new();
+
+        if let Some(hz) = self.highpass_hz {
+            filters.push(format!("highpass=f={}", hz));
+        }
+        if let Some(hz) = self.lowpass_hz {
+            filters.push(format!("lowpass=f={}", hz));
+        }
+        if let Some(nr) = self.noise_reduction_db {
+            // afftdn accepts 0.01 to 97 dB
+            filters.push(format!("afftdn=nr={:.2}", nr.clamp(0.01, 97.0)));
+        }
+        if self.compressor {
+            filters.push(
+                "acompressor=threshold=-21dB:ratio=3:attack=20:release=250:makeup=2".to_string(),
+            );
+        }
+
+        filters
+    }
+
+    /// Builds the full filter chain for one input. With loudness normalization
+    /// enabled this runs the measuring pass first and returns its statistics.
+    pub fn input_filters(
+        &self,
+        ffmpeg_path: &Path,
+        input: &Path,
+    ) -> Result<(Vec<String>, Option<LoudnessStats>), String> {
+        let mut filters = self.cleanup_filters();
+
+        let Some(target) = &self.loudnorm else {
+            return Ok((filters, None));
+        };
+
+        let stats = measure_loudness(ffmpeg_path, input, &filters, target)?;
+        match &stats {
+            Some(stats) => filters.push(target.filter(stats)),
+            // Nothing above the gate to measure, so there is nothing to normalize
+            None => eprintln!(
+                "Skipping loudness normalization for silent input {:?}",
+                input
+            ),
+        }
+
+        Ok((filters, stats))
+    }
+}
+
+/// Measures the EBU R128 loudness of an audio file after `pre_filters` are
+/// applied. Returns `None` when the whole file sits below the loudness gate.
+pub fn measure_loudness(
+    ffmpeg_path: &Path,
+    input: &Path,
+    pre_filters: &[String],
+    target: &LoudnormTarget,
+) -> Result<Option<LoudnessStats>, String> {
+    let mut filters = pre_filters.to_vec();
+    filters.push(format!(
+        "loudnorm=I={}:TP={}:LRA={}:print_format=json",
+        target.integrated, target.true_peak, target.loudness_range
+    ));
+
+    let output = Command::new(ffmpeg_path)
+        .arg("-i")
+        .arg(input)
+        .args(["-vn", "-af", &filters.join(","), "-f", "null", "-"])
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(format!("FFmpeg loudness scan failed: {}", stderr));
+    }
+
+    parse_loudnorm_output(&stderr)
+}
+
+fn parse_loudnorm_output(stderr: &str) -> Result<Option<LoudnessStats>, String> {
+    // loudnorm prints a JSON object as the last thing on stderr, with every
+    // value as a string ("-23.45", or "-inf" for silence)
+    let start = stderr
+        .rfind('{')
+        .ok_or("FFmpeg loudness scan produced no summary")?;
+    let end = stderr[start..]
+        .find('}')
+        .map(|pos| start + pos + 1)
+        .ok_or("FFmpeg loudness summary is incomplete")?;
+
+    let summary: Value = serde_json::from_str(&stderr[start..end])
+        .map_err(|e| format!("Failed to parse loudness summary: {}", e))?;
+
+    let field = |name: &str| {
+        summary
+            .get(name)
+            .and_then(Value::as_str)
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|v| v.is_finite())
+    };
+
+    let (Some(input_i), Some(input_tp), Some(input_lra), Some(input_thresh)) = (
+        field("input_i"),
+        field("input_tp"),
+        field("input_lra"),
+        field("input_thresh"),
+    ) else {
+        return Ok(None);
+    };
+
+    // -70 LUFS is the absolute gate; anything at or below it is silence
+    if input_i <= -70.0 {
+        return Ok(None);
+    }
+
+    Ok(Some(LoudnessStats {
+        input_i,
+        input_tp,
+        input_lra,
+        input_thresh,
+        target_offset: field("target_offset").unwrap_or(0.0),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// stderr of `ffmpeg -i talk.flac -af loudnorm=...:print_format=json -f null -`
+    const SCAN_OUTPUT: &str = r#"Input #0, flac, from 'talk.flac':
+  Metadata:
+    comment         : {"recorder":"craig"}
+  Duration: 00:42:13.52, start: 0.000000, bitrate: 612 kb/s
+  Stream #0:0: Audio: flac, 48000 Hz, stereo, s16
+Stream mapping:
+  Stream #0:0 -> #0:0 (flac (native) -> pcm_s16le (native))
+Press [q] to stop, [?] for help
+Output #0, null, to 'pipe:':
+  Stream #0:0: Audio: pcm_s16le, 192000 Hz, stereo, s16, 6144 kb/s
+[out#0/null @ 0x600003a1c000] video:0KiB audio:1945632KiB subtitle:0KiB other streams:0KiB global headers:0KiB muxing overhead: unknown
+size=N/A time=00:42:13.52 bitrate=N/A speed= 118x
+[Parsed_loudnorm_0 @ 0x600002d18160] 
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.02",
+	"input_lra" : "9.30",
+	"input_thresh" : "-38.12",
+	"output_i" : "-16.38",
+	"output_tp" : "-1.50",
+	"output_lra" : "7.40",
+	"output_thresh" : "-26.85",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.38"
+}
+"#;
+
+    /// The same scan of a file that is silent throughout
+    const SILENT_SCAN_OUTPUT: &str = r#"size=N/A time=00:00:30.00 bitrate=N/A speed= 910x
+[Parsed_loudnorm_0 @ 0x600002d18160] 
+{
+	"input_i" : "-inf",
+	"input_tp" : "-inf",
+	"input_lra" : "0.00",
+	"input_thresh" : "-inf",
+	"output_i" : "-inf",
+	"output_tp" : "-inf",
+	"output_lra" : "0.00",
+	"output_thresh" : "-inf",
+	"normalization_type" : "dynamic",
+	"target_offset" : "inf"
+}
+"#;
+
+    #[test]
+    fn reads_the_summary_among_log_lines() {
+        let stats = parse_loudnorm_output(SCAN_OUTPUT).unwrap().unwrap();
+        assert_eq!(stats.input_i, -27.61);
+        assert_eq!(stats.input_tp, -4.02);
+        assert_eq!(stats.input_lra, 9.3);
+        assert_eq!(stats.input_thresh, -38.12);
+        assert_eq!(stats.target_offset, 0.38);
+    }
+
+    #[test]
+    fn silent_input_has_nothing_to_normalize() {
+        assert!(parse_loudnorm_output(SILENT_SCAN_OUTPUT).unwrap().is_none());
+
+        // Quiet enough to sit below the absolute gate
+        let gated = SCAN_OUTPUT.replace("\"-27.61\"", "\"-70.00\"");
+        assert!(parse_loudnorm_output(&gated).unwrap().is_none());
+    }
+
+    #[test]
+    fn a_missing_target_offset_counts_as_zero() {
+        let output = SCAN_OUTPUT.replace(",\n\t\"target_offset\" : \"0.38\"", "");
+        let stats = parse_loudnorm_output(&output).unwrap().unwrap();
+        assert_eq!(stats.target_offset, 0.0);
+    }
+
+    #[test]
+    fn a_scan_without_summary_is_an_error() {
+        let cut = &SCAN_OUTPUT[..SCAN_OUTPUT.rfind('}').unwrap()];
+        assert!(parse_loudnorm_output(cut).is_err());
+        assert!(parse_loudnorm_output("size=N/A time=00:00:01.00").is_err());
+    }
+
+    #[test]
+    fn cleanup_filters_run_in_field_order() {
+        let profile = ProcessingProfile {
+            highpass_hz: Some(80),
+            lowpass_hz: Some(12000),
+            noise_reduction_db: Some(12.0),
+            compressor: true,
+            loudnorm: None,
+        };
+        assert_eq!(
+            profile.cleanup_filters(),
+            [
+                "highpass=f=80",
+                "lowpass=f=12000",
+                "afftdn=nr=12.00",
+                "acompressor=threshold=-21dB:ratio=3:attack=20:release=250:makeup=2",
+            ]
+        );
+
+        // Without loudness normalization no scan is needed, so FFmpeg isn't run
+        let (filters, stats) = profile
+            .input_filters(Path::new("/nonexistent/ffmpeg"), Path::new("talk.flac"))
+            .unwrap();
+        assert_eq!(filters, profile.cleanup_filters());
+        assert!(stats.is_none());
+    }
+
+    #[test]
+    fn an_empty_profile_adds_no_filters() {
+        assert!(ProcessingProfile::default().cleanup_filters().is_empty());
+    }
+
+    #[test]
+    fn noise_reduction_stays_in_the_range_afftdn_accepts() {
+        let profile = |nr| ProcessingProfile {
+            noise_reduction_db: Some(nr),
+            ..Default::default()
+        };
+        assert_eq!(profile(0.0).cleanup_filters(), ["afftdn=nr=0.01"]);
+        assert_eq!(profile(120.0).cleanup_filters(), ["afftdn=nr=97.00"]);
+    }
+
+    #[test]
+    fn loudnorm_second_pass_uses_the_measurement() {
+        let stats = parse_loudnorm_output(SCAN_OUTPUT).unwrap().unwrap();
+        assert_eq!(
+            LoudnormTarget::default().filter(&stats),
+            "loudnorm=I=-16:TP=-1.5:LRA=11:measured_I=-27.61:measured_TP=-4.02:\
+             measured_LRA=9.3:measured_thresh=-38.12:offset=0.38:linear=true"
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioFormat {
+    #[default]
+    Mp3,
+    Opus,
+    Aac,
+    Flac,
+    Wav,
+}
+
+impl AudioFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Aac => "m4a",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "wav",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "mp3" => Some(AudioFormat::Mp3),
+            "opus" | "ogg" => Some(AudioFormat::Opus),
+            "m4a" | "aac" => Some(AudioFormat::Aac),
+            "flac" => Some(AudioFormat::Flac),
+            "wav" => Some(AudioFormat::Wav),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "audio/mpeg",
+            AudioFormat::Opus => "audio/ogg",
+            AudioFormat::Aac => "audio/mp4",
+            AudioFormat::Flac => "audio/flac",
+            AudioFormat::Wav => "audio/wav",
+        }
+    }
+
+    fn codec(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "libmp3lame",
+            AudioFormat::Opus => "libopus",
+            AudioFormat::Aac => "aac",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "pcm_s16le",
+        }
+    }
+
+    pub fn is_lossless(&self) -> bool {
+        matches!(self, AudioFormat::Flac | AudioFormat::Wav)
+    }
+}
+
+/// Encoding settings for one rendition of the session audio
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputOptions {
+    #[serde(default)]
+    pub format: AudioFormat,
+    /// Bitrate in kbps; ignored for lossless formats
+    #[serde(default = "default_bitrate_kbps")]
+    pub bitrate_kbps: u32,
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    #[serde(default = "default_channels")]
+    pub channels: u32,
+}
+
+fn default_bitrate_kbps() -> u32 {
+    192
+}
+
+fn default_sample_rate() -> u32 {
+    44100
+}
+
+fn default_channels() -> u32 {
+    2
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            format: AudioFormat::default(),
+            bitrate_kbps: default_bitrate_kbps(),
+            sample_rate: default_sample_rate(),
+            channels: default_channels(),
+        }
+    }
+}
+
+impl OutputOptions {
+    /// Lossless working format used between processing steps, so the final
+    /// renditions are only encoded once
+    pub fn intermediate(sample_rate: u32, channels: u32) -> Self {
+        Self {
+            format: AudioFormat::Wav,
+            bitrate_kbps: 0,
+            sample_rate,
+            channels,
+        }
+    }
+
+    /// Rough size of `duration` seconds of audio in this format. FLAC is taken
+    /// to shrink speech to about 60% of WAV.
+    pub fn estimated_bytes(&self, duration: f64) -> u64 {
+        let pcm_bytes = duration * self.effective_sample_rate() as f64 * self.channels as f64 * 2.0;
+        let bytes = match self.format {
+            AudioFormat::Wav => pcm_bytes,
+            AudioFormat::Flac => pcm_bytes * 0.6,
+            _ => duration * self.bitrate_kbps as f64 * 1000.0 / 8.0,
+        };
+        bytes.ceil() as u64
+    }
+
+    /// Opus only supports a fixed set of sample rates
+    fn effective_sample_rate(&self) -> u32 {
+        if self.format == AudioFormat::Opus
+            && !matches!(self.sample_rate, 8000 | 12000 | 16000 | 24000 | 48000)
+        {
+            48000
+        } else {
+            self.sample_rate
+        }
+    }
+
+    /// FFmpeg output arguments (codec, sample rate, channels and bitrate)
+    pub fn encode_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-vn".to_string(),
+            "-acodec".to_string(),
+            self.format.codec().to_string(),
+            "-ar".to_string(),
+            self.effective_sample_rate().to_string(),
+            "-ac".to_string(),
+            self.channels.to_string(),
+        ];
+
+        if !self.format.is_lossless() {
+            args.push("-b:a".to_string());
+            args.push(format!("{}k", self.bitrate_kbps));
+        }
+
+        // Plain RIFF WAV tops out at 4 GiB, which a long multi-track session
+        // can pass. RF64 takes over only when it is needed.
+        if self.format == AudioFormat::Wav {
+            args.push("-rf64".to_string());
+            args.push("auto".to_string());
+        }
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions_round_trip() {
+        for format in [
+            AudioFormat::Mp3,
+            AudioFormat::Opus,
+            AudioFormat::Aac,
+            AudioFormat::Flac,
+            AudioFormat::Wav,
+        ] {
+            assert_eq!(
+                AudioFormat::from_extension(format.extension()),
+                Some(format)
+            );
+        }
+        assert_eq!(AudioFormat::from_extension("OGG"), Some(AudioFormat::Opus));
+        assert_eq!(AudioFormat::from_extension("webm"), None);
+    }
+
+    #[test]
+    fn intermediates_are_wav_that_can_grow_past_4_gib() {
+        assert_eq!(
+            OutputOptions::intermediate(48000, 2).encode_args(),
+            [
+                "-vn",
+                "-acodec",
+                "pcm_s16le",
+                "-ar",
+                "48000",
+                "-ac",
+                "2",
+                "-rf64",
+                "auto"
+            ]
+        );
+    }
+
+    #[test]
+    fn lossy_formats_carry_a_bitrate() {
+        let options = OutputOptions {
+            format: AudioFormat::Opus,
+            bitrate_kbps: 32,
+            sample_rate: 44100,
+            channels: 1,
+        };
+        assert_eq!(
+            options.encode_args(),
+            ["-vn", "-acodec", "libopus", "-ar", "48000", "-ac", "1", "-b:a", "32k"]
+        );
+    }
+
+    #[test]
+    fn opus_is_sent_as_ogg() {
+        assert_eq!(AudioFormat::Opus.mime_type(), "audio/ogg");
+        assert_eq!(AudioFormat::Aac.mime_type(), "audio/mp4");
+    }
+}
@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::{command, AppHandle, Manager};
+
+use crate::audio_processor;
+use crate::audio_transcription;
+use crate::audio_utils;
+use crate::drizzle_proxy;
+
+/// A detected chapter this close to the start of an input marks the same
+/// break, so the input's own chapter is kept
+const DUPLICATE_CHAPTER_SECS: f64 = 30.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start: f64,
+    pub end: f64,
+    pub title: String,
+}
+
+/// Embeds the session's current tags and chapters in its audio, e.g. before
+/// exporting it after chapters were detected or edited
+#[command]
+pub async fn tag_session_audio_files(app: AppHandle, session_id: String) -> Result<(), String> {
+    tag_session_audio(&app, &session_id).await
+}
+
+/// Writes the session's tags and chapters into every rendition of its audio,
+/// so the files still say what they are once they leave the app. The DM is
+/// the artist, the campaign the album and the session number the track.
+pub async fn tag_session_audio(app: &AppHandle, session_id: &str) -> Result<(), String> {
+    let session_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?
+        .join("sessions")
+        .join(session_id);
+    let manifest = audio_processor::load_manifest(&session_dir)?;
+
+    let pool = drizzle_proxy::pool(app);
+    let tags = session_tags(&pool, session_id).await?;
+    let chapters = merge_chapters(
+        &manifest.chapters,
+        stored_chapters(&pool, session_id).await?,
+    );
+
+    let ffmpeg_path = audio_utils::get_ffmpeg_path(app)?;
+    audio_utils::run_blocking(move || {
+        write_tags(&ffmpeg_path, &session_dir, manifest, &tags, &chapters)
+    })
+    .await
+}
+
+fn write_tags(
+    ffmpeg_path: &Path,
+    session_dir: &Path,
+    manifest: audio_processor::ProcessingManifest,
+    tags: &[(&str, String)],
+    chapters: &[Chapter],
+) -> Result<(), String> {
+    let renditions = std::iter::once(manifest.output_path)
+        .chain(manifest.archive_path)
+        .map(PathBuf::from)
+        .filter(|path| path.exists());
+
+    let metadata_path = session_dir.join("metadata.txt");
+    for path in renditions {
+        let duration = audio_transcription::get_audio_duration(ffmpeg_path, &path)?;
+        std::fs::write(
+            &metadata_path,
+            ffmetadata(tags, &sequence(chapters, duration)),
+        )
+        .map_err(|e| format!("Failed to write metadata: {}", e))?;
+
+        // FFmpeg can't write in place; the extension picks the muxer
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        let tagged_path = path.with_extension(format!("tagged.{}", extension));
+        let result =
+            apply_metadata(ffmpeg_path, &path, &metadata_path, &tagged_path).and_then(|_| {
+                std::fs::rename(&tagged_path, &path)
+                    .map_err(|e| format!("Failed to replace {:?}: {}", path, e))
+            });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tagged_path);
+        }
+        result?;
+    }
+
+    let _ = std::fs::remove_file(&metadata_path);
+    Ok(())
+}
+
+async fn session_tags(
+    pool: &sqlx::SqlitePool,
+    session_id: &str,
+) -> Result<Vec<(&'static str, String)>, String> {
+    let row = sqlx::query_as::<_, (i64, Option<String>, String, String, String)>(
+        "SELECT s.number, s.name, s.date, c.name, c.dm_name FROM sessions s \
+         JOIN campaigns c ON c.id = s.campaign_id WHERE s.id = ?",
+    )
+    .bind(session_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to load session: {}", e))?;
+
+    let Some((number, name, date, campaign, dm_name)) = row else {
+        return Ok(Vec::new());
+    };
+    let title = match name.filter(|name| !name.is_empty()) {
+        Some(name) => format!("Session {}: {}", number, name),
+        None => format!("Session {}", number),
+    };
+
+    Ok(vec![
+        ("title", title),
+        ("album", campaign),
+        ("artist", dm_name.clone()),
+        ("album_artist", dm_name),
+        ("track", number.to_string()),
+        ("date", date),
+    ])
+}
+
+/// Breaks and scenes found by chapter detection
+async fn stored_chapters(
+    pool: &sqlx::SqlitePool,
+    session_id: &str,
+) -> Result<Vec<Chapter>, String> {
+    let rows = sqlx::query_as::<_, (f64, f64, String)>(
+        "SELECT start_time, end_time, title FROM chapters WHERE session_id = ?",
+    )
+    .bind(session_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to load chapters: {}", e))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(start, end, title)| Chapter { start, end, title })
+        .collect())
+}
+
+/// The inputs' chapters plus the detected ones that don't duplicate them
+fn merge_chapters(inputs: &[Chapter], detected: Vec<Chapter>) -> Vec<Chapter> {
+    let mut chapters = inputs.to_vec();
+    chapters.extend(detected.into_iter().filter(|chapter| {
+        inputs
+            .iter()
+            .all(|input| (input.start - chapter.start).abs() >= DUPLICATE_CHAPTER_SECS)
+    }));
+    chapters
+}
+
+/// Players expect chapters to follow one another, so each one runs until the
+/// next starts and the last until the end of the audio. Chapters starting
+/// within a second of the one before are dropped.
+fn sequence(chapters: &[Chapter], duration: f64) -> Vec<Chapter> {
+    let mut sorted: Vec<Chapter> = chapters
+        .iter()
+        .filter(|chapter| chapter.start < duration)
+        .cloned()
+        .collect();
+    sorted.sort_by(|a, b| a.start.total_cmp(&b.start));
+    sorted.dedup_by(|next, previous| next.start - previous.start < 1.0);
+
+    let starts: Vec<f64> = sorted.iter().map(|chapter| chapter.start).collect();
+    for (index, chapter) in sorted.iter_mut().enumerate() {
+        chapter.end = starts.get(index + 1).copied().unwrap_or(duration);
+    }
+    sorted
+}
+
+/// Builds an FFmpeg metadata file (`;FFMETADATA1`) with global tags and
+/// millisecond-precision chapters
+pub fn ffmetadata(tags: &[(&str, String)], chapters: &[Chapter]) -> String {
+    let mut content = String::from(";FFMETADATA1\n");
+    for (key, value) in tags {
+        content.push_str(&format!("{}={}\n", key, escape(value)));
+    }
+    for chapter in chapters {
+        content.push_str(&format!(
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (chapter.start * 1000.0).round() as u64,
+            (chapter.end * 1000.0).round() as u64,
+            escape(&chapter.title)
+        ));
+    }
+    content
+}
+
+/// Special characters in metadata values must be backslash-escaped
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Copies `input` to `output` with the tags and chapters from an FFmpeg
+/// metadata file, without re-encoding. MP3s get ID3v2.3 tags, with chapters as
+/// CHAP/CTOC frames.
+pub fn apply_metadata(
+    ffmpeg_path: &Path,
+    input: &Path,
+    metadata_path: &Path,
+    output: &Path,
+) -> Result<(), String> {
+    let mut command = Command::new(ffmpeg_path);
+    command
+        .arg("-i")
+        .arg(input)
+        .arg("-i")
+        .arg(metadata_path)
+        .args(["-map", "0:a", "-map_metadata", "1", "-map_chapters", "1"])
+        .args(["-c", "copy"]);
+
+    let is_mp3 = output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("mp3"));
+    if is_mp3 {
+        command.args(["-id3v2_version", "3"]);
+    }
+
+    let result = command
+        .arg("-y")
+        .arg(output)
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(format!("FFmpeg failed to write metadata: {}", stderr));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(start: f64, title: &str) -> Chapter {
+        Chapter {
+            start,
+            end: start,
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn merge_chapters_drops_detected_chapters_at_input_boundaries() {
+        let inputs = [chapter(0.0, "part1"), chapter(3600.0, "part2")];
+        let detected = vec![
+            chapter(0.0, "Start"),
+            chapter(1800.0, "Scene"),
+            chapter(3612.0, "Break"),
+        ];
+
+        let titles: Vec<String> = merge_chapters(&inputs, detected)
+            .into_iter()
+            .map(|chapter| chapter.title)
+            .collect();
+        assert_eq!(titles, ["part1", "part2", "Scene"]);
+    }
+}
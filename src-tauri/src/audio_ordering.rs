@@ -0,0 +1,458 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+
+use crate::audio_silence;
+use crate::date_utils;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputOrder {
+    /// Keep the order the files were picked in
+    #[default]
+    AsGiven,
+    /// `creation_time` stored in the container by cameras, phones and OBS
+    CreationTime,
+    /// File modification time, which is when the recording stopped
+    ModifiedTime,
+    /// A timestamp in the file name, e.g. `2024-05-01 19-02-11.mkv` (OBS) or
+    /// `REC_20240501_190211.m4a`
+    FilenameTimestamp,
+}
+
+/// One input's place on the session timeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    pub path: String,
+    /// When the recording started (Unix seconds), if the ordering strategy
+    /// could tell
+    pub recorded_at: Option<f64>,
+    pub duration: Option<f64>,
+    /// Time between the end of the previous input and the start of this one;
+    /// negative when they overlap
+    pub gap_secs: Option<f64>,
+    /// Silence inserted before this input
+    #[serde(default)]
+    pub inserted_silence_secs: f64,
+    /// Audio dropped from the start of this input because the previous one
+    /// already covers it
+    #[serde(default)]
+    pub trimmed_secs: f64,
+}
+
+/// Sorts the inputs by `order` and lays them out on a timeline. Inputs with no
+/// usable timestamp keep their relative order after the ones that have one.
+pub fn build_timeline(
+    ffmpeg_path: &Path,
+    file_paths: &[String],
+    order: InputOrder,
+) -> Vec<TimelineEntry> {
+    let mut entries: Vec<TimelineEntry> = file_paths
+        .iter()
+        .map(|path| {
+            let header = probe(ffmpeg_path, Path::new(path));
+            let duration = header.as_deref().and_then(audio_silence::parse_duration);
+            let recorded_at = match order {
+                InputOrder::AsGiven => None,
+                InputOrder::CreationTime => header.as_deref().and_then(parse_creation_time),
+                InputOrder::ModifiedTime => {
+                    modified_time(Path::new(path)).map(|stopped| stopped - duration.unwrap_or(0.0))
+                }
+                InputOrder::FilenameTimestamp => Path::new(path)
+                    .file_stem()
+                    .and_then(|stem| parse_filename_timestamp(&stem.to_string_lossy())),
+            };
+
+            if order != InputOrder::AsGiven && recorded_at.is_none() {
+                eprintln!("No recording time found for {}, keeping it last", path);
+            }
+
+            TimelineEntry {
+                path: path.clone(),
+                recorded_at,
+                duration,
+                gap_secs: None,
+                inserted_silence_secs: 0.0,
+                trimmed_secs: 0.0,
+            }
+        })
+        .collect();
+
+    order_timeline(&mut entries, order);
+    entries
+}
+
+/// Sorts entries by recording time (unless `order` keeps them as given) and
+/// works out the gap before each one
+fn order_timeline(entries: &mut [TimelineEntry], order: InputOrder) {
+    if order != InputOrder::AsGiven {
+        entries.sort_by(|a, b| match (a.recorded_at, b.recorded_at) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+    }
+
+    for index in 1..entries.len() {
+        let previous = &entries[index - 1];
+        let previous_end = previous
+            .recorded_at
+            .zip(previous.duration)
+            .map(|(start, duration)| start + duration);
+        entries[index].gap_secs = previous_end
+            .zip(entries[index].recorded_at)
+            .map(|(end, start)| start - end);
+    }
+}
+
+/// Fills gaps with silence and drops overlapping audio so the joined output
+/// follows wall-clock time. Gaps longer than `max_gap_secs` (e.g. recordings
+/// from different days) are left closed.
+pub fn fill_gaps(entries: &mut [TimelineEntry], max_gap_secs: f64) {
+    for entry in entries.iter_mut() {
+        match entry.gap_secs {
+            Some(gap) if gap > 0.0 && gap <= max_gap_secs => entry.inserted_silence_secs = gap,
+            Some(gap) if gap < 0.0 => {
+                entry.trimmed_secs = (-gap).min(entry.duration.unwrap_or(f64::MAX));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl TimelineEntry {
+    /// Filters that go before an input's own filter chain
+    pub fn leading_filters(&self) -> Vec<String> {
+        if self.trimmed_secs > 0.0 {
+            vec![format!(
+                "atrim=start={:.3},asetpts=PTS-STARTPTS",
+                self.trimmed_secs
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Filters that go after an input's own filter chain
+    pub fn trailing_filters(&self) -> Vec<String> {
+        if self.inserted_silence_secs > 0.0 {
+            vec![format!(
+                "adelay=delays={}:all=1",
+                (self.inserted_silence_secs * 1000.0).round() as u64
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+fn probe(ffmpeg_path: &Path, input: &Path) -> Option<String> {
+    let output = Command::new(ffmpeg_path)
+        .arg("-i")
+        .arg(input)
+        .output()
+        .ok()?;
+    Some(String::from_utf8_lossy(&output.stderr).to_string())
+}
+
+fn modified_time(path: &Path) -> Option<f64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs_f64())
+}
+
+/// Reads `creation_time   : 2024-05-01T19:02:11.000000Z` from FFmpeg's
+/// description of the input. A trailing UTC offset (`+02:00`, `-0500`) is
+/// applied; without one the time is taken to be UTC.
+fn parse_creation_time(stderr: &str) -> Option<f64> {
+    let value = stderr
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("creation_time"))?
+        .trim_start()
+        .strip_prefix(':')?
+        .trim();
+
+    let mut digits = String::new();
+    let mut end = value.len();
+    for (index, c) in value.char_indices() {
+        if digits.len() == 14 {
+            end = index;
+            break;
+        }
+        match c {
+            '0'..='9' => digits.push(c),
+            '-' | ':' | 'T' | ' ' => {}
+            _ => return None,
+        }
+    }
+    let seconds = timestamp_from_digits(&digits)?;
+
+    let mut rest = &value[end..];
+    let mut fraction = 0.0;
+    if let Some(after_dot) = rest.strip_prefix('.') {
+        let len = after_dot
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after_dot.len());
+        fraction = format!("0.{}", &after_dot[..len]).parse().unwrap_or(0.0);
+        rest = &after_dot[len..];
+    }
+
+    Some(seconds + fraction - utc_offset(rest.trim())?)
+}
+
+/// Seconds ahead of UTC for a `Z`, `+hh`, `+hhmm` or `+hh:mm` suffix
+fn utc_offset(suffix: &str) -> Option<f64> {
+    if suffix.is_empty() || suffix == "Z" {
+        return Some(0.0);
+    }
+    let sign = match suffix.chars().next()? {
+        '+' => 1.0,
+        '-' => -1.0,
+        _ => return None,
+    };
+    let digits = suffix[1..].replacen(':', "", 1);
+    if !matches!(digits.len(), 2 | 4) || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: f64 = digits[..2].parse().ok()?;
+    let minutes: f64 = digits[2..].parse().unwrap_or(0.0);
+    if hours > 14.0 || minutes > 59.0 {
+        return None;
+    }
+    Some(sign * (hours * 3600.0 + minutes * 60.0))
+}
+
+/// Finds the first run of 14 digits (`YYYYMMDDhhmmss`) in a file name,
+/// allowing the usual separators between them. File names carry local time,
+/// which is fine for ordering and gaps as long as every input uses it.
+fn parse_filename_timestamp(stem: &str) -> Option<f64> {
+    let mut digits = String::new();
+    let chars: Vec<char> = stem.chars().collect();
+
+    for (index, c) in chars.iter().enumerate() {
+        if c.is_ascii_digit() {
+            digits.push(*c);
+            if digits.len() == 14 {
+                if let Some(timestamp) = timestamp_from_digits(&digits) {
+                    return Some(timestamp);
+                }
+                digits.remove(0);
+            }
+        } else {
+            let joins_digits = matches!(c, '-' | '_' | ' ' | '.' | 'T' | ':')
+                && index > 0
+                && chars[index - 1].is_ascii_digit()
+                && chars.get(index + 1).is_some_and(|n| n.is_ascii_digit());
+            if !joins_digits {
+                digits.clear();
+            }
+        }
+    }
+
+    None
+}
+
+/// `YYYYMMDDhhmmss` to Unix seconds, rejecting out-of-range fields
+fn timestamp_from_digits(digits: &str) -> Option<f64> {
+    if digits.len() < 14 {
+        return None;
+    }
+    let field = |range: std::ops::Range<usize>| digits[range].parse::<i64>().ok();
+    let (year, month, day) = (field(0..4)?, field(4..6)?, field(6..8)?);
+    let (hour, minute, second) = (field(8..10)?, field(10..12)?, field(12..14)?);
+
+    if !(1990..=2100).contains(&year)
+        || !(1..=12).contains(&month)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    // Days past the end of the month (e.g. 02-30) don't survive the round trip
+    let days = date_utils::days_from_civil(year, month, day);
+    if date_utils::civil_from_days(days) != (year, month, day) {
+        return None;
+    }
+    Some((days * 86400 + hour * 3600 + minute * 60 + second) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-05-01T19:02:11Z
+    const MAY_DAY: f64 = 1714590131.0;
+
+    fn entry(path: &str, recorded_at: Option<f64>, duration: Option<f64>) -> TimelineEntry {
+        TimelineEntry {
+            path: path.to_string(),
+            recorded_at,
+            duration,
+            gap_secs: None,
+            inserted_silence_secs: 0.0,
+            trimmed_secs: 0.0,
+        }
+    }
+
+    fn creation_time(value: &str) -> Option<f64> {
+        parse_creation_time(&format!(
+            "  Metadata:\n    major_brand     : isom\n    creation_time   : {}\n",
+            value
+        ))
+    }
+
+    #[test]
+    fn filename_timestamps_in_common_patterns() {
+        for stem in [
+            "2024-05-01 19-02-11",
+            "REC_20240501_190211",
+            "20240501190211",
+            "Session 2024-05-01T19:02:11 (2)",
+            "VID_20240501_190211_001",
+            "zoom_2024.05.01_19.02.11",
+        ] {
+            assert_eq!(parse_filename_timestamp(stem), Some(MAY_DAY), "{}", stem);
+        }
+    }
+
+    #[test]
+    fn filename_digits_that_are_not_timestamps() {
+        for stem in [
+            "track 1",
+            "recording_1714590131",
+            "2024-05-01",
+            "IMG_99999999999999",
+            "REC_20241301_190211",
+            "REC_20240230_190211",
+            "REC_20240501_250211",
+            "2024-05-01 - 19-02-11",
+        ] {
+            assert_eq!(parse_filename_timestamp(stem), None, "{}", stem);
+        }
+    }
+
+    #[test]
+    fn filename_timestamp_accepts_leap_days() {
+        assert_eq!(
+            parse_filename_timestamp("REC_20240229_000000"),
+            Some(date_utils::days_from_civil(2024, 2, 29) as f64 * 86400.0)
+        );
+        assert_eq!(parse_filename_timestamp("REC_20230229_000000"), None);
+    }
+
+    #[test]
+    fn creation_time_with_fraction_and_zulu() {
+        assert_eq!(
+            creation_time("2024-05-01T19:02:11.250000Z"),
+            Some(MAY_DAY + 0.25)
+        );
+        assert_eq!(creation_time("2024-05-01T19:02:11Z"), Some(MAY_DAY));
+        assert_eq!(creation_time("2024-05-01 19:02:11"), Some(MAY_DAY));
+    }
+
+    #[test]
+    fn creation_time_applies_utc_offsets() {
+        assert_eq!(creation_time("2024-05-01T21:02:11+02:00"), Some(MAY_DAY));
+        assert_eq!(creation_time("2024-05-01T14:02:11.000-0500"), Some(MAY_DAY));
+        assert_eq!(creation_time("2024-05-01T20:02:11+01"), Some(MAY_DAY));
+    }
+
+    #[test]
+    fn creation_time_rejects_garbage() {
+        assert_eq!(creation_time("2024-05-01"), None);
+        assert_eq!(creation_time("1714590131"), None);
+        assert_eq!(creation_time("2024-05-01T19:02:11 PST"), None);
+        assert_eq!(creation_time("2024-05-01T19:02:11+2:00"), None);
+        assert_eq!(creation_time("1904-01-01T00:00:00.000000Z"), None);
+        assert_eq!(parse_creation_time("Duration: 00:10:00.00"), None);
+    }
+
+    #[test]
+    fn timeline_sorts_by_recording_time_with_unknowns_last() {
+        let files = [
+            "/r/notes.m4a",
+            "/r/REC_20240501_200000.m4a",
+            "/r/extra.m4a",
+            "/r/REC_20240501_190211.m4a",
+        ]
+        .map(String::from);
+        let entries = build_timeline(
+            Path::new("/nonexistent/ffmpeg"),
+            &files,
+            InputOrder::FilenameTimestamp,
+        );
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "/r/REC_20240501_190211.m4a",
+                "/r/REC_20240501_200000.m4a",
+                "/r/notes.m4a",
+                "/r/extra.m4a",
+            ]
+        );
+        assert_eq!(entries[0].recorded_at, Some(MAY_DAY));
+        assert_eq!(entries[2].recorded_at, None);
+    }
+
+    #[test]
+    fn timeline_keeps_given_order() {
+        let files = ["/r/REC_20240501_200000.m4a", "/r/REC_20240501_190211.m4a"].map(String::from);
+        let entries = build_timeline(
+            Path::new("/nonexistent/ffmpeg"),
+            &files,
+            InputOrder::AsGiven,
+        );
+        assert_eq!(entries[0].path, files[0]);
+        assert!(entries.iter().all(|e| e.recorded_at.is_none()));
+    }
+
+    #[test]
+    fn gaps_are_measured_from_the_previous_end() {
+        let mut entries = vec![
+            entry("b", Some(1000.0), Some(50.0)),
+            entry("a", Some(0.0), Some(900.0)),
+            entry("c", Some(1040.0), Some(30.0)),
+            entry("d", None, Some(30.0)),
+        ];
+        order_timeline(&mut entries, InputOrder::CreationTime);
+        let gaps: Vec<Option<f64>> = entries.iter().map(|e| e.gap_secs).collect();
+        assert_eq!(gaps, [None, Some(100.0), Some(-10.0), None]);
+    }
+
+    #[test]
+    fn fill_gaps_inserts_silence_and_trims_overlaps() {
+        let mut entries = vec![
+            entry("a", Some(0.0), Some(60.0)),
+            entry("b", Some(90.0), Some(60.0)),
+            entry("c", Some(140.0), Some(60.0)),
+            entry("d", Some(100_000.0), Some(60.0)),
+        ];
+        order_timeline(&mut entries, InputOrder::CreationTime);
+        fill_gaps(&mut entries, 3600.0);
+
+        assert_eq!(entries[1].inserted_silence_secs, 30.0);
+        assert_eq!(entries[2].trimmed_secs, 10.0);
+        assert_eq!(entries[2].inserted_silence_secs, 0.0);
+        // Too long to be a pause in the same recording
+        assert_eq!(entries[3].inserted_silence_secs, 0.0);
+    }
+
+    #[test]
+    fn fill_gaps_trims_no_more_than_the_input() {
+        let mut entries = vec![
+            entry("a", Some(0.0), Some(600.0)),
+            entry("b", Some(100.0), Some(60.0)),
+            entry("c", Some(120.0), None),
+        ];
+        order_timeline(&mut entries, InputOrder::CreationTime);
+        fill_gaps(&mut entries, 3600.0);
+
+        assert_eq!(entries[1].trimmed_secs, 60.0);
+        // Without its own duration an input is trimmed by the whole overlap
+        assert_eq!(entries[2].trimmed_secs, 40.0);
+    }
+}
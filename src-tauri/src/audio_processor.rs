@@ -1,22 +1,196 @@
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use tauri::{command, AppHandle, Manager};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tauri::{command, AppHandle, Emitter, Manager};
 
+use crate::audio_cache::AudioCache;
+use crate::audio_filters::{self, LoudnessStats, LoudnormTarget, ProcessingProfile};
+use crate::audio_format::OutputOptions;
+use crate::audio_metadata::{self, Chapter};
+use crate::audio_ordering::{self, InputOrder, TimelineEntry};
+use crate::audio_recovery::{self, RecoveryReport};
+use crate::audio_silence::{self, EdlEntry, SilenceTrimOptions};
+use crate::audio_transcription;
 use crate::audio_utils;
+use crate::drizzle_proxy;
+use crate::folder_watcher;
+use crate::session_plan;
 
-#[derive(Debug, Serialize, Deserialize)]
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ProcessAudioRequest {
     pub file_paths: Vec<String>,
     pub output_filename: String,
     pub session_id: String,
+    #[serde(default)]
+    pub layout: TrackLayout,
+    /// Order sequential inputs are joined in
+    #[serde(default)]
+    pub order: InputOrder,
+    /// Insert silence for gaps between sequential inputs, and drop audio where
+    /// they overlap, so the output follows wall-clock time. Needs an `order`
+    /// that knows when each file was recorded.
+    #[serde(default)]
+    pub fill_gaps: bool,
+    /// Longer gaps are reported but not filled. Defaults to one hour.
+    pub max_gap_secs: Option<f64>,
+    /// Per-track gain in dB, matched to `file_paths` by index. Only used for
+    /// simultaneous tracks; missing entries default to 0 dB.
+    #[serde(default)]
+    pub track_gains: Vec<f64>,
+    /// Bring every simultaneous track to the same loudness before mixing
+    #[serde(default)]
+    pub match_levels: bool,
+    /// Clean-up filters and loudness normalization applied to every input
+    pub profile: Option<ProcessingProfile>,
+    /// Shorten long silences in the output to save on transcription
+    pub silence_trim: Option<SilenceTrimOptions>,
+    /// Encoding of the main (transcription) copy of the session audio
+    #[serde(default)]
+    pub output: OutputOptions,
+    /// Optional second, higher quality rendition kept for archiving
+    pub archive: Option<OutputOptions>,
+    #[serde(default)]
+    pub concat_strategy: ConcatStrategy,
+    /// How many inputs to analyze and normalize at once. Defaults to the
+    /// number of CPU cores.
+    pub max_concurrency: Option<usize>,
+    /// Ignore cached analysis and intermediates from earlier runs
+    #[serde(default)]
+    pub bypass_cache: bool,
+    /// A healthy recording made with the same device and settings, used to
+    /// rebuild fragmented MP4s whose index was never written
+    pub recovery_reference: Option<String>,
+    /// Background job this run belongs to, repeated in its progress events
+    #[serde(default)]
+    pub job_id: Option<String>,
+}
+
+/// Payload of the `audio-processing-progress` event, sent as each input
+/// starts and finishes a per-file step
+#[derive(Debug, Clone, Serialize)]
+pub struct FileProgress {
+    pub session_id: String,
+    pub job_id: Option<String>,
+    /// Position of the file in processing order (`file_paths` after sorting)
+    pub index: usize,
+    pub file_path: String,
+    pub stage: &'static str,
+    pub status: &'static str,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConcatStrategy {
+    /// Decode, filter, join and encode every input in one FFmpeg invocation,
+    /// falling back to per-file processing if that fails
+    #[default]
+    FilterGraph,
+    /// Normalize each input to its own intermediate file, then join those
+    Intermediate,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessAudioResponse {
+    /// Main copy of the session audio, used for transcription and playback
+    pub output_path: String,
+    pub archive_path: Option<String>,
+    pub manifest_path: String,
+    /// Gaps and overlaps between sequential inputs, when they were ordered
+    /// by recording time
+    pub timeline: Option<Vec<TimelineEntry>>,
+    /// Inputs that were damaged and only partly salvaged
+    pub recovered: Vec<RecoveryReport>,
+}
+
+/// Record of how a session's audio was produced, written next to it as
+/// `manifest.json`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessingManifest {
+    pub session_id: String,
+    pub layout: TrackLayout,
+    pub profile: Option<ProcessingProfile>,
+    pub inputs: Vec<InputManifest>,
+    pub output_path: String,
+    #[serde(default)]
+    pub output: OutputOptions,
+    #[serde(default)]
+    pub archive_path: Option<String>,
+    #[serde(default)]
+    pub archive: Option<OutputOptions>,
+    /// Maps the trimmed output back to the original timeline when silences
+    /// were removed
+    #[serde(default)]
+    pub edl: Option<Vec<EdlEntry>>,
+    #[serde(default)]
+    pub timeline: Option<Vec<TimelineEntry>>,
+    /// Where each sequential input sits in the output, embedded as chapters
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InputManifest {
+    pub path: String,
+    /// Loudness measured before normalization, when the profile enables it
+    pub loudness: Option<LoudnessStats>,
+    /// Set when the input was damaged and had to be salvaged
+    #[serde(default)]
+    pub recovery: Option<RecoveryReport>,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackLayout {
+    /// Inputs are consecutive parts of one recording and are joined end-to-end
+    #[default]
+    Sequential,
+    /// Inputs are per-speaker tracks that all start at the same time (e.g. Craig)
+    /// and are mixed in parallel
+    Simultaneous,
+}
+
+const DEFAULT_MAX_GAP_SECS: f64 = 3600.0;
+
+// Level matching never moves a track by more than this, so a near-silent
+// track doesn't get its noise floor boosted into the mix
+const MAX_LEVEL_MATCH_GAIN_DB: f64 = 20.0;
+
 #[command]
 pub async fn process_audio_files(
     app: AppHandle,
     request: ProcessAudioRequest,
-) -> Result<String, String> {
+) -> Result<ProcessAudioResponse, String> {
+    let session_id = request.session_id.clone();
+    let response = {
+        let app = app.clone();
+        audio_utils::run_blocking(move || process(app, request)).await?
+    };
+
+    // Tags are a convenience; the audio is still good without them
+    if let Err(e) = audio_metadata::tag_session_audio(&app, &session_id).await {
+        eprintln!("Failed to tag session audio: {}", e);
+    }
+
+    // Draft sessions from a watch folder are ready once their audio is
+    let pool = drizzle_proxy::pool(&app);
+    if let Err(e) = folder_watcher::mark_processed(&pool, &session_id).await {
+        eprintln!("Failed to mark session as processed: {}", e);
+    }
+
+    Ok(response)
+}
+
+/// Everything `process_audio_files` does up to writing the manifest, which is
+/// all FFmpeg runs and file I/O
+fn process(
+    app: AppHandle,
+    mut request: ProcessAudioRequest,
+) -> Result<ProcessAudioResponse, String> {
     let app_data_dir = app
         .path()
         .app_data_dir()
@@ -26,54 +200,646 @@ pub async fn process_audio_files(
     std::fs::create_dir_all(&session_dir)
         .map_err(|e| format!("Failed to create session directory: {}", e))?;
 
-    // Output path: app_data_dir/sessions/{session_id}/audio.mp3
-    let output_path = session_dir.join("audio.mp3");
+    // Output paths: app_data_dir/sessions/{session_id}/audio.{ext} (and archive.{ext})
+    let output_path = session_dir.join(format!("audio.{}", request.output.format.extension()));
+    let archive_path = request
+        .archive
+        .as_ref()
+        .map(|archive| session_dir.join(format!("archive.{}", archive.format.extension())));
 
     let ffmpeg_path = audio_utils::get_ffmpeg_path(&app)?;
 
-    let temp_dir = app_data_dir.join("temp_audio");
+    // Better to refuse now than to fill the disk hours in
+    session_plan::check_disk_space(&ffmpeg_path, &app_data_dir, &request)?;
+
+    // Sequential inputs are sorted before anything else, so every later step
+    // (and its progress events) works in recording order
+    let timeline = if request.layout == TrackLayout::Sequential
+        && request.file_paths.len() > 1
+        && (request.order != InputOrder::AsGiven || request.fill_gaps)
+    {
+        let mut timeline =
+            audio_ordering::build_timeline(&ffmpeg_path, &request.file_paths, request.order);
+        if request.fill_gaps {
+            audio_ordering::fill_gaps(
+                &mut timeline,
+                request.max_gap_secs.unwrap_or(DEFAULT_MAX_GAP_SECS),
+            );
+        }
+        request.file_paths = timeline.iter().map(|entry| entry.path.clone()).collect();
+        Some(timeline)
+    } else {
+        None
+    };
+
+    // Scratch files are per session, so sessions can be processed side by side
+    let temp_dir = app_data_dir.join("temp_audio").join(&request.session_id);
     std::fs::create_dir_all(&temp_dir)
         .map_err(|e| format!("Failed to create temp directory: {}", e))?;
 
-    // 1. Convert all videos to audio, normalize all audio files to common format
-    let mut normalized_audio_files = Vec::new();
-    for (index, input_path) in request.file_paths.iter().enumerate() {
-        let input_path = PathBuf::from(input_path);
-        let is_video = is_video_file(&input_path);
-
-        let audio_path = if is_video {
-            // Convert video to audio
-            let temp_audio = temp_dir.join(format!("audio_{}.wav", index));
-            convert_video_to_audio(&ffmpeg_path, &input_path, &temp_audio)
-                .map_err(|e| format!("Failed to convert video to audio: {}", e))?;
-            temp_audio
+    // Everything up to the final renditions stays lossless, at the highest
+    // quality any rendition asks for. Renditions are written next to the old
+    // ones and only replace them once all of them are done, so a failed run
+    // leaves the previous audio (and the manifest pointing at it) intact.
+    let mut renditions = vec![(partial_path(&output_path), request.output.clone())];
+    if let (Some(archive), Some(archive_path)) = (&request.archive, &archive_path) {
+        renditions.push((partial_path(archive_path), archive.clone()));
+    }
+    let intermediate = intermediate_format(&request);
+
+    let concurrency = request.max_concurrency.unwrap_or_else(default_concurrency);
+
+    // Results from earlier runs are reused for inputs whose content and
+    // settings haven't changed. A broken cache never fails processing.
+    let cache = app
+        .try_state::<AudioCache>()
+        .filter(|_| !request.bypass_cache);
+
+    // Inputs that fail to decode are salvaged into a lossless copy, which then
+    // stands in for the original in every later step
+    let recoveries: Mutex<Vec<Option<RecoveryReport>>> =
+        Mutex::new(vec![None; request.file_paths.len()]);
+    let recovered_path = |index: usize| temp_dir.join(format!("recovered_{}.wav", index));
+    let recover = |index: usize, error: &str| -> Result<PathBuf, String> {
+        let input_path = Path::new(&request.file_paths[index]);
+        eprintln!("Attempting to recover {:?}: {}", input_path, error);
+
+        let report = audio_recovery::recover_input(
+            &ffmpeg_path,
+            input_path,
+            &recovered_path(index),
+            &temp_dir.join(format!("remux_{}.mka", index)),
+            request.recovery_reference.as_deref().map(Path::new),
+            &intermediate,
+        )
+        .map_err(|e| format!("{} (recovery failed: {})", error, e))?;
+
+        recoveries.lock().unwrap()[index] = Some(report);
+        Ok(recovered_path(index))
+    };
+    let source_path = |index: usize| match &recoveries.lock().unwrap()[index] {
+        Some(_) => recovered_path(index),
+        None => PathBuf::from(&request.file_paths[index]),
+    };
+
+    // 1. Analyze every input and build its clean-up filter chain
+    let analyzed = run_per_file(
+        &app,
+        &request,
+        concurrency,
+        "analyze",
+        |index, input_path| {
+            let input_path = Path::new(input_path);
+            let content_hash = cache
+                .as_ref()
+                .and_then(|cache| cache.content_hash(input_path).ok());
+            let key = content_hash
+                .as_ref()
+                .map(|hash| AudioCache::key(hash, &("analyze", &request.profile)));
+
+            if let (Some(cache), Some(key)) = (&cache, &key) {
+                if let Some(analysis) = cache.get_value(key) {
+                    return Ok((analysis, content_hash));
+                }
+            }
+
+            let analysis = match &request.profile {
+                Some(profile) => match profile.input_filters(&ffmpeg_path, input_path) {
+                    Ok(analysis) => analysis,
+                    Err(e) => {
+                        let recovered =
+                            recover(index, &format!("Failed to analyze audio file: {}", e))?;
+                        profile
+                            .input_filters(&ffmpeg_path, &recovered)
+                            .map_err(|e| format!("Failed to analyze recovered audio: {}", e))?
+                    }
+                },
+                None => (Vec::new(), None),
+            };
+
+            // Like the intermediates below, analysis of salvaged audio isn't
+            // cached, so later runs still recover the input and report it
+            let is_recovered = recoveries.lock().unwrap()[index].is_some();
+            let cache = cache.as_ref().filter(|_| !is_recovered);
+            if let (Some(cache), Some(key)) = (cache, &key) {
+                if let Err(e) = cache.put_value(key, &analysis) {
+                    eprintln!("Failed to cache analysis of {:?}: {}", input_path, e);
+                }
+            }
+
+            Ok((analysis, content_hash))
+        },
+    )?;
+
+    let mut input_filters = Vec::new();
+    let mut input_manifests = Vec::new();
+    let mut intermediate_keys = Vec::new();
+    for (index, ((filters, loudness), content_hash)) in analyzed.into_iter().enumerate() {
+        // Gap filling wraps the input's own chain: overlap is cut before it and
+        // silence is added after it
+        let filters = match &timeline {
+            Some(timeline) => [
+                timeline[index].leading_filters(),
+                filters,
+                timeline[index].trailing_filters(),
+            ]
+            .concat(),
+            None => filters,
+        };
+
+        intermediate_keys.push(
+            content_hash
+                .map(|hash| AudioCache::key(&hash, &("normalize", &filters, &intermediate))),
+        );
+        input_filters.push(filters);
+        input_manifests.push(InputManifest {
+            path: request.file_paths[index].clone(),
+            loudness,
+            recovery: None,
+        });
+    }
+
+    // Joining cached intermediates beats re-encoding every input in one pass
+    let has_cached_intermediates = cache.as_ref().is_some_and(|cache| {
+        intermediate_keys
+            .iter()
+            .flatten()
+            .any(|key| cache.has_file(key))
+    });
+
+    // 2. Join the inputs. Sequential inputs go through a single filter graph
+    // when possible, which encodes straight to the renditions unless silence
+    // trimming still needs a lossless master.
+    let master_path = temp_dir.join("master.wav");
+    let mut renditions_written = false;
+    let mut master_written = false;
+
+    if request.layout == TrackLayout::Sequential
+        && request.concat_strategy == ConcatStrategy::FilterGraph
+        && !has_cached_intermediates
+    {
+        let targets = if request.silence_trim.is_some() {
+            vec![(master_path.clone(), intermediate.clone())]
         } else {
-            input_path
+            renditions.clone()
         };
 
-        // Normalize all audio files to a common format for consistent concatenation
-        let normalized_path = temp_dir.join(format!("normalized_{}.mp3", index));
-        normalize_audio_file(&ffmpeg_path, &audio_path, &normalized_path)
-            .map_err(|e| format!("Failed to normalize audio file: {}", e))?;
+        let sources: Vec<String> = (0..request.file_paths.len())
+            .map(|index| source_path(index).to_string_lossy().to_string())
+            .collect();
 
-        normalized_audio_files.push(normalized_path);
+        match concatenate_with_filter_graph(
+            &ffmpeg_path,
+            &sources,
+            &input_filters,
+            &intermediate,
+            &targets,
+            0.0,
+        ) {
+            Ok(()) if request.silence_trim.is_some() => master_written = true,
+            Ok(()) => renditions_written = true,
+            Err(e) => eprintln!(
+                "Single-pass concatenation failed, falling back to per-file processing: {}",
+                e
+            ),
+        }
     }
 
-    // 2. Concatenate all normalized audio files
-    if normalized_audio_files.len() == 1 {
-        std::fs::copy(&normalized_audio_files[0], &output_path)
-            .map_err(|e| format!("Failed to copy file: {}", e))?;
-    } else {
-        concatenate_audio_files(&ffmpeg_path, &normalized_audio_files, &output_path)
-            .map_err(|e| format!("Failed to concatenate audio: {}", e))?;
+    // Per-file path: convert all videos to audio, normalize all audio files to
+    // a common format, then concatenate (or mix) them into one master
+    let mut normalized_audio_files: Vec<PathBuf> = Vec::new();
+    if !renditions_written && !master_written {
+        normalized_audio_files = run_per_file(
+            &app,
+            &request,
+            concurrency,
+            "normalize",
+            |index, input_path| {
+                let normalized_path = temp_dir.join(format!("normalized_{}.wav", index));
+                let cached = cache.as_ref().zip(intermediate_keys[index].as_ref());
+
+                if let Some((cache, key)) = cached {
+                    if cache.restore_file(key, &normalized_path) {
+                        return Ok(normalized_path);
+                    }
+                }
+
+                // A leftover from an interrupted run may be hard-linked into
+                // the cache, so never let FFmpeg overwrite it in place
+                let _ = std::fs::remove_file(&normalized_path);
+                let prepare = |source: &Path| {
+                    prepare_input(
+                        &ffmpeg_path,
+                        &temp_dir,
+                        index,
+                        source,
+                        &normalized_path,
+                        &input_filters[index],
+                        &intermediate,
+                    )
+                };
+
+                let is_recovered = || recoveries.lock().unwrap()[index].is_some();
+                if let Err(e) = prepare(&source_path(index)) {
+                    if is_recovered() {
+                        return Err(e);
+                    }
+                    prepare(&recover(index, &e)?)?;
+                }
+
+                // Salvaged audio isn't cached, so the recovery report stays
+                // accurate on every run
+                if let Some((cache, key)) = cached.filter(|_| !is_recovered()) {
+                    if let Err(e) = cache.store_file(key, &normalized_path) {
+                        eprintln!("Failed to cache {:?}: {}", input_path, e);
+                    }
+                }
+
+                Ok(normalized_path)
+            },
+        )?;
+
+        if normalized_audio_files.len() == 1 {
+            std::fs::copy(&normalized_audio_files[0], &master_path)
+                .map_err(|e| format!("Failed to copy file: {}", e))?;
+        } else if request.layout == TrackLayout::Simultaneous {
+            let gains = track_gains(&ffmpeg_path, &request, &normalized_audio_files)?;
+            mix_audio_files(
+                &ffmpeg_path,
+                &normalized_audio_files,
+                &gains,
+                &master_path,
+                &intermediate,
+            )
+            .map_err(|e| format!("Failed to mix audio tracks: {}", e))?;
+        } else {
+            concatenate_audio_files(&ffmpeg_path, &normalized_audio_files, &master_path)
+                .map_err(|e| format!("Failed to concatenate audio: {}", e))?;
+        }
     }
 
+    // 3. Shorten long silences, keeping an edit decision list back to original time
+    let edl = match &request.silence_trim {
+        Some(options) => Some(
+            trim_silences(
+                &ffmpeg_path,
+                &master_path,
+                &temp_dir,
+                options,
+                &intermediate,
+            )
+            .map_err(|e| format!("Failed to trim silences: {}", e))?,
+        ),
+        None => None,
+    };
+
+    // 4. Encode the renditions from the master
+    if !renditions_written {
+        for (path, options) in &renditions {
+            encode_audio_file(&ffmpeg_path, &master_path, path, options)
+                .map_err(|e| format!("Failed to encode {:?}: {}", path, e))?;
+        }
+    }
+
+    // Keep the individual speaker tracks so transcripts can be attributed per player
+    let tracks_dir = session_dir.join("tracks");
+    let partial_tracks_dir = partial_path(&tracks_dir);
+    if request.layout == TrackLayout::Simultaneous {
+        let _ = std::fs::remove_dir_all(&partial_tracks_dir);
+        std::fs::create_dir_all(&partial_tracks_dir)
+            .map_err(|e| format!("Failed to create tracks directory: {}", e))?;
+        for (index, track) in normalized_audio_files.iter().enumerate() {
+            encode_audio_file(
+                &ffmpeg_path,
+                track,
+                &partial_tracks_dir.join(track_file_name(index)),
+                &OutputOptions::default(),
+            )
+            .map_err(|e| format!("Failed to keep track {}: {}", index, e))?;
+        }
+    }
+
+    // Every rendition succeeded, so the new audio can take the old one's place
+    let finals: Vec<&PathBuf> = std::iter::once(&output_path)
+        .chain(archive_path.as_ref())
+        .collect();
+    for ((partial, _), path) in renditions.iter().zip(&finals) {
+        std::fs::rename(partial, path)
+            .map_err(|e| format!("Failed to replace {:?}: {}", path, e))?;
+    }
+    remove_stale_renditions(&session_dir, &finals)?;
+    if request.layout == TrackLayout::Simultaneous {
+        let _ = std::fs::remove_dir_all(&tracks_dir);
+        std::fs::rename(&partial_tracks_dir, &tracks_dir)
+            .map_err(|e| format!("Failed to replace speaker tracks: {}", e))?;
+    }
+
+    let chapters = if request.layout == TrackLayout::Sequential && request.file_paths.len() > 1 {
+        input_chapters(
+            &ffmpeg_path,
+            &request.file_paths,
+            timeline.as_deref(),
+            edl.as_deref(),
+            source_path,
+        )
+    } else {
+        Vec::new()
+    };
+
     let _ = std::fs::remove_dir_all(&temp_dir);
 
-    Ok(output_path.to_string_lossy().to_string())
+    let recoveries = recoveries.into_inner().unwrap();
+    for (input, recovery) in input_manifests.iter_mut().zip(&recoveries) {
+        input.recovery = recovery.clone();
+    }
+
+    let manifest = ProcessingManifest {
+        session_id: request.session_id.clone(),
+        layout: request.layout,
+        profile: request.profile.clone(),
+        inputs: input_manifests,
+        output_path: output_path.to_string_lossy().to_string(),
+        output: request.output.clone(),
+        archive_path: archive_path
+            .as_ref()
+            .map(|p| p.to_string_lossy().to_string()),
+        archive: request.archive.clone(),
+        edl,
+        timeline,
+        chapters,
+    };
+    write_manifest(&session_dir, &manifest)?;
+
+    Ok(ProcessAudioResponse {
+        output_path: manifest.output_path,
+        archive_path: manifest.archive_path,
+        manifest_path: session_dir
+            .join(MANIFEST_FILE)
+            .to_string_lossy()
+            .to_string(),
+        timeline: manifest.timeline,
+        recovered: recoveries.into_iter().flatten().collect(),
+    })
 }
 
-fn is_video_file(path: &Path) -> bool {
+/// Converts timestamps in a session's (possibly silence-trimmed) audio back to
+/// the original recording's timeline
+#[command]
+pub async fn map_to_original_time(
+    app: AppHandle,
+    session_id: String,
+    timestamps: Vec<f64>,
+) -> Result<Vec<f64>, String> {
+    let session_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?
+        .join("sessions")
+        .join(&session_id);
+
+    let manifest = load_manifest(&session_dir)?;
+    Ok(match manifest.edl {
+        Some(edl) => timestamps
+            .iter()
+            .map(|t| audio_silence::to_original_time(&edl, *t))
+            .collect(),
+        None => timestamps,
+    })
+}
+
+/// Lossless working format for a request: the highest sample rate and
+/// channel count any of its renditions asks for
+pub fn intermediate_format(request: &ProcessAudioRequest) -> OutputOptions {
+    let renditions: Vec<&OutputOptions> = std::iter::once(&request.output)
+        .chain(request.archive.as_ref())
+        .collect();
+    OutputOptions::intermediate(
+        renditions
+            .iter()
+            .map(|r| r.sample_rate)
+            .max()
+            .unwrap_or(44100),
+        renditions.iter().map(|r| r.channels).max().unwrap_or(2),
+    )
+}
+
+/// Main audio file of a session, whatever format it was encoded in
+pub fn session_audio_path(session_dir: &Path) -> PathBuf {
+    load_manifest(session_dir)
+        .map(|manifest| PathBuf::from(manifest.output_path))
+        .unwrap_or_else(|_| session_dir.join("audio.mp3"))
+}
+
+pub fn load_manifest(session_dir: &Path) -> Result<ProcessingManifest, String> {
+    let content = std::fs::read_to_string(session_dir.join(MANIFEST_FILE))
+        .map_err(|e| format!("Failed to read manifest: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse manifest: {}", e))
+}
+
+/// Where a file is written before it replaces `path`, keeping its extension
+/// so FFmpeg still picks the right muxer
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = match name.split_once('.') {
+        Some((stem, extension)) => format!("{}.partial.{}", stem, extension),
+        None => format!("{}.partial", name),
+    };
+    path.with_file_name(name)
+}
+
+/// Removes renditions left over from earlier runs in other formats (or an
+/// archive that is no longer wanted)
+fn remove_stale_renditions(session_dir: &Path, keep: &[&PathBuf]) -> Result<(), String> {
+    let entries = std::fs::read_dir(session_dir)
+        .map_err(|e| format!("Failed to read session directory: {}", e))?;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_rendition = path
+            .file_stem()
+            .is_some_and(|stem| stem == "audio" || stem == "archive");
+        if is_rendition && path.is_file() && !keep.contains(&&path) {
+            std::fs::remove_file(&path)
+                .map_err(|e| format!("Failed to remove old audio {:?}: {}", path, e))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_manifest(session_dir: &Path, manifest: &ProcessingManifest) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(manifest)
+        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+    std::fs::write(session_dir.join(MANIFEST_FILE), content)
+        .map_err(|e| format!("Failed to write manifest: {}", e))
+}
+
+/// File name of a kept speaker track, indexed like `ProcessAudioRequest::file_paths`
+pub fn track_file_name(index: usize) -> String {
+    format!("track_{}.mp3", index)
+}
+
+/// One chapter per sequential input, named after its file and placed on the
+/// output's timeline: after any silence inserted before it, without the
+/// overlap trimmed from it, and shifted by the silences trimmed before it
+fn input_chapters(
+    ffmpeg_path: &Path,
+    file_paths: &[String],
+    timeline: Option<&[TimelineEntry]>,
+    edl: Option<&[EdlEntry]>,
+    source_path: impl Fn(usize) -> PathBuf,
+) -> Vec<Chapter> {
+    let mut chapters = Vec::new();
+    let mut position = 0.0;
+
+    for (index, path) in file_paths.iter().enumerate() {
+        let entry = timeline.map(|timeline| &timeline[index]);
+        let duration = match entry.and_then(|entry| entry.duration) {
+            Some(duration) => duration,
+            None => match audio_transcription::get_audio_duration(ffmpeg_path, &source_path(index))
+            {
+                Ok(duration) => duration,
+                Err(e) => {
+                    eprintln!("Skipping input chapters, no duration for {}: {}", path, e);
+                    return Vec::new();
+                }
+            },
+        };
+
+        position += entry.map_or(0.0, |entry| entry.inserted_silence_secs);
+        let start = position;
+        position += duration - entry.map_or(0.0, |entry| entry.trimmed_secs);
+
+        let to_output = |time: f64| match edl {
+            Some(edl) => audio_silence::to_trimmed_time(edl, time),
+            None => time,
+        };
+        chapters.push(Chapter {
+            start: to_output(start),
+            end: to_output(position),
+            title: Path::new(path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| format!("Part {}", index + 1)),
+        });
+    }
+
+    chapters
+}
+
+/// Runs `step` for every input, at most `concurrency` at a time, reporting
+/// progress per file. Results keep the order of `request.file_paths`; the first
+/// failing input (in that order) fails the whole step.
+fn run_per_file<T, F>(
+    app: &AppHandle,
+    request: &ProcessAudioRequest,
+    concurrency: usize,
+    stage: &'static str,
+    step: F,
+) -> Result<Vec<T>, String>
+where
+    T: Send,
+    F: Fn(usize, &str) -> Result<T, String> + Sync,
+{
+    let report = |index: usize, status: &'static str, error: Option<String>| {
+        let _ = app.emit(
+            "audio-processing-progress",
+            FileProgress {
+                session_id: request.session_id.clone(),
+                job_id: request.job_id.clone(),
+                index,
+                file_path: request.file_paths[index].clone(),
+                stage,
+                status,
+                error,
+            },
+        );
+    };
+
+    run_bounded(request.file_paths.len(), concurrency, |index| {
+        report(index, "started", None);
+        let result = step(index, &request.file_paths[index]);
+        match &result {
+            Ok(_) => report(index, "finished", None),
+            Err(e) => report(index, "failed", Some(e.clone())),
+        }
+        result
+    })
+    .into_iter()
+    .collect()
+}
+
+/// Runs `step` for every index below `count` on at most `concurrency`
+/// threads. Results keep the order of the indices.
+pub fn run_bounded<T, F>(count: usize, concurrency: usize, step: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<T>>> = Mutex::new((0..count).map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, count.max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= count {
+                    break;
+                }
+                let result = step(index);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    // Every index was claimed by a thread, and a panicking one takes the
+    // whole scope down with it
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every index is processed"))
+        .collect()
+}
+
+/// One FFmpeg per CPU core
+pub fn default_concurrency() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Converts one input to the lossless working format at `normalized_path`,
+/// applying its filters. Videos get their audio extracted first.
+fn prepare_input(
+    ffmpeg_path: &Path,
+    temp_dir: &Path,
+    index: usize,
+    input_path: &Path,
+    normalized_path: &Path,
+    filters: &[String],
+    intermediate: &OutputOptions,
+) -> Result<(), String> {
+    let temp_audio = temp_dir.join(format!("audio_{}.wav", index));
+    let audio_path = if is_video_file(input_path) {
+        convert_video_to_audio(ffmpeg_path, input_path, &temp_audio)
+            .map_err(|e| format!("Failed to convert video to audio: {}", e))?;
+        temp_audio.as_path()
+    } else {
+        input_path
+    };
+
+    normalize_audio_file(
+        ffmpeg_path,
+        audio_path,
+        normalized_path,
+        filters,
+        intermediate,
+    )
+    .map_err(|e| format!("Failed to normalize audio file: {}", e))
+}
+
+pub fn is_video_file(path: &Path) -> bool {
     if let Some(ext) = path.extension() {
         let ext_lower = ext.to_string_lossy().to_lowercase();
         matches!(
@@ -120,32 +886,30 @@ fn convert_video_to_audio(ffmpeg_path: &Path, input: &Path, output: &Path) -> Re
     }
 }
 
-fn normalize_audio_file(ffmpeg_path: &Path, input: &Path, output: &Path) -> Result<(), String> {
+fn normalize_audio_file(
+    ffmpeg_path: &Path,
+    input: &Path,
+    output: &Path,
+    filters: &[String],
+    format: &OutputOptions,
+) -> Result<(), String> {
     let output_str = output.to_str().ok_or("Invalid output path")?;
 
     let input_str = input.to_str().ok_or("Invalid input path")?;
 
     let ffmpeg_str = ffmpeg_path.to_str().ok_or("Invalid FFmpeg path")?;
 
-    // Normalize audio to mp3
-    // - Sample rate: 44100 Hz
-    // - Channels: Stereo
-    // - Bitrate: 192k
-    // - Codec: libmp3lame
-    let result = Command::new(ffmpeg_str)
+    // Normalize audio to the requested format (sample rate, channels, codec)
+    let mut command = Command::new(ffmpeg_str);
+    command.args(["-i", input_str]);
+    if !filters.is_empty() {
+        command.args(["-af", &filters.join(",")]);
+    }
+
+    let result = command
+        .args(format.encode_args())
         .args([
-            "-i",
-            input_str,
-            "-vn", // No video
-            "-acodec",
-            "libmp3lame", // MP3 codec
-            "-ar",
-            "44100", // Sample rate
-            "-ac",
-            "2", // Stereo
-            "-b:a",
-            "192k", // Audio bitrate
-            "-y",   // Overwrite output
+            "-y", // Overwrite output
             output_str,
         ])
         .output();
@@ -162,6 +926,107 @@ fn normalize_audio_file(ffmpeg_path: &Path, input: &Path, output: &Path) -> Resu
     }
 }
 
+/// Encodes a processed file into a final rendition without further filtering
+fn encode_audio_file(
+    ffmpeg_path: &Path,
+    input: &Path,
+    output: &Path,
+    format: &OutputOptions,
+) -> Result<(), String> {
+    normalize_audio_file(ffmpeg_path, input, output, &[], format)
+}
+
+/// Joins every input end-to-end in one FFmpeg invocation: each input gets its
+/// own filter chain and is resampled to a common format before `concat`, and
+/// the result is split to every target so nothing is encoded twice. With a
+/// `crossfade_secs` above zero, neighbouring inputs overlap by that much.
+pub fn concatenate_with_filter_graph(
+    ffmpeg_path: &Path,
+    inputs: &[String],
+    input_filters: &[Vec<String>],
+    common: &OutputOptions,
+    targets: &[(PathBuf, OutputOptions)],
+    crossfade_secs: f64,
+) -> Result<(), String> {
+    if inputs.is_empty() {
+        return Err("No input files provided".to_string());
+    }
+
+    let ffmpeg_str = ffmpeg_path.to_str().ok_or("Invalid FFmpeg path")?;
+
+    // [0:a:0]highpass=f=80,aresample=44100,aformat=...[a0];...;[a0][a1]concat=n=2:v=0:a=1[cat]
+    let mut graph = String::new();
+    for (index, filters) in input_filters.iter().enumerate() {
+        let mut chain = filters.clone();
+        chain.push(format!("aresample={}", common.sample_rate));
+        chain.push(format!(
+            "aformat=sample_fmts=s16:channel_layouts={}",
+            channel_layout(common.channels)
+        ));
+        graph.push_str(&format!("[{}:a:0]{}[a{}];", index, chain.join(","), index));
+    }
+    if crossfade_secs > 0.0 && inputs.len() > 1 {
+        // [a0][a1]acrossfade=d=1.000[x1];[x1][a2]acrossfade=d=1.000
+        let mut joined = "[a0]".to_string();
+        for index in 1..inputs.len() {
+            graph.push_str(&format!(
+                "{}[a{}]acrossfade=d={:.3}:c1=tri:c2=tri",
+                joined, index, crossfade_secs
+            ));
+            if index < inputs.len() - 1 {
+                joined = format!("[x{}]", index);
+                graph.push_str(&format!("{};", joined));
+            }
+        }
+    } else {
+        for index in 0..inputs.len() {
+            graph.push_str(&format!("[a{}]", index));
+        }
+        graph.push_str(&format!("concat=n={}:v=0:a=1", inputs.len()));
+    }
+
+    let labels: Vec<String> = if targets.len() > 1 {
+        let labels: Vec<String> = (0..targets.len()).map(|i| format!("[o{}]", i)).collect();
+        graph.push_str(&format!(",asplit={}{}", targets.len(), labels.concat()));
+        labels
+    } else {
+        graph.push_str("[o0]");
+        vec!["[o0]".to_string()]
+    };
+
+    let mut command = Command::new(ffmpeg_str);
+    for input in inputs {
+        command.args(["-i", input]);
+    }
+    command.args(["-filter_complex", &graph]);
+    for ((path, options), label) in targets.iter().zip(&labels) {
+        let path_str = path.to_str().ok_or("Invalid output path")?;
+        command
+            .args(["-map", label])
+            .args(options.encode_args())
+            .args(["-y", path_str]);
+    }
+
+    match command.output() {
+        Ok(output) => {
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(format!("FFmpeg filter graph failed: {}", stderr));
+            }
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to run FFmpeg: {}", e)),
+    }
+}
+
+fn channel_layout(channels: u32) -> String {
+    match channels {
+        1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        n => format!("{}c", n),
+    }
+}
+
 fn concatenate_audio_files(
     ffmpeg_path: &Path,
     inputs: &[PathBuf],
@@ -223,3 +1088,185 @@ fn concatenate_audio_files(
         Err(e) => Err(format!("Failed to run FFmpeg: {}", e)),
     }
 }
+
+fn track_gains(
+    ffmpeg_path: &Path,
+    request: &ProcessAudioRequest,
+    tracks: &[PathBuf],
+) -> Result<Vec<f64>, String> {
+    let mut gains: Vec<f64> = (0..tracks.len())
+        .map(|i| request.track_gains.get(i).copied().unwrap_or(0.0))
+        .collect();
+
+    if !request.match_levels {
+        return Ok(gains);
+    }
+
+    let mut loudness = Vec::with_capacity(tracks.len());
+    for track in tracks {
+        let stats =
+            audio_filters::measure_loudness(ffmpeg_path, track, &[], &LoudnormTarget::default())
+                .map_err(|e| format!("Failed to measure track loudness: {}", e))?;
+        loudness.push(stats.map(|s| s.input_i));
+    }
+
+    // Tracks that are entirely silent report no loudness; they are left alone
+    // and don't pull the target down
+    let measured: Vec<f64> = loudness.iter().flatten().copied().collect();
+    if measured.is_empty() {
+        return Ok(gains);
+    }
+    let target = measured.iter().sum::<f64>() / measured.len() as f64;
+
+    for (gain, lufs) in gains.iter_mut().zip(&loudness) {
+        if let Some(lufs) = lufs {
+            *gain += (target - lufs).clamp(-MAX_LEVEL_MATCH_GAIN_DB, MAX_LEVEL_MATCH_GAIN_DB);
+        }
+    }
+
+    Ok(gains)
+}
+
+fn mix_audio_files(
+    ffmpeg_path: &Path,
+    inputs: &[PathBuf],
+    gains_db: &[f64],
+    output: &Path,
+    format: &OutputOptions,
+) -> Result<(), String> {
+    if inputs.is_empty() {
+        return Err("No input files provided".to_string());
+    }
+
+    let output_str = output.to_str().ok_or("Invalid output path")?;
+
+    let ffmpeg_str = ffmpeg_path.to_str().ok_or("Invalid FFmpeg path")?;
+
+    // [0:a]volume=0.00dB[a0];[1:a]volume=-3.00dB[a1];[a0][a1]amix=...[out]
+    let mut filter = String::new();
+    for (index, gain) in gains_db.iter().enumerate() {
+        filter.push_str(&format!("[{}:a]volume={:.2}dB[a{}];", index, gain, index));
+    }
+    for index in 0..inputs.len() {
+        filter.push_str(&format!("[a{}]", index));
+    }
+    // normalize=0 keeps each speaker at their own level instead of dividing by
+    // the track count; the limiter catches peaks where people talk over each other
+    filter.push_str(&format!(
+        "amix=inputs={}:duration=longest:normalize=0,alimiter=limit=0.95[out]",
+        inputs.len()
+    ));
+
+    let mut command = Command::new(ffmpeg_str);
+    for input in inputs {
+        command.arg("-i").arg(input);
+    }
+
+    let result = command
+        .args(["-filter_complex", &filter, "-map", "[out]"])
+        .args(format.encode_args())
+        .args(["-y", output_str])
+        .output();
+
+    match result {
+        Ok(output) => {
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(format!("FFmpeg mix failed: {}", stderr));
+            }
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to run FFmpeg: {}", e)),
+    }
+}
+
+fn trim_silences(
+    ffmpeg_path: &Path,
+    audio_path: &Path,
+    temp_dir: &Path,
+    options: &SilenceTrimOptions,
+    format: &OutputOptions,
+) -> Result<Vec<EdlEntry>, String> {
+    let (silences, duration) = audio_silence::detect_silences(
+        ffmpeg_path,
+        audio_path,
+        options.threshold_db,
+        options.min_silence_secs,
+    )?;
+
+    let edl = audio_silence::build_edl(&silences, duration, options);
+    let kept: f64 = edl.iter().map(|entry| entry.duration).sum();
+    if duration - kept < 0.001 {
+        // Nothing long enough to cut
+        return Ok(edl);
+    }
+
+    let trimmed_path = temp_dir.join(format!("trimmed.{}", format.format.extension()));
+    let filter = format!(
+        "aselect='{}',asetpts=N/SR/TB",
+        audio_silence::select_expression(&edl)
+    );
+
+    let output_str = trimmed_path.to_str().ok_or("Invalid output path")?;
+
+    let input_str = audio_path.to_str().ok_or("Invalid input path")?;
+
+    let ffmpeg_str = ffmpeg_path.to_str().ok_or("Invalid FFmpeg path")?;
+
+    let result = Command::new(ffmpeg_str)
+        .args(["-i", input_str, "-af", &filter])
+        .args(format.encode_args())
+        .args(["-y", output_str])
+        .output();
+
+    match result {
+        Ok(output) => {
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(format!("FFmpeg trim failed: {}", stderr));
+            }
+        }
+        Err(e) => return Err(format!("Failed to run FFmpeg: {}", e)),
+    }
+
+    std::fs::rename(&trimmed_path, audio_path)
+        .or_else(|_| std::fs::copy(&trimmed_path, audio_path).map(|_| ()))
+        .map_err(|e| format!("Failed to replace audio with trimmed version: {}", e))?;
+
+    Ok(edl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("{}_{}_{}", name, std::process::id(), nanos))
+    }
+
+    #[test]
+    fn session_audio_path_follows_the_manifest_output() {
+        let session_dir = temp_dir("session_audio_path");
+        std::fs::create_dir_all(&session_dir).unwrap();
+        let fallback = session_audio_path(&session_dir);
+
+        let manifest = serde_json::json!({
+            "session_id": "session",
+            "layout": "sequential",
+            "profile": null,
+            "inputs": [],
+            "output_path": session_dir.join("audio.opus"),
+            "output": { "format": "opus", "bitrate_kbps": 32, "sample_rate": 48000, "channels": 1 },
+        });
+        std::fs::write(session_dir.join(MANIFEST_FILE), manifest.to_string()).unwrap();
+        let from_manifest = session_audio_path(&session_dir);
+        let _ = std::fs::remove_dir_all(&session_dir);
+
+        assert_eq!(fallback, session_dir.join("audio.mp3"));
+        assert_eq!(from_manifest, session_dir.join("audio.opus"));
+    }
+}
@@ -0,0 +1,420 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tauri::{command, AppHandle};
+
+use crate::audio_processor;
+use crate::audio_silence;
+use crate::audio_utils;
+
+const ANALYSIS_SAMPLE_RATE: usize = 16000;
+/// Levels are measured every 50 ms
+const FRAME_SAMPLES: usize = ANALYSIS_SAMPLE_RATE / 20;
+/// Samples at or above 99% of full scale count as clipped
+const CLIP_LEVEL: i32 = 32440;
+/// Frames quieter than this are silence
+const SILENCE_DB: f32 = -50.0;
+/// Speech is judged in windows of this many frames (2 s)
+const SPEECH_WINDOW_FRAMES: usize = 40;
+/// Talking leaves gaps between words and syllables that music and steady
+/// noise don't. A window is speech-like when at least `SPEECH_DIP_SHARE` of
+/// its frames are `SPEECH_DIP_DB` below the window's loudest frame.
+const SPEECH_DIP_DB: f32 = 15.0;
+const SPEECH_DIP_SHARE: f32 = 0.2;
+
+#[derive(Debug, Deserialize)]
+pub struct QualityRequest {
+    pub file_paths: Vec<String>,
+    /// How many inputs to analyze at once. Defaults to the number of CPU
+    /// cores.
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QualityReport {
+    pub inputs: Vec<InputQuality>,
+    /// Most severe warning across all inputs, if there is any
+    pub worst: Option<Severity>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct InputQuality {
+    pub path: String,
+    pub duration: f64,
+    pub channels: usize,
+    /// Loudest sample in dBFS
+    pub peak_db: f64,
+    pub rms_db: f64,
+    /// Share of samples at or near full scale, in percent
+    pub clipping_percent: f64,
+    /// Share of the recording below -50 dBFS, from 0 to 1
+    pub silence_share: f64,
+    /// Share of the recording that sounds like people talking, from 0 to 1.
+    /// What is neither silence nor speech is music or steady noise.
+    pub speech_share: f64,
+    /// Left minus right RMS level in dB; positive when the left channel is
+    /// louder. Only set for multichannel inputs.
+    pub channel_imbalance_db: Option<f64>,
+    pub warnings: Vec<QualityWarning>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Worth knowing, no action needed
+    Info,
+    /// Transcription will work but may be poor; ask before going on
+    Warning,
+    /// Transcribing this would very likely waste money
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityWarning {
+    pub severity: Severity,
+    /// Stable identifier, e.g. `clipping` or `mostly_silence`
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Checks recordings before anything is spent on them: levels, clipping, how
+/// much is silence or speech, and whether one channel drowns out the other.
+/// Inputs are analyzed in parallel, a few at a time; one that can't be
+/// decoded gets an error warning rather than failing the whole report.
+#[command]
+pub async fn analyze_recording_quality(
+    app: AppHandle,
+    request: QualityRequest,
+) -> Result<QualityReport, String> {
+    let ffmpeg_path = audio_utils::get_ffmpeg_path(&app)?;
+    let concurrency = request
+        .max_concurrency
+        .unwrap_or_else(audio_processor::default_concurrency);
+
+    // Decoding is blocking work, so it stays off the async runtime
+    let inputs: Vec<InputQuality> = audio_utils::run_blocking(move || {
+        let paths = &request.file_paths;
+        let inputs = audio_processor::run_bounded(paths.len(), concurrency, |index| {
+            let analyze = || analyze_input(&ffmpeg_path, &paths[index]);
+            std::panic::catch_unwind(analyze).unwrap_or_else(|_| InputQuality {
+                path: paths[index].clone(),
+                warnings: vec![QualityWarning {
+                    severity: Severity::Error,
+                    code: "unreadable",
+                    message: "Analysis crashed".to_string(),
+                }],
+                ..Default::default()
+            })
+        });
+        Ok(inputs)
+    })
+    .await?;
+
+    let worst = inputs
+        .iter()
+        .flat_map(|input| &input.warnings)
+        .map(|warning| warning.severity)
+        .max();
+
+    Ok(QualityReport { inputs, worst })
+}
+
+fn analyze_input(ffmpeg_path: &Path, path: &str) -> InputQuality {
+    match measure(ffmpeg_path, Path::new(path)) {
+        Ok(mut quality) => {
+            quality.path = path.to_string();
+            quality.warnings = warnings(&quality);
+            quality
+        }
+        Err(e) => InputQuality {
+            path: path.to_string(),
+            warnings: vec![QualityWarning {
+                severity: Severity::Error,
+                code: "unreadable",
+                message: format!("Could not decode the recording: {}", e),
+            }],
+            ..Default::default()
+        },
+    }
+}
+
+/// Decodes the input at 16 kHz, keeping up to two channels, and measures it
+/// in one pass
+fn measure(ffmpeg_path: &Path, input: &Path) -> Result<InputQuality, String> {
+    let header = Command::new(ffmpeg_path)
+        .arg("-i")
+        .arg(input)
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+    let header = String::from_utf8_lossy(&header.stderr);
+    let source_channels = parse_channel_count(&header).ok_or("No audio stream found")?;
+    let channels = source_channels.min(2);
+
+    let mut child = Command::new(ffmpeg_path)
+        .arg("-i")
+        .arg(input)
+        .args(["-vn", "-ac"])
+        .arg(channels.to_string())
+        .arg("-ar")
+        .arg(ANALYSIS_SAMPLE_RATE.to_string())
+        .args(["-f", "s16le", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    let stdout = child.stdout.take().ok_or("Failed to read FFmpeg output")?;
+    let mut reader = BufReader::new(stdout);
+    let mut buffer = vec![0u8; FRAME_SAMPLES * channels * 2];
+
+    let mut frame_levels: Vec<f32> = Vec::new();
+    let mut channel_energy = vec![0.0f64; channels];
+    let mut peak: i32 = 0;
+    let mut clipped: u64 = 0;
+    let mut sample_count: u64 = 0;
+
+    loop {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match reader.read(&mut buffer[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(e) => {
+                    // Don't leave FFmpeg running (or a zombie) behind
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("Failed to read decoded audio: {}", e));
+                }
+            }
+        }
+        let frame = &buffer[..filled - filled % (channels * 2)];
+        if frame.is_empty() {
+            break;
+        }
+
+        let mut frame_energy = 0.0f64;
+        for (index, bytes) in frame.chunks_exact(2).enumerate() {
+            let sample = i16::from_le_bytes([bytes[0], bytes[1]]) as i32;
+            let magnitude = sample.abs();
+            peak = peak.max(magnitude);
+            if magnitude >= CLIP_LEVEL {
+                clipped += 1;
+            }
+            let energy = (sample as f64 / 32768.0).powi(2);
+            channel_energy[index % channels] += energy;
+            frame_energy += energy;
+        }
+        let frame_samples = frame.len() / 2;
+        sample_count += frame_samples as u64;
+        frame_levels.push(to_db(frame_energy / frame_samples as f64) as f32);
+
+        if filled < buffer.len() {
+            break;
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+    if !status.success() {
+        return Err(format!("FFmpeg could not decode {:?}", input));
+    }
+    if sample_count == 0 {
+        return Err("The recording contains no audio".to_string());
+    }
+
+    let frames_per_channel = sample_count as f64 / channels as f64;
+    let channel_db: Vec<f64> = channel_energy
+        .iter()
+        .map(|energy| to_db(energy / frames_per_channel))
+        .collect();
+    let total_energy: f64 = channel_energy.iter().sum();
+
+    let frame_count = frame_levels.len() as f64;
+    let silent = frame_levels.iter().filter(|&&l| l < SILENCE_DB).count();
+    let speech_frames: usize = frame_levels
+        .chunks(SPEECH_WINDOW_FRAMES)
+        .filter(|window| is_speech_like(window))
+        .map(|window| window.len())
+        .sum();
+
+    Ok(InputQuality {
+        path: String::new(),
+        duration: audio_silence::parse_duration(&header)
+            .unwrap_or(frames_per_channel / ANALYSIS_SAMPLE_RATE as f64),
+        channels: source_channels,
+        peak_db: to_db((peak as f64 / 32768.0).powi(2)),
+        rms_db: to_db(total_energy / sample_count as f64),
+        clipping_percent: clipped as f64 / sample_count as f64 * 100.0,
+        silence_share: silent as f64 / frame_count,
+        speech_share: speech_frames as f64 / frame_count,
+        channel_imbalance_db: (channels == 2).then(|| channel_db[0] - channel_db[1]),
+        warnings: Vec::new(),
+    })
+}
+
+fn is_speech_like(window: &[f32]) -> bool {
+    let sounding = window.iter().filter(|&&l| l >= SILENCE_DB).count();
+    if sounding * 2 < window.len() {
+        return false;
+    }
+    let loudest = window.iter().copied().fold(f32::MIN, f32::max);
+    let dips = window
+        .iter()
+        .filter(|&&l| l <= loudest - SPEECH_DIP_DB)
+        .count();
+    dips as f32 >= window.len() as f32 * SPEECH_DIP_SHARE
+}
+
+fn warnings(quality: &InputQuality) -> Vec<QualityWarning> {
+    let mut warnings = Vec::new();
+    let mut warn = |severity, code, message: String| {
+        warnings.push(QualityWarning {
+            severity,
+            code,
+            message,
+        })
+    };
+
+    if quality.clipping_percent > 1.0 {
+        warn(
+            Severity::Error,
+            "clipping",
+            format!(
+                "{:.1}% of the recording is clipped; speech will be distorted",
+                quality.clipping_percent
+            ),
+        );
+    } else if quality.clipping_percent > 0.1 {
+        warn(
+            Severity::Warning,
+            "clipping",
+            format!(
+                "{:.2}% of the recording is clipped",
+                quality.clipping_percent
+            ),
+        );
+    }
+
+    if quality.peak_db < -30.0 || quality.rms_db < -55.0 {
+        warn(
+            Severity::Error,
+            "nearly_silent",
+            format!(
+                "The recording is nearly silent (peak {:.0} dBFS)",
+                quality.peak_db
+            ),
+        );
+    } else if quality.rms_db < -40.0 {
+        warn(
+            Severity::Warning,
+            "quiet",
+            format!(
+                "The recording is very quiet ({:.0} dBFS RMS)",
+                quality.rms_db
+            ),
+        );
+    }
+
+    if quality.silence_share > 0.9 {
+        warn(
+            Severity::Error,
+            "mostly_silence",
+            format!(
+                "{:.0}% of the recording is silence",
+                quality.silence_share * 100.0
+            ),
+        );
+    } else if quality.silence_share > 0.6 {
+        warn(
+            Severity::Warning,
+            "mostly_silence",
+            format!(
+                "{:.0}% of the recording is silence",
+                quality.silence_share * 100.0
+            ),
+        );
+    }
+
+    // Judged on the part that isn't silence, which the checks above cover
+    let sounding = 1.0 - quality.silence_share;
+    if sounding > 0.1 {
+        let speech = quality.speech_share / sounding;
+        if speech < 0.2 {
+            warn(
+                Severity::Error,
+                "little_speech",
+                format!(
+                    "Only {:.0}% of the audible part sounds like speech; it may be music or noise",
+                    speech * 100.0
+                ),
+            );
+        } else if speech < 0.5 {
+            warn(
+                Severity::Warning,
+                "little_speech",
+                format!(
+                    "Only {:.0}% of the audible part sounds like speech",
+                    speech * 100.0
+                ),
+            );
+        }
+    }
+
+    if let Some(imbalance) = quality.channel_imbalance_db {
+        let louder = if imbalance > 0.0 { "left" } else { "right" };
+        if imbalance.abs() > 20.0 {
+            warn(
+                Severity::Warning,
+                "channel_imbalance",
+                format!(
+                    "Almost everything is on the {} channel ({:.0} dB louder)",
+                    louder,
+                    imbalance.abs()
+                ),
+            );
+        } else if imbalance.abs() > 6.0 {
+            warn(
+                Severity::Info,
+                "channel_imbalance",
+                format!("The {} channel is {:.0} dB louder", louder, imbalance.abs()),
+            );
+        }
+    }
+
+    warnings
+}
+
+/// Channel count of the first audio stream in FFmpeg's description of an
+/// input, e.g. `Audio: aac (LC), 48000 Hz, stereo, fltp`
+fn parse_channel_count(stderr: &str) -> Option<usize> {
+    let line = stderr.lines().find(|line| line.contains("Audio:"))?;
+    let layout = line
+        .split(',')
+        .map(str::trim)
+        .skip_while(|part| !part.ends_with("Hz"))
+        .nth(1)?;
+
+    let layout = layout.split('(').next().unwrap_or(layout);
+    match layout {
+        "mono" => Some(1),
+        "stereo" => Some(2),
+        _ => {
+            if let Some(count) = layout.strip_suffix(" channels") {
+                return count.trim().parse().ok();
+            }
+            // Surround layouts like `5.1` or `7.1`
+            let count: usize = layout
+                .split('.')
+                .map(|part| part.parse::<usize>().ok())
+                .sum::<Option<usize>>()?;
+            Some(count)
+        }
+    }
+}
+
+fn to_db(mean_square: f64) -> f64 {
+    10.0 * mean_square.max(1e-10).log10()
+}
@@ -0,0 +1,383 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::Command;
+
+use crate::audio_format::OutputOptions;
+use crate::audio_silence;
+
+// Keep decoding past damaged packets and rebuild missing timestamps
+const TOLERANT_INPUT_FLAGS: [&str; 4] = [
+    "-err_detect",
+    "ignore_err",
+    "-fflags",
+    "+genpts+discardcorrupt",
+];
+
+/// What could be salvaged from an input that failed to decode normally
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    pub path: String,
+    /// "remux" or "tolerant_decode", prefixed with "moov_rebuild+" when the
+    /// MP4 index had to be taken from a reference recording
+    pub method: String,
+    pub recovered_secs: f64,
+    /// Duration claimed by the container, when it reports one
+    pub expected_secs: Option<f64>,
+}
+
+/// Salvages as much audio as possible from a damaged recording into a lossless
+/// file at `output`. Tries a tolerant remux into Matroska first, which rebuilds
+/// a broken index, then decodes the original directly while skipping corrupt
+/// packets.
+///
+/// A fragmented MP4 (e.g. from OBS) that crashed before writing its index
+/// (moov atom) is first given the index of `reference`, a healthy recording
+/// from the same device. A plain MP4 without its index can't be rebuilt here:
+/// its sample table would have to be reconstructed frame by frame, which is
+/// left to tools like untrunc.
+pub fn recover_input(
+    ffmpeg_path: &Path,
+    input: &Path,
+    output: &Path,
+    remux_path: &Path,
+    reference: Option<&Path>,
+    format: &OutputOptions,
+) -> Result<RecoveryReport, String> {
+    let header = probe(ffmpeg_path, input)?;
+    let mut expected_secs = audio_silence::parse_duration(&header);
+    let mut method_prefix = "";
+    let mut rebuilt = None;
+
+    if header.contains("moov atom not found") {
+        // Without its index an MP4 doesn't say which codec or sample layout
+        // its audio uses, so FFmpeg has nothing to decode with
+        let reference = reference.ok_or_else(|| {
+            format!(
+                "{:?} is an MP4 whose index (moov atom) was never written. Pick a \
+                 healthy recording from the same device as a recovery reference.",
+                input
+            )
+        })?;
+        let rebuilt_path = remux_path.with_extension("mp4");
+        rebuild_fragmented_mp4(input, reference, &rebuilt_path)?;
+        // The reference's index claims the reference's duration
+        expected_secs = None;
+        method_prefix = "moov_rebuild+";
+        rebuilt = Some(rebuilt_path);
+    }
+
+    let result = salvage(
+        ffmpeg_path,
+        rebuilt.as_deref().unwrap_or(input),
+        output,
+        remux_path,
+        format,
+    );
+    if let Some(rebuilt) = &rebuilt {
+        let _ = std::fs::remove_file(rebuilt);
+    }
+
+    let (method, recovered_secs) = result?;
+    Ok(RecoveryReport {
+        path: input.to_string_lossy().to_string(),
+        method: format!("{}{}", method_prefix, method),
+        recovered_secs,
+        expected_secs,
+    })
+}
+
+/// Remuxes, then decodes tolerantly. Returns the method that worked and how
+/// many seconds it recovered.
+fn salvage(
+    ffmpeg_path: &Path,
+    input: &Path,
+    output: &Path,
+    remux_path: &Path,
+    format: &OutputOptions,
+) -> Result<(&'static str, f64), String> {
+    let mut attempts = Vec::new();
+
+    match remux(ffmpeg_path, input, remux_path) {
+        Ok(()) => match decode(ffmpeg_path, remux_path, output, format) {
+            Ok(recovered_secs) if recovered_secs > 0.0 => {
+                let _ = std::fs::remove_file(remux_path);
+                return Ok(("remux", recovered_secs));
+            }
+            Ok(_) => attempts.push("remux: no audio decoded".to_string()),
+            Err(e) => attempts.push(format!("remux: {}", e)),
+        },
+        Err(e) => attempts.push(format!("remux: {}", e)),
+    }
+    let _ = std::fs::remove_file(remux_path);
+
+    match decode(ffmpeg_path, input, output, format) {
+        Ok(recovered_secs) if recovered_secs > 0.0 => Ok(("tolerant_decode", recovered_secs)),
+        Ok(_) => {
+            attempts.push("tolerant decode: no audio decoded".to_string());
+            Err(format!(
+                "Nothing could be recovered: {}",
+                attempts.join("; ")
+            ))
+        }
+        Err(e) => {
+            attempts.push(format!("tolerant decode: {}", e));
+            Err(format!(
+                "Nothing could be recovered: {}",
+                attempts.join("; ")
+            ))
+        }
+    }
+}
+
+/// Writes the reference's `ftyp` and `moov` boxes followed by every fragment
+/// (`moof` + `mdat`) of `input` to `output`. A fragmented MP4 keeps its sample
+/// tables in the fragments, so the reference's index only has to describe the
+/// tracks, which it does as long as it was recorded with the same settings.
+fn rebuild_fragmented_mp4(input: &Path, reference: &Path, output: &Path) -> Result<(), String> {
+    let mut reference_file =
+        File::open(reference).map_err(|e| format!("Failed to open {:?}: {}", reference, e))?;
+    let reference_boxes = top_level_boxes(&mut reference_file)?;
+    let find = |kind: &[u8; 4]| reference_boxes.iter().find(|b| &b.kind == kind);
+    let (Some(ftyp), Some(moov)) = (find(b"ftyp"), find(b"moov")) else {
+        return Err(format!("{:?} is not a complete MP4", reference));
+    };
+
+    let mut moov_data = Vec::new();
+    read_range(&mut reference_file, moov.start, moov.end, &mut moov_data)?;
+    if !contains_box(&moov_data[moov.header_len..], b"mvex") {
+        return Err(format!(
+            "{:?} is not a fragmented MP4. The index of a plain MP4 lists every \
+             sample, so it can't be reused for another recording.",
+            reference
+        ));
+    }
+
+    let mut input_file =
+        File::open(input).map_err(|e| format!("Failed to open {:?}: {}", input, e))?;
+    let input_boxes = top_level_boxes(&mut input_file)?;
+    let Some(first_fragment) = input_boxes.iter().find(|b| &b.kind == b"moof") else {
+        return Err(format!(
+            "{:?} is a plain MP4 whose index was never written. Its sample table \
+             can't be rebuilt here; try untrunc with a healthy recording from \
+             the same device.",
+            input
+        ));
+    };
+
+    let mut out =
+        File::create(output).map_err(|e| format!("Failed to create {:?}: {}", output, e))?;
+    let mut ftyp_data = Vec::new();
+    read_range(&mut reference_file, ftyp.start, ftyp.end, &mut ftyp_data)?;
+    out.write_all(&ftyp_data)
+        .and_then(|_| out.write_all(&moov_data))
+        .map_err(|e| format!("Failed to write {:?}: {}", output, e))?;
+
+    // Everything from the first fragment on, including a truncated last one
+    input_file
+        .seek(SeekFrom::Start(first_fragment.start))
+        .and_then(|_| std::io::copy(&mut input_file, &mut out))
+        .map_err(|e| format!("Failed to copy fragments of {:?}: {}", input, e))?;
+    Ok(())
+}
+
+/// Position of a box in an MP4 file
+struct Mp4Box {
+    kind: [u8; 4],
+    start: u64,
+    end: u64,
+    header_len: usize,
+}
+
+/// Lists the top-level boxes of an MP4 file. A box running past the end of a
+/// truncated file is cut off there, and listing stops.
+fn top_level_boxes(file: &mut File) -> Result<Vec<Mp4Box>, String> {
+    let len = file
+        .metadata()
+        .map_err(|e| format!("Failed to read file size: {}", e))?
+        .len();
+    let mut boxes = Vec::new();
+    let mut start = 0;
+
+    while start + 8 <= len {
+        let mut header = [0u8; 16];
+        let header_len = (len - start).min(16) as usize;
+        file.seek(SeekFrom::Start(start))
+            .and_then(|_| file.read_exact(&mut header[..header_len]))
+            .map_err(|e| format!("Failed to read MP4 box: {}", e))?;
+        let Some((size, header_len)) = box_size(&header[..header_len], len - start) else {
+            break;
+        };
+
+        let end = (start + size).min(len);
+        boxes.push(Mp4Box {
+            kind: [header[4], header[5], header[6], header[7]],
+            start,
+            end,
+            header_len,
+        });
+        start = end;
+    }
+
+    Ok(boxes)
+}
+
+/// Size and header length of the box whose header starts `header`, with
+/// `remaining` bytes left in its container. `None` if the header is invalid.
+fn box_size(header: &[u8], remaining: u64) -> Option<(u64, usize)> {
+    let size = u32::from_be_bytes(header.get(..4)?.try_into().ok()?) as u64;
+    let (size, header_len) = match size {
+        // Runs to the end of the file
+        0 => (remaining, 8),
+        // The real size follows the type as a 64-bit number
+        1 => (u64::from_be_bytes(header.get(8..16)?.try_into().ok()?), 16),
+        size => (size, 8),
+    };
+    (size >= header_len as u64).then_some((size, header_len))
+}
+
+/// Whether `data`, the body of a container box, has a direct child of `kind`
+fn contains_box(data: &[u8], kind: &[u8; 4]) -> bool {
+    let mut start = 0;
+    while start + 8 <= data.len() {
+        let Some((size, _)) = box_size(&data[start..], (data.len() - start) as u64) else {
+            return false;
+        };
+        if &data[start + 4..start + 8] == kind {
+            return true;
+        }
+        start = start.saturating_add(size as usize);
+    }
+    false
+}
+
+fn read_range(file: &mut File, start: u64, end: u64, buffer: &mut Vec<u8>) -> Result<(), String> {
+    file.seek(SeekFrom::Start(start))
+        .and_then(|_| (&mut *file).take(end - start).read_to_end(buffer))
+        .map_err(|e| format!("Failed to read MP4 box: {}", e))?;
+    Ok(())
+}
+
+/// Copies the first audio stream into Matroska without re-encoding
+fn remux(ffmpeg_path: &Path, input: &Path, output: &Path) -> Result<(), String> {
+    let _ = std::fs::remove_file(output);
+    let result = Command::new(ffmpeg_path)
+        .args(TOLERANT_INPUT_FLAGS)
+        .arg("-i")
+        .arg(input)
+        .args(["-map", "0:a:0", "-c", "copy", "-f", "matroska", "-y"])
+        .arg(output)
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    // A non-zero exit is expected when the tail is truncated; whatever was
+    // written before that point is still usable
+    if !output.exists() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(format!("FFmpeg remux failed: {}", stderr));
+    }
+    Ok(())
+}
+
+/// Decodes to the working format, ignoring errors. Returns how many seconds
+/// made it into `output`.
+fn decode(
+    ffmpeg_path: &Path,
+    input: &Path,
+    output: &Path,
+    format: &OutputOptions,
+) -> Result<f64, String> {
+    let _ = std::fs::remove_file(output);
+    let result = Command::new(ffmpeg_path)
+        .args(TOLERANT_INPUT_FLAGS)
+        .arg("-i")
+        .arg(input)
+        .args(format.encode_args())
+        .arg("-y")
+        .arg(output)
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    if !output.exists() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(format!("FFmpeg decode failed: {}", stderr));
+    }
+
+    let header = probe(ffmpeg_path, output)?;
+    Ok(audio_silence::parse_duration(&header).unwrap_or(0.0))
+}
+
+/// FFmpeg's description of a file (it exits with an error because no output
+/// is given, which is expected)
+fn probe(ffmpeg_path: &Path, input: &Path) -> Result<String, String> {
+    let output = Command::new(ffmpeg_path)
+        .arg("-i")
+        .arg(input)
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+    Ok(String::from_utf8_lossy(&output.stderr).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(body);
+        data
+    }
+
+    #[test]
+    fn box_size_handles_all_size_forms() {
+        assert_eq!(
+            box_size(&[0, 0, 0, 16, b'm', b'o', b'o', b'f'], 100),
+            Some((16, 8))
+        );
+        assert_eq!(
+            box_size(&[0, 0, 0, 0, b'm', b'd', b'a', b't'], 100),
+            Some((100, 8))
+        );
+        let large = [0, 0, 0, 1, b'm', b'd', b'a', b't', 0, 0, 0, 0, 0, 0, 1, 0];
+        assert_eq!(box_size(&large, 1000), Some((256, 16)));
+        // Smaller than its own header
+        assert_eq!(box_size(&[0, 0, 0, 4, b'f', b'r', b'e', b'e'], 100), None);
+    }
+
+    #[test]
+    fn contains_box_finds_direct_children() {
+        let moov = [mp4_box(b"mvhd", &[0; 4]), mp4_box(b"mvex", &[])].concat();
+        assert!(contains_box(&moov, b"mvex"));
+        assert!(!contains_box(&moov, b"trak"));
+    }
+
+    #[test]
+    fn rebuild_fragmented_mp4_prepends_the_reference_index() {
+        let dir = std::env::temp_dir().join(format!("audio_recovery_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let reference = dir.join("reference.mp4");
+        let input = dir.join("input.mp4");
+        let output = dir.join("rebuilt.mp4");
+
+        let ftyp = mp4_box(b"ftyp", b"isom");
+        let moov = mp4_box(b"moov", &mp4_box(b"mvex", &[]));
+        let fragments = [mp4_box(b"moof", &[1; 8]), mp4_box(b"mdat", &[2; 32])].concat();
+        std::fs::write(
+            &reference,
+            [ftyp.clone(), moov.clone(), mp4_box(b"moof", &[3; 8])].concat(),
+        )
+        .unwrap();
+        // A crashed recording: header and fragments, last one cut short, no index
+        let mut crashed = [mp4_box(b"ftyp", b"isom"), fragments.clone()].concat();
+        crashed.truncate(crashed.len() - 10);
+        std::fs::write(&input, &crashed).unwrap();
+
+        rebuild_fragmented_mp4(&input, &reference, &output).unwrap();
+        let rebuilt = std::fs::read(&output).unwrap();
+        let expected = [ftyp, moov, fragments[..fragments.len() - 10].to_vec()].concat();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(rebuilt, expected);
+    }
+}
@@ -0,0 +1,365 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Silence {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Silence {
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SilenceTrimOptions {
+    /// Level below which audio counts as silence, in dBFS
+    #[serde(default = "default_threshold_db")]
+    pub threshold_db: f64,
+    /// Only silences at least this long are shortened
+    #[serde(default = "default_min_silence_secs")]
+    pub min_silence_secs: f64,
+    /// How much of each long silence to leave in, split across both ends.
+    /// 0 removes the silence entirely.
+    #[serde(default = "default_keep_silence_secs")]
+    pub keep_silence_secs: f64,
+}
+
+fn default_threshold_db() -> f64 {
+    -40.0
+}
+
+fn default_min_silence_secs() -> f64 {
+    10.0
+}
+
+fn default_keep_silence_secs() -> f64 {
+    1.0
+}
+
+/// One kept stretch of the original recording. Consecutive entries describe the
+/// trimmed timeline from start to end.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct EdlEntry {
+    /// Where this stretch starts in the trimmed audio
+    pub trimmed_start: f64,
+    /// Where this stretch starts in the original recording
+    pub original_start: f64,
+    pub duration: f64,
+}
+
+/// Runs FFmpeg's `silencedetect` over a file. Returns the silences and the
+/// total duration of the input.
+pub fn detect_silences(
+    ffmpeg_path: &Path,
+    input: &Path,
+    threshold_db: f64,
+    min_duration: f64,
+) -> Result<(Vec<Silence>, f64), String> {
+    let filter = format!("silencedetect=noise={}dB:d={}", threshold_db, min_duration);
+    let output = Command::new(ffmpeg_path)
+        .arg("-i")
+        .arg(input)
+        .args(["-vn", "-af", &filter, "-f", "null", "-"])
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(format!("FFmpeg silence detection failed: {}", stderr));
+    }
+
+    let duration = parse_duration(&stderr)
+        .or_else(|| parse_last_time(&stderr))
+        .ok_or("Could not determine input duration")?;
+
+    Ok((parse_silencedetect_output(&stderr, duration), duration))
+}
+
+/// Parses lines like
+/// `[silencedetect @ 0x...] silence_start: 12.34` and
+/// `[silencedetect @ 0x...] silence_end: 20.5 | silence_duration: 8.16`.
+/// A silence still open at the end of the file is closed at `duration`.
+pub fn parse_silencedetect_output(stderr: &str, duration: f64) -> Vec<Silence> {
+    let mut silences = Vec::new();
+    let mut open_start: Option<f64> = None;
+
+    for line in stderr.lines() {
+        if let Some(value) = value_after(line, "silence_start:") {
+            open_start = Some(value.max(0.0));
+        } else if let Some(end) = value_after(line, "silence_end:") {
+            if let Some(start) = open_start.take() {
+                silences.push(Silence { start, end });
+            }
+        }
+    }
+
+    if let Some(start) = open_start {
+        if duration > start {
+            silences.push(Silence {
+                start,
+                end: duration,
+            });
+        }
+    }
+
+    silences
+}
+
+fn value_after(line: &str, key: &str) -> Option<f64> {
+    let rest = &line[line.find(key)? + key.len()..];
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+/// Reads `Duration: HH:MM:SS.mmm` from FFmpeg's input description
+pub fn parse_duration(stderr: &str) -> Option<f64> {
+    stderr
+        .lines()
+        .find_map(|line| line.split("Duration:").nth(1))
+        .and_then(|rest| rest.split(',').next())
+        .and_then(|value| parse_clock(value.trim()))
+}
+
+/// Reads the last `time=HH:MM:SS.mm` progress value, which is how far FFmpeg
+/// actually decoded (useful when the container reports no duration)
+pub fn parse_last_time(stderr: &str) -> Option<f64> {
+    let pos = stderr.rfind("time=")?;
+    stderr[pos + "time=".len()..]
+        .split_whitespace()
+        .next()
+        .and_then(parse_clock)
+}
+
+fn parse_clock(value: &str) -> Option<f64> {
+    let parts: Vec<&str> = value.split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let hours: f64 = parts[0].parse().ok()?;
+    let minutes: f64 = parts[1].parse().ok()?;
+    let seconds: f64 = parts[2].parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// Works out which parts of the recording to keep. Every silence at least
+/// `min_silence_secs` long is cut down to `keep_silence_secs`, half of it at
+/// each end so speech is never clipped.
+pub fn build_edl(
+    silences: &[Silence],
+    duration: f64,
+    options: &SilenceTrimOptions,
+) -> Vec<EdlEntry> {
+    let keep_edge = options.keep_silence_secs.max(0.0) / 2.0;
+
+    let mut edl = Vec::new();
+    let mut original_start = 0.0;
+    let mut trimmed_start = 0.0;
+
+    for silence in silences {
+        if silence.duration() < options.min_silence_secs {
+            continue;
+        }
+        let cut_start = silence.start + keep_edge;
+        let cut_end = (silence.end - keep_edge).min(duration);
+        if cut_end <= cut_start || cut_start < original_start {
+            continue;
+        }
+
+        // A silence right at the start leaves nothing to keep before it
+        let kept = cut_start - original_start;
+        if kept > 0.0 {
+            edl.push(EdlEntry {
+                trimmed_start,
+                original_start,
+                duration: kept,
+            });
+            trimmed_start += kept;
+        }
+        original_start = cut_end;
+    }
+
+    if duration > original_start {
+        edl.push(EdlEntry {
+            trimmed_start,
+            original_start,
+            duration: duration - original_start,
+        });
+    }
+
+    edl
+}
+
+/// Maps a timestamp in the trimmed audio back to the original recording
+pub fn to_original_time(edl: &[EdlEntry], trimmed: f64) -> f64 {
+    let entry = edl
+        .iter()
+        .rev()
+        .find(|entry| entry.trimmed_start <= trimmed)
+        .or_else(|| edl.first());
+
+    match entry {
+        Some(entry) => entry.original_start + (trimmed - entry.trimmed_start),
+        None => trimmed,
+    }
+}
+
+/// Maps a timestamp in the original recording to the trimmed audio. Times
+/// inside a cut land where the cut was made.
+pub fn to_trimmed_time(edl: &[EdlEntry], original: f64) -> f64 {
+    let entry = edl
+        .iter()
+        .rev()
+        .find(|entry| entry.original_start <= original)
+        .or_else(|| edl.first());
+
+    match entry {
+        Some(entry) => {
+            entry.trimmed_start + (original - entry.original_start).clamp(0.0, entry.duration)
+        }
+        None => original,
+    }
+}
+
+/// Builds the `aselect` expression that keeps only the EDL's stretches,
+/// e.g. `between(t,0.000,12.500)+between(t,30.250,95.000)`
+pub fn select_expression(edl: &[EdlEntry]) -> String {
+    edl.iter()
+        .map(|entry| {
+            format!(
+                "between(t,{:.3},{:.3})",
+                entry.original_start,
+                entry.original_start + entry.duration
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> SilenceTrimOptions {
+        SilenceTrimOptions {
+            threshold_db: -40.0,
+            min_silence_secs: 10.0,
+            keep_silence_secs: 1.0,
+        }
+    }
+
+    fn silence(start: f64, end: f64) -> Silence {
+        Silence { start, end }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn build_edl_keeps_everything_without_long_silences() {
+        let edl = build_edl(&[silence(5.0, 9.0)], 60.0, &options());
+        assert_eq!(edl.len(), 1);
+        assert_close(edl[0].original_start, 0.0);
+        assert_close(edl[0].duration, 60.0);
+    }
+
+    #[test]
+    fn build_edl_keeps_half_the_silence_at_each_edge() {
+        let edl = build_edl(&[silence(10.0, 30.0)], 60.0, &options());
+        assert_eq!(edl.len(), 2);
+        assert_close(edl[0].duration, 10.5);
+        assert_close(edl[1].trimmed_start, 10.5);
+        assert_close(edl[1].original_start, 29.5);
+        assert_close(edl[1].duration, 30.5);
+    }
+
+    #[test]
+    fn build_edl_handles_silences_at_start_and_end() {
+        let edl = build_edl(&[silence(0.0, 20.0), silence(45.0, 60.0)], 60.0, &options());
+        let kept: f64 = edl.iter().map(|entry| entry.duration).sum();
+        assert_close(kept, 0.5 + 26.0 + 0.5);
+        assert_close(edl[0].original_start, 0.0);
+        assert_close(edl[1].original_start, 19.5);
+        let last = edl.last().unwrap();
+        assert_close(last.original_start + last.duration, 60.0);
+    }
+
+    #[test]
+    fn build_edl_entries_are_contiguous_in_trimmed_time() {
+        let silences = [
+            silence(10.0, 30.0),
+            silence(40.0, 55.0),
+            silence(70.0, 71.0),
+        ];
+        let edl = build_edl(&silences, 100.0, &options());
+        for pair in edl.windows(2) {
+            assert_close(
+                pair[0].trimmed_start + pair[0].duration,
+                pair[1].trimmed_start,
+            );
+        }
+    }
+
+    #[test]
+    fn times_map_across_edl_boundaries() {
+        let edl = build_edl(&[silence(10.0, 30.0)], 60.0, &options());
+
+        assert_close(to_original_time(&edl, 0.0), 0.0);
+        assert_close(to_original_time(&edl, 10.4), 10.4);
+        // The first trimmed second after the cut is the end of the silence
+        assert_close(to_original_time(&edl, 10.5), 29.5);
+        assert_close(to_original_time(&edl, 20.0), 39.0);
+
+        assert_close(to_trimmed_time(&edl, 10.4), 10.4);
+        assert_close(to_trimmed_time(&edl, 29.5), 10.5);
+        assert_close(to_trimmed_time(&edl, 39.0), 20.0);
+    }
+
+    #[test]
+    fn times_inside_a_cut_land_on_the_cut() {
+        let edl = build_edl(&[silence(10.0, 30.0)], 60.0, &options());
+        assert_close(to_trimmed_time(&edl, 15.0), 10.5);
+        assert_close(to_trimmed_time(&edl, 29.0), 10.5);
+    }
+
+    #[test]
+    fn kept_times_round_trip() {
+        let edl = build_edl(
+            &[silence(10.0, 30.0), silence(40.0, 55.0)],
+            80.0,
+            &options(),
+        );
+        for original in [0.0, 5.0, 10.4, 29.5, 35.0, 40.4, 54.5, 79.0] {
+            assert_close(
+                to_original_time(&edl, to_trimmed_time(&edl, original)),
+                original,
+            );
+        }
+    }
+
+    #[test]
+    fn empty_edl_maps_times_unchanged() {
+        assert_close(to_original_time(&[], 12.0), 12.0);
+        assert_close(to_trimmed_time(&[], 12.0), 12.0);
+    }
+
+    #[test]
+    fn parses_silencedetect_output_and_closes_open_silence() {
+        let stderr = "[silencedetect @ 0x1] silence_start: 1.5\n\
+                      [silencedetect @ 0x1] silence_end: 4 | silence_duration: 2.5\n\
+                      [silencedetect @ 0x1] silence_start: 50\n";
+        let silences = parse_silencedetect_output(stderr, 60.0);
+        assert_eq!(silences.len(), 2);
+        assert_close(silences[0].start, 1.5);
+        assert_close(silences[0].end, 4.0);
+        assert_close(silences[1].end, 60.0);
+    }
+}
@@ -1,16 +1,33 @@
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::process::Command;
 use tauri::{command, AppHandle, Manager};
 
+use crate::audio_clip;
+use crate::audio_format::AudioFormat;
+use crate::audio_processor;
 use crate::audio_utils;
+use crate::craig_import;
 
 const MAX_FILE_SIZE: usize = 25 * 1024 * 1024; // 25MB in bytes
 
+/// Number of uploads a file of `file_size` bytes is split into
+pub fn chunk_count(file_size: usize) -> usize {
+    if file_size <= MAX_FILE_SIZE {
+        1
+    } else {
+        (file_size as f64 / (1024.0 * 1024.0) / 20.0).ceil() as usize
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TranscriptionRequest {
     pub audio_data: Vec<u8>,
     pub api_key: String,
+    /// Format of `audio_data`
+    #[serde(default)]
+    pub format: AudioFormat,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -18,6 +35,35 @@ pub struct TranscriptionResponse {
     pub text: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptSegment {
+    /// Start time in seconds from the beginning of the session audio
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VerboseTranscriptionResponse {
+    #[serde(default)]
+    segments: Vec<TranscriptSegment>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpeakerTranscriptionRequest {
+    pub session_id: String,
+    pub api_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpeakerTranscriptionResponse {
+    /// Speaker-labelled transcript, one line per change of speaker
+    pub text: String,
+    pub segments: Vec<TranscriptSegment>,
+}
+
 #[command]
 pub async fn transcribe_audio(
     app: AppHandle,
@@ -27,7 +73,7 @@ pub async fn transcribe_audio(
         return transcribe_large_file(app, request).await;
     }
 
-    transcribe_chunk(&request.audio_data, &request.api_key).await
+    transcribe_chunk(&request.audio_data, request.format, &request.api_key).await
 }
 
 async fn transcribe_large_file(
@@ -43,7 +89,7 @@ async fn transcribe_large_file(
     std::fs::create_dir_all(&temp_dir)
         .map_err(|e| format!("Failed to create temp directory at {:?}: {}", temp_dir, e))?;
 
-    let input_file = temp_dir.join("input_audio.mp3");
+    let input_file = temp_dir.join(format!("input_audio.{}", request.format.extension()));
     std::fs::write(&input_file, &request.audio_data)
         .map_err(|e| format!("Failed to write temp audio file to {:?}: {}", input_file, e))?;
 
@@ -82,15 +128,16 @@ async fn transcribe_large_file(
             continue;
         }
 
-        let chunk_file = temp_dir.join(format!("chunk_{}.mp3", i));
+        let chunk_file = temp_dir.join(format!("chunk_{}.{}", i, request.format.extension()));
 
         eprintln!("Extracting chunk {}: start={:.2}s, duration={:.2}s", i, start_time, actual_chunk_duration);
-        extract_audio_chunk(
+        audio_clip::extract_audio_segment(
             &ffmpeg_path,
             &input_file,
             &chunk_file,
             start_time,
             actual_chunk_duration,
+            None,
         )
         .map_err(|e| format!("Failed to extract chunk {} (start: {:.2}s, duration: {:.2}s): {}", i, start_time, actual_chunk_duration, e))?;
 
@@ -108,7 +155,7 @@ async fn transcribe_large_file(
             .map_err(|e| format!("Failed to read chunk file {:?}: {}", chunk_file, e))?;
 
         eprintln!("Transcribing chunk {}/{} ({} bytes)...", i + 1, num_chunks, chunk_data.len());
-        let chunk_transcript = transcribe_chunk(&chunk_data, &request.api_key)
+        let chunk_transcript = transcribe_chunk(&chunk_data, request.format, &request.api_key)
             .await
             .map_err(|e| format!("Failed to transcribe chunk {}: {}", i, e))?;
         
@@ -132,10 +179,214 @@ async fn transcribe_large_file(
     })
 }
 
+/// Transcribes every kept speaker track of a session separately, so each segment
+/// is attributed to the person whose microphone it came from
+#[command]
+pub async fn transcribe_speaker_tracks(
+    app: AppHandle,
+    request: SpeakerTranscriptionRequest,
+) -> Result<SpeakerTranscriptionResponse, String> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not resolve app data directory: {:?}", e))?;
+
+    let session_dir = app_data_dir.join("sessions").join(&request.session_id);
+    let tracks_dir = session_dir.join("tracks");
+    let speakers = craig_import::load_speaker_tracks(&session_dir)?;
+    if speakers.is_empty() {
+        return Err(format!(
+            "Session {} has no speaker tracks",
+            request.session_id
+        ));
+    }
+
+    let ffmpeg_path = audio_utils::get_ffmpeg_path(&app)
+        .map_err(|e| format!("Failed to get FFmpeg path: {}", e))?;
+
+    let temp_dir = app_data_dir
+        .join("temp_speaker_tracks")
+        .join(&request.session_id);
+    std::fs::create_dir_all(&temp_dir)
+        .map_err(|e| format!("Failed to create temp directory at {:?}: {}", temp_dir, e))?;
+
+    let mut segments = Vec::new();
+    for speaker in &speakers {
+        let track_path = tracks_dir.join(audio_processor::track_file_name(speaker.track_index));
+        eprintln!(
+            "Transcribing track {} ({})",
+            speaker.track_index,
+            speaker.label()
+        );
+
+        let track_segments =
+            transcribe_file_segments(&ffmpeg_path, &track_path, &request.api_key, &temp_dir)
+                .await
+                .map_err(|e| {
+                    format!("Failed to transcribe track for {}: {}", speaker.label(), e)
+                })?;
+
+        segments.extend(track_segments.into_iter().map(|segment| TranscriptSegment {
+            speaker: Some(speaker.label()),
+            ..segment
+        }));
+    }
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+
+    segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    Ok(SpeakerTranscriptionResponse {
+        text: format_speaker_transcript(&segments),
+        segments,
+    })
+}
+
+fn format_speaker_transcript(segments: &[TranscriptSegment]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current_speaker: Option<&str> = None;
+
+    for segment in segments {
+        let text = segment.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let speaker = segment.speaker.as_deref().unwrap_or("Unknown");
+        match lines.last_mut() {
+            Some(line) if current_speaker == Some(speaker) => {
+                line.push(' ');
+                line.push_str(text);
+            }
+            _ => {
+                lines.push(format!(
+                    "[{}] {}: {}",
+                    format_timestamp(segment.start),
+                    speaker,
+                    text
+                ));
+                current_speaker = Some(speaker);
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+pub fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        total / 3600,
+        (total % 3600) / 60,
+        total % 60
+    )
+}
+
+/// Transcribes a file with segment timestamps, splitting it into chunks when it
+/// is over the API upload limit
+pub async fn transcribe_file_segments(
+    ffmpeg_path: &Path,
+    audio_file: &Path,
+    api_key: &str,
+    temp_dir: &Path,
+) -> Result<Vec<TranscriptSegment>, String> {
+    let file_size = std::fs::metadata(audio_file)
+        .map_err(|e| format!("Failed to read {:?}: {}", audio_file, e))?
+        .len() as usize;
+    let format = file_format(audio_file)?;
+
+    if file_size <= MAX_FILE_SIZE {
+        let audio_data = std::fs::read(audio_file)
+            .map_err(|e| format!("Failed to read {:?}: {}", audio_file, e))?;
+        return transcribe_chunk_segments(&audio_data, format, api_key).await;
+    }
+
+    let duration = get_audio_duration(ffmpeg_path, audio_file)
+        .map_err(|e| format!("Failed to get audio duration: {}", e))?;
+
+    let num_chunks = chunk_count(file_size);
+    let chunk_duration = duration / num_chunks as f64;
+
+    let mut segments = Vec::new();
+    for i in 0..num_chunks {
+        let start_time = i as f64 * chunk_duration;
+        let actual_chunk_duration = chunk_duration.min(duration - start_time);
+        if actual_chunk_duration < 0.1 {
+            continue;
+        }
+
+        // Chunks are stream copies, so they keep the source's container
+        let chunk_file = temp_dir.join(format!("segment_chunk_{}.{}", i, format.extension()));
+        audio_clip::extract_audio_segment(
+            ffmpeg_path,
+            audio_file,
+            &chunk_file,
+            start_time,
+            actual_chunk_duration,
+            None,
+        )
+        .map_err(|e| format!("Failed to extract chunk {}: {}", i, e))?;
+
+        let chunk_data = std::fs::read(&chunk_file)
+            .map_err(|e| format!("Failed to read chunk file {:?}: {}", chunk_file, e))?;
+        let _ = std::fs::remove_file(&chunk_file);
+
+        let chunk_segments = transcribe_chunk_segments(&chunk_data, format, api_key)
+            .await
+            .map_err(|e| format!("Failed to transcribe chunk {}: {}", i, e))?;
+
+        segments.extend(chunk_segments.into_iter().map(|segment| TranscriptSegment {
+            start: segment.start + start_time,
+            end: segment.end + start_time,
+            ..segment
+        }));
+    }
+
+    Ok(segments)
+}
+
+/// Format of a session audio file, from its extension
+pub fn file_format(audio_file: &Path) -> Result<AudioFormat, String> {
+    audio_file
+        .extension()
+        .and_then(|ext| AudioFormat::from_extension(&ext.to_string_lossy()))
+        .ok_or_else(|| format!("Unsupported audio format: {:?}", audio_file))
+}
+
+/// File name the upload is labelled with. The API goes by the extension and
+/// knows Ogg Opus as `.ogg`, not `.opus`.
+pub fn upload_file_name(format: AudioFormat) -> String {
+    match format {
+        AudioFormat::Opus => "audio.ogg".to_string(),
+        _ => format!("audio.{}", format.extension()),
+    }
+}
+
 async fn transcribe_chunk(
     audio_data: &[u8],
+    format: AudioFormat,
     api_key: &str,
 ) -> Result<TranscriptionResponse, String> {
+    post_transcription(audio_data, format, api_key, "json").await
+}
+
+pub async fn transcribe_chunk_segments(
+    audio_data: &[u8],
+    format: AudioFormat,
+    api_key: &str,
+) -> Result<Vec<TranscriptSegment>, String> {
+    let transcription: VerboseTranscriptionResponse =
+        post_transcription(audio_data, format, api_key, "verbose_json").await?;
+    Ok(transcription.segments)
+}
+
+async fn post_transcription<T: DeserializeOwned>(
+    audio_data: &[u8],
+    format: AudioFormat,
+    api_key: &str,
+    response_format: &str,
+) -> Result<T, String> {
     if audio_data.is_empty() {
         return Err("Audio data is empty".to_string());
     }
@@ -150,11 +401,12 @@ async fn transcribe_chunk(
         .part(
             "file",
             reqwest::multipart::Part::bytes(audio_data.to_vec())
-                .file_name("audio.mp3")
-                .mime_str("audio/mpeg")
+                .file_name(upload_file_name(format))
+                .mime_str(format.mime_type())
                 .map_err(|e| format!("Failed to set mime type: {}", e))?,
         )
-        .text("model", "whisper-1");
+        .text("model", "whisper-1")
+        .text("response_format", response_format.to_string());
 
     let response = client
         .post("https://api.openai.com/v1/audio/transcriptions")
@@ -177,7 +429,7 @@ async fn transcribe_chunk(
         ));
     }
 
-    let transcription: TranscriptionResponse = response
+    let transcription: T = response
         .json()
         .await
         .map_err(|e| format!("Failed to parse OpenAI API response: {}", e))?;
@@ -185,7 +437,7 @@ async fn transcribe_chunk(
     Ok(transcription)
 }
 
-fn get_audio_duration(ffmpeg_path: &Path, audio_file: &Path) -> Result<f64, String> {
+pub fn get_audio_duration(ffmpeg_path: &Path, audio_file: &Path) -> Result<f64, String> {
     let ffmpeg_str = ffmpeg_path.to_str().ok_or("Invalid FFmpeg path")?;
     let audio_str = audio_file.to_str().ok_or("Invalid audio file path")?;
 
@@ -282,55 +534,22 @@ fn extract_duration_from_ffmpeg_output(stderr: &str) -> Option<f64> {
     None
 }
 
-fn extract_audio_chunk(
-    ffmpeg_path: &Path,
-    input: &Path,
-    output: &Path,
-    start_time: f64,
-    duration: f64,
-) -> Result<(), String> {
-    let ffmpeg_str = ffmpeg_path.to_str().ok_or("Invalid FFmpeg path")?;
-    let input_str = input.to_str().ok_or("Invalid input path")?;
-    let output_str = output.to_str().ok_or("Invalid output path")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if start_time < 0.0 {
-        return Err("Start time cannot be negative".to_string());
-    }
-    if duration <= 0.0 {
-        return Err(format!("Duration must be positive, got: {}", duration));
+    #[test]
+    fn file_format_comes_from_the_extension() {
+        assert_eq!(file_format(Path::new("/s/audio.opus")), Ok(AudioFormat::Opus));
+        assert_eq!(file_format(Path::new("/s/audio.M4A")), Ok(AudioFormat::Aac));
+        assert!(file_format(Path::new("/s/audio.webm")).is_err());
+        assert!(file_format(Path::new("/s/audio")).is_err());
     }
 
-    let result = Command::new(ffmpeg_str)
-        .args([
-            "-i",
-            input_str,
-            "-ss",
-            &format!("{:.3}", start_time), 
-            "-t",
-            &format!("{:.3}", duration),
-            "-acodec",
-            "copy",
-            "-avoid_negative_ts",
-            "make_zero", 
-            "-y",
-            output_str,
-        ])
-        .output();
-
-    match result {
-        Ok(output) => {
-            if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                return Err(format!(
-                    "FFmpeg extraction failed. Exit code: {:?}. Stderr: {}. Stdout: {}",
-                    output.status.code(),
-                    stderr,
-                    stdout
-                ));
-            }
-            Ok(())
-        }
-        Err(e) => Err(format!("Failed to run FFmpeg: {}", e)),
+    #[test]
+    fn opus_uploads_are_named_ogg() {
+        assert_eq!(upload_file_name(AudioFormat::Opus), "audio.ogg");
+        assert_eq!(upload_file_name(AudioFormat::Mp3), "audio.mp3");
+        assert_eq!(upload_file_name(AudioFormat::Aac), "audio.m4a");
     }
 }
\ No newline at end of file
@@ -60,3 +60,14 @@ pub fn get_ffmpeg_path(app: &AppHandle) -> Result<PathBuf, String> {
 
 }
 
+/// Runs blocking work (FFmpeg, file I/O) on the blocking thread pool, so it
+/// doesn't hold up the async runtime that serves commands and events
+pub async fn run_blocking<T, F>(work: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| format!("Background task failed: {}", e))?
+}
@@ -0,0 +1,388 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::UNIX_EPOCH;
+use tauri::{command, AppHandle, Manager};
+
+use crate::audio_processor;
+use crate::audio_utils;
+
+const WAVEFORM_FILE: &str = "waveform.bin";
+const MAGIC: &[u8; 4] = b"LMWF";
+const FORMAT_VERSION: u32 = 1;
+
+const DECODE_SAMPLE_RATE: u32 = 16000;
+/// The finest level has one peak per 10 ms
+const BASE_SAMPLES_PER_PEAK: u32 = 160;
+/// Each level is this many times coarser than the one before
+const LEVEL_FACTOR: u32 = 4;
+const LEVEL_COUNT: usize = 6;
+
+#[derive(Debug, Deserialize)]
+pub struct WaveformRequest {
+    pub session_id: String,
+    /// Start of the range in seconds; defaults to the beginning
+    pub start_secs: Option<f64>,
+    /// End of the range in seconds; defaults to the end of the audio
+    pub end_secs: Option<f64>,
+    /// Number of peaks to return across the range
+    pub resolution: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WaveformResponse {
+    pub duration: f64,
+    pub start_secs: f64,
+    pub end_secs: f64,
+    /// Lowest sample in each bucket, from -1 to 1
+    pub min: Vec<f32>,
+    /// Highest sample in each bucket, from -1 to 1
+    pub max: Vec<f32>,
+}
+
+/// Min/max peaks of a session's audio at several zoom levels. Stored as
+/// `waveform.bin` in the session directory:
+///
+/// ```text
+/// "LMWF" | version u32 | source size u64 | source mtime (ms) u64 |
+/// sample rate u32 | sample count u64 | level count u32 |
+/// per level: samples per peak u32, peak count u32, then peak count
+/// (min i8, max i8) pairs
+/// ```
+///
+/// All integers are little-endian.
+struct Waveform {
+    source_size: u64,
+    source_modified_ms: u64,
+    sample_rate: u32,
+    sample_count: u64,
+    levels: Vec<PeakLevel>,
+}
+
+struct PeakLevel {
+    samples_per_peak: u32,
+    /// Interleaved min/max pairs
+    peaks: Vec<i8>,
+}
+
+#[command]
+pub async fn get_waveform(
+    app: AppHandle,
+    request: WaveformRequest,
+) -> Result<WaveformResponse, String> {
+    audio_utils::run_blocking(move || load_waveform(app, request)).await
+}
+
+fn load_waveform(app: AppHandle, request: WaveformRequest) -> Result<WaveformResponse, String> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?;
+
+    let session_dir = app_data_dir.join("sessions").join(&request.session_id);
+    let audio_path = audio_processor::session_audio_path(&session_dir);
+    let (source_size, source_modified_ms) = source_fingerprint(&audio_path)?;
+
+    let waveform_path = session_dir.join(WAVEFORM_FILE);
+    let waveform = match read_waveform(&waveform_path) {
+        Some(waveform)
+            if waveform.source_size == source_size
+                && waveform.source_modified_ms == source_modified_ms =>
+        {
+            waveform
+        }
+        // Missing, unreadable or made from an older version of the audio
+        _ => {
+            let ffmpeg_path = audio_utils::get_ffmpeg_path(&app)?;
+            let waveform = generate_waveform(&ffmpeg_path, &audio_path)?;
+            write_waveform(&waveform_path, &waveform)?;
+            waveform
+        }
+    };
+
+    Ok(waveform.range(
+        request.start_secs.unwrap_or(0.0),
+        request.end_secs,
+        request.resolution,
+    ))
+}
+
+fn source_fingerprint(audio_path: &Path) -> Result<(u64, u64), String> {
+    let metadata = std::fs::metadata(audio_path)
+        .map_err(|e| format!("Failed to read session audio: {}", e))?;
+    let modified_ms = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    Ok((metadata.len(), modified_ms))
+}
+
+/// Decodes the audio once as mono PCM and builds every level from the finest
+fn generate_waveform(ffmpeg_path: &Path, audio_path: &Path) -> Result<Waveform, String> {
+    let (source_size, source_modified_ms) = source_fingerprint(audio_path)?;
+
+    let mut child = Command::new(ffmpeg_path)
+        .arg("-i")
+        .arg(audio_path)
+        .args(["-vn", "-ac", "1", "-ar"])
+        .arg(DECODE_SAMPLE_RATE.to_string())
+        .args(["-f", "s16le", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    let stdout = child.stdout.take().ok_or("Failed to read FFmpeg output")?;
+    let mut reader = BufReader::new(stdout);
+    let mut buffer = vec![0u8; BASE_SAMPLES_PER_PEAK as usize * 2];
+    let mut base = Vec::new();
+    let mut sample_count: u64 = 0;
+
+    loop {
+        // The last bucket may be partial
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match reader.read(&mut buffer[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(e) => return Err(format!("Failed to read decoded audio: {}", e)),
+            }
+        }
+        if filled < 2 {
+            break;
+        }
+
+        let (mut low, mut high) = (i16::MAX, i16::MIN);
+        for bytes in buffer[..filled].chunks_exact(2) {
+            let sample = i16::from_le_bytes([bytes[0], bytes[1]]);
+            low = low.min(sample);
+            high = high.max(sample);
+        }
+        base.push((low >> 8) as i8);
+        base.push((high >> 8) as i8);
+        sample_count += (filled / 2) as u64;
+
+        if filled < buffer.len() {
+            break;
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+    if !status.success() {
+        return Err(format!("FFmpeg could not decode {:?}", audio_path));
+    }
+
+    let mut levels = vec![PeakLevel {
+        samples_per_peak: BASE_SAMPLES_PER_PEAK,
+        peaks: base,
+    }];
+    while levels.len() < LEVEL_COUNT {
+        let finer = levels.last().unwrap();
+        let peaks = finer
+            .peaks
+            .chunks(2 * LEVEL_FACTOR as usize)
+            .flat_map(|group| {
+                let low = group.iter().step_by(2).copied().min().unwrap_or(0);
+                let high = group.iter().skip(1).step_by(2).copied().max().unwrap_or(0);
+                [low, high]
+            })
+            .collect();
+        levels.push(PeakLevel {
+            samples_per_peak: finer.samples_per_peak * LEVEL_FACTOR,
+            peaks,
+        });
+    }
+
+    Ok(Waveform {
+        source_size,
+        source_modified_ms,
+        sample_rate: DECODE_SAMPLE_RATE,
+        sample_count,
+        levels,
+    })
+}
+
+impl Waveform {
+    fn duration(&self) -> f64 {
+        self.sample_count as f64 / self.sample_rate as f64
+    }
+
+    /// Peaks for `start..end` in `resolution` buckets, taken from the coarsest
+    /// level that still has at least one peak per bucket
+    fn range(&self, start: f64, end: Option<f64>, resolution: usize) -> WaveformResponse {
+        let duration = self.duration();
+        let start = start.clamp(0.0, duration);
+        let end = end.unwrap_or(duration).clamp(start, duration);
+        let resolution = resolution.max(1);
+
+        let level = self
+            .levels
+            .iter()
+            .rev()
+            .find(|level| {
+                let peaks_in_range =
+                    (end - start) * self.sample_rate as f64 / level.samples_per_peak as f64;
+                peaks_in_range >= resolution as f64
+            })
+            .unwrap_or(&self.levels[0]);
+
+        let peaks_per_second = self.sample_rate as f64 / level.samples_per_peak as f64;
+        let peak_count = level.peaks.len() / 2;
+        let mut min = Vec::with_capacity(resolution);
+        let mut max = Vec::with_capacity(resolution);
+
+        for bucket in 0..resolution {
+            let from = start + (end - start) * bucket as f64 / resolution as f64;
+            let to = start + (end - start) * (bucket + 1) as f64 / resolution as f64;
+            let first = ((from * peaks_per_second) as usize).min(peak_count);
+            let last = ((to * peaks_per_second).ceil() as usize)
+                .max(first + 1)
+                .min(peak_count);
+
+            let pairs = &level.peaks[first * 2..last * 2];
+            let low = pairs.iter().step_by(2).copied().min().unwrap_or(0);
+            let high = pairs.iter().skip(1).step_by(2).copied().max().unwrap_or(0);
+            min.push(low as f32 / 128.0);
+            max.push(high as f32 / 128.0);
+        }
+
+        WaveformResponse {
+            duration,
+            start_secs: start,
+            end_secs: end,
+            min,
+            max,
+        }
+    }
+}
+
+fn write_waveform(path: &Path, waveform: &Waveform) -> Result<(), String> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&waveform.source_size.to_le_bytes());
+    bytes.extend_from_slice(&waveform.source_modified_ms.to_le_bytes());
+    bytes.extend_from_slice(&waveform.sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&waveform.sample_count.to_le_bytes());
+    bytes.extend_from_slice(&(waveform.levels.len() as u32).to_le_bytes());
+    for level in &waveform.levels {
+        bytes.extend_from_slice(&level.samples_per_peak.to_le_bytes());
+        bytes.extend_from_slice(&((level.peaks.len() / 2) as u32).to_le_bytes());
+        bytes.extend(level.peaks.iter().map(|&p| p as u8));
+    }
+
+    std::fs::write(path, bytes).map_err(|e| format!("Failed to write waveform: {}", e))
+}
+
+fn read_waveform(path: &Path) -> Option<Waveform> {
+    let bytes = std::fs::read(path).ok()?;
+    let mut cursor = bytes.as_slice();
+
+    let mut take = |len: usize| -> Option<&[u8]> {
+        if cursor.len() < len {
+            return None;
+        }
+        let (head, rest) = cursor.split_at(len);
+        cursor = rest;
+        Some(head)
+    };
+
+    if take(4)? != MAGIC {
+        return None;
+    }
+    let u32_at = |b: &[u8]| u32::from_le_bytes(b.try_into().unwrap());
+    let u64_at = |b: &[u8]| u64::from_le_bytes(b.try_into().unwrap());
+
+    if u32_at(take(4)?) != FORMAT_VERSION {
+        return None;
+    }
+    let source_size = u64_at(take(8)?);
+    let source_modified_ms = u64_at(take(8)?);
+    let sample_rate = u32_at(take(4)?);
+    let sample_count = u64_at(take(8)?);
+    let level_count = u32_at(take(4)?) as usize;
+
+    // Each level starts with an 8-byte header after the 40-byte file header,
+    // so a count the file is too short for means it's damaged; don't
+    // allocate for it
+    if level_count > (bytes.len() - 40) / 8 {
+        return None;
+    }
+
+    let mut levels = Vec::with_capacity(level_count);
+    for _ in 0..level_count {
+        let samples_per_peak = u32_at(take(4)?);
+        let peak_count = u32_at(take(4)?) as usize;
+        let peaks = take(peak_count * 2)?.iter().map(|&b| b as i8).collect();
+        levels.push(PeakLevel {
+            samples_per_peak,
+            peaks,
+        });
+    }
+
+    if levels.is_empty() || sample_rate == 0 {
+        return None;
+    }
+
+    Some(Waveform {
+        source_size,
+        source_modified_ms,
+        sample_rate,
+        sample_count,
+        levels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waveform() -> Waveform {
+        Waveform {
+            source_size: 1234,
+            source_modified_ms: 5678,
+            sample_rate: DECODE_SAMPLE_RATE,
+            sample_count: 320,
+            levels: vec![PeakLevel {
+                samples_per_peak: BASE_SAMPLES_PER_PEAK,
+                peaks: vec![-10, 10, -20, 20],
+            }],
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}_{}.bin", name, std::process::id()))
+    }
+
+    #[test]
+    fn read_waveform_round_trips() {
+        let path = temp_path("waveform_round_trip");
+        write_waveform(&path, &waveform()).unwrap();
+        let read = read_waveform(&path);
+        let _ = std::fs::remove_file(&path);
+
+        let read = read.unwrap();
+        assert_eq!(read.source_size, 1234);
+        assert_eq!(read.sample_count, 320);
+        assert_eq!(read.levels.len(), 1);
+        assert_eq!(read.levels[0].peaks, [-10, 10, -20, 20]);
+    }
+
+    #[test]
+    fn read_waveform_rejects_a_level_count_the_file_cannot_hold() {
+        let path = temp_path("waveform_level_count");
+        write_waveform(&path, &waveform()).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[36..40].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        let read = read_waveform(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert!(read.is_none());
+    }
+}
@@ -0,0 +1,531 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, Manager};
+
+use crate::audio_processor::{self, ProcessAudioRequest, TrackLayout};
+use crate::drizzle_proxy;
+
+const SPEAKERS_FILE: &str = "speakers.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CraigImportRequest {
+    /// Path to the zip Craig produces (one FLAC per participant plus `info.txt`)
+    pub archive_path: String,
+    pub session_id: String,
+    pub campaign_id: String,
+    #[serde(default = "default_match_levels")]
+    pub match_levels: bool,
+}
+
+fn default_match_levels() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CraigImportResponse {
+    pub output_path: String,
+    pub speakers: Vec<SpeakerTrack>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpeakerMappingRequest {
+    pub campaign_id: String,
+    pub speaker_name: String,
+    pub player_id: Option<String>,
+    #[serde(default)]
+    pub is_dm: bool,
+    /// When set, the session's kept tracks are relabelled as well
+    pub session_id: Option<String>,
+}
+
+/// One kept speaker track of a session and who it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeakerTrack {
+    /// Index of the kept track (`tracks/track_{index}.mp3`)
+    pub track_index: usize,
+    /// Discord username the track was recorded for
+    pub speaker_name: String,
+    pub player_id: Option<String>,
+    pub player_name: Option<String>,
+    pub character_name: Option<String>,
+    #[serde(default)]
+    pub is_dm: bool,
+}
+
+impl SpeakerTrack {
+    /// Name used to label transcript segments from this track
+    pub fn label(&self) -> String {
+        match (&self.character_name, &self.player_name) {
+            (Some(character), Some(player)) => format!("{} ({})", character, player),
+            (None, Some(player)) => player.clone(),
+            _ => self.speaker_name.clone(),
+        }
+    }
+}
+
+struct CampaignSpeakers {
+    dm_name: String,
+    players: Vec<(String, String, String)>,
+    mappings: Vec<(String, Option<String>, bool)>,
+}
+
+#[command]
+pub async fn import_craig_archive(
+    app: AppHandle,
+    request: CraigImportRequest,
+) -> Result<CraigImportResponse, String> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?;
+
+    let extract_dir = app_data_dir
+        .join("sessions")
+        .join(&request.session_id)
+        .join("craig");
+    let _ = std::fs::remove_dir_all(&extract_dir);
+    std::fs::create_dir_all(&extract_dir)
+        .map_err(|e| format!("Failed to create extraction directory: {}", e))?;
+
+    let extracted = extract_archive(Path::new(&request.archive_path), &extract_dir)
+        .map_err(|e| format!("Failed to unpack Craig archive: {}", e))?;
+
+    let info = extracted
+        .iter()
+        .find(|p| {
+            p.file_name()
+                .is_some_and(|n| n.eq_ignore_ascii_case("info.txt"))
+        })
+        .map(std::fs::read_to_string)
+        .transpose()
+        .map_err(|e| format!("Failed to read info.txt: {}", e))?;
+    let usernames = info.as_deref().map(parse_info_tracks).unwrap_or_default();
+
+    // Craig names tracks "{number}-{username}.flac", numbered from 1 in the
+    // order they are listed in info.txt
+    let mut tracks: Vec<(u32, String, PathBuf)> = extracted
+        .iter()
+        .filter(|p| is_track_file(p))
+        .filter_map(|p| {
+            let stem = p.file_stem()?.to_string_lossy().to_string();
+            let (number, file_username) = stem.split_once('-')?;
+            let number: u32 = number.parse().ok()?;
+            let username = usernames
+                .iter()
+                .find(|(n, _)| *n == number)
+                .map(|(_, name)| name.clone())
+                .unwrap_or_else(|| file_username.to_string());
+            Some((number, username, p.clone()))
+        })
+        .collect();
+    tracks.sort_by_key(|(number, _, _)| *number);
+
+    if tracks.is_empty() {
+        return Err("Craig archive contains no speaker tracks".to_string());
+    }
+
+    let pool = drizzle_proxy::pool(&app);
+    let campaign = load_campaign_speakers(&pool, &request.campaign_id).await?;
+
+    let mut speakers = Vec::with_capacity(tracks.len());
+    for (track_index, (_, username, _)) in tracks.iter().enumerate() {
+        let speaker = resolve_speaker(&campaign, track_index, username);
+        if speaker.player_id.is_some() || speaker.is_dm {
+            remember_mapping(&pool, &request.campaign_id, &speaker).await?;
+        }
+        speakers.push(speaker);
+    }
+
+    let process_request = ProcessAudioRequest {
+        file_paths: tracks
+            .iter()
+            .map(|(_, _, path)| path.to_string_lossy().to_string())
+            .collect(),
+        output_filename: "audio.mp3".to_string(),
+        session_id: request.session_id.clone(),
+        layout: TrackLayout::Simultaneous,
+        match_levels: request.match_levels,
+        ..Default::default()
+    };
+    let processed = audio_processor::process_audio_files(app.clone(), process_request).await?;
+
+    let session_dir = app_data_dir.join("sessions").join(&request.session_id);
+    save_speaker_tracks(&session_dir, &speakers)?;
+
+    let _ = std::fs::remove_dir_all(&extract_dir);
+
+    Ok(CraigImportResponse {
+        output_path: processed.output_path,
+        speakers,
+    })
+}
+
+/// Assigns a Discord username to a player (or the DM) for a campaign, and
+/// optionally relabels an already imported session
+#[command]
+pub async fn set_speaker_mapping(
+    app: AppHandle,
+    request: SpeakerMappingRequest,
+) -> Result<(), String> {
+    let pool = drizzle_proxy::pool(&app);
+
+    sqlx::query(
+        "INSERT INTO speaker_mappings (id, campaign_id, speaker_name, player_id, is_dm) \
+         VALUES (?, ?, ?, ?, ?) \
+         ON CONFLICT(campaign_id, speaker_name) DO UPDATE SET \
+         player_id = excluded.player_id, is_dm = excluded.is_dm, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(uuid::Uuid::now_v7().to_string())
+    .bind(&request.campaign_id)
+    .bind(&request.speaker_name)
+    .bind(&request.player_id)
+    .bind(request.is_dm)
+    .execute(&pool)
+    .await
+    .map_err(|e| format!("Failed to save speaker mapping: {}", e))?;
+
+    let Some(session_id) = &request.session_id else {
+        return Ok(());
+    };
+
+    let session_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?
+        .join("sessions")
+        .join(session_id);
+
+    let campaign = load_campaign_speakers(&pool, &request.campaign_id).await?;
+    let speakers: Vec<SpeakerTrack> = load_speaker_tracks(&session_dir)?
+        .into_iter()
+        .map(|track| {
+            if track.speaker_name == request.speaker_name {
+                resolve_speaker(&campaign, track.track_index, &track.speaker_name)
+            } else {
+                track
+            }
+        })
+        .collect();
+
+    save_speaker_tracks(&session_dir, &speakers)
+}
+
+/// Reads the speaker labels of a session's kept tracks. Tracks kept without a
+/// speaker file (plain simultaneous processing) are labelled by number.
+///
+/// The speaker file sits next to `tracks/` rather than in it, since
+/// reprocessing replaces that directory wholesale.
+pub fn load_speaker_tracks(session_dir: &Path) -> Result<Vec<SpeakerTrack>, String> {
+    let tracks_dir = session_dir.join("tracks");
+    // Sessions imported before the file moved keep it in `tracks/` until
+    // they are reprocessed
+    let speakers_path = [
+        session_dir.join(SPEAKERS_FILE),
+        tracks_dir.join(SPEAKERS_FILE),
+    ]
+    .into_iter()
+    .find(|path| path.exists());
+    if let Some(speakers_path) = speakers_path {
+        let content = std::fs::read_to_string(&speakers_path)
+            .map_err(|e| format!("Failed to read speaker file: {}", e))?;
+        let speakers: Vec<SpeakerTrack> = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse speaker file: {}", e))?;
+        // A later run without separate tracks leaves the labels behind
+        return Ok(speakers
+            .into_iter()
+            .filter(|speaker| {
+                tracks_dir
+                    .join(audio_processor::track_file_name(speaker.track_index))
+                    .exists()
+            })
+            .collect());
+    }
+
+    let mut speakers = Vec::new();
+    while tracks_dir
+        .join(audio_processor::track_file_name(speakers.len()))
+        .exists()
+    {
+        let track_index = speakers.len();
+        speakers.push(SpeakerTrack {
+            track_index,
+            speaker_name: format!("Track {}", track_index + 1),
+            player_id: None,
+            player_name: None,
+            character_name: None,
+            is_dm: false,
+        });
+    }
+    Ok(speakers)
+}
+
+fn save_speaker_tracks(session_dir: &Path, speakers: &[SpeakerTrack]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(speakers)
+        .map_err(|e| format!("Failed to serialize speaker file: {}", e))?;
+    std::fs::write(session_dir.join(SPEAKERS_FILE), content)
+        .map_err(|e| format!("Failed to write speaker file: {}", e))?;
+    let _ = std::fs::remove_file(session_dir.join("tracks").join(SPEAKERS_FILE));
+    Ok(())
+}
+
+fn extract_archive(archive_path: &Path, dest: &Path) -> Result<Vec<PathBuf>, String> {
+    let file = File::open(archive_path).map_err(|e| format!("Failed to open archive: {}", e))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("Invalid zip archive: {}", e))?;
+
+    let mut extracted = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read archive entry {}: {}", i, e))?;
+
+        // enclosed_name rejects absolute paths and `..` components
+        let Some(relative) = entry.enclosed_name() else {
+            eprintln!("Skipping unsafe archive entry: {}", entry.name());
+            continue;
+        };
+        if entry.is_dir() {
+            continue;
+        }
+
+        let out_path = dest.join(relative);
+        if let Some(parent) = out_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        let mut out_file = File::create(&out_path)
+            .map_err(|e| format!("Failed to create {:?}: {}", out_path, e))?;
+        std::io::copy(&mut entry, &mut out_file)
+            .map_err(|e| format!("Failed to extract {:?}: {}", out_path, e))?;
+
+        extracted.push(out_path);
+    }
+
+    Ok(extracted)
+}
+
+/// Parses the "Tracks:" section of Craig's info.txt:
+///
+/// ```text
+/// Tracks:
+///     alice (123456789012345678)
+///     bob#1234 (234567890123456789)
+/// ```
+///
+/// Returns `(track number, username)` pairs, numbered from 1.
+fn parse_info_tracks(info: &str) -> Vec<(u32, String)> {
+    let mut tracks = Vec::new();
+    let mut in_tracks = false;
+
+    for line in info.lines() {
+        if line.trim_end() == "Tracks:" {
+            in_tracks = true;
+            continue;
+        }
+        if !in_tracks {
+            continue;
+        }
+        // The section is indented and ends at the first blank or unindented line
+        if line.trim().is_empty() || !line.starts_with(char::is_whitespace) {
+            break;
+        }
+
+        let entry = line.trim();
+        let username = match entry.rfind(" (") {
+            Some(pos) if entry.ends_with(')') => &entry[..pos],
+            _ => entry,
+        };
+        tracks.push((tracks.len() as u32 + 1, username.to_string()));
+    }
+
+    tracks
+}
+
+fn is_track_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| {
+            matches!(
+                ext.as_str(),
+                "flac" | "ogg" | "opus" | "m4a" | "aac" | "wav" | "mp3"
+            )
+        })
+}
+
+/// Lowercases and drops the legacy `#1234` discriminator and punctuation, so
+/// "Alice_B#0420" and "alice b" compare equal
+fn normalize_name(name: &str) -> String {
+    let name = name.split('#').next().unwrap_or(name);
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+async fn load_campaign_speakers(
+    pool: &SqlitePool,
+    campaign_id: &str,
+) -> Result<CampaignSpeakers, String> {
+    let dm_name: String = sqlx::query_scalar("SELECT dm_name FROM campaigns WHERE id = ?")
+        .bind(campaign_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("Failed to load campaign: {}", e))?
+        .ok_or_else(|| format!("Campaign {} not found", campaign_id))?;
+
+    let players =
+        sqlx::query_as("SELECT id, player_name, character_name FROM players WHERE campaign_id = ?")
+            .bind(campaign_id)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Failed to load players: {}", e))?;
+
+    let mappings = sqlx::query_as(
+        "SELECT speaker_name, player_id, is_dm FROM speaker_mappings WHERE campaign_id = ?",
+    )
+    .bind(campaign_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to load speaker mappings: {}", e))?;
+
+    Ok(CampaignSpeakers {
+        dm_name,
+        players,
+        mappings,
+    })
+}
+
+/// Matches a Discord username to a player: a remembered mapping wins, then an
+/// exact (normalized) match against player names or the DM name
+fn resolve_speaker(
+    campaign: &CampaignSpeakers,
+    track_index: usize,
+    username: &str,
+) -> SpeakerTrack {
+    let mut speaker = SpeakerTrack {
+        track_index,
+        speaker_name: username.to_string(),
+        player_id: None,
+        player_name: None,
+        character_name: None,
+        is_dm: false,
+    };
+
+    let remembered = campaign
+        .mappings
+        .iter()
+        .find(|(name, _, _)| name == username)
+        .map(|(_, player_id, is_dm)| (player_id.clone(), *is_dm));
+
+    let (player_id, is_dm) = remembered.unwrap_or_else(|| {
+        let normalized = normalize_name(username);
+        if normalized.is_empty() {
+            return (None, false);
+        }
+        let player_id = campaign
+            .players
+            .iter()
+            .find(|(_, player_name, _)| normalize_name(player_name) == normalized)
+            .map(|(id, _, _)| id.clone());
+        let is_dm = player_id.is_none() && normalize_name(&campaign.dm_name) == normalized;
+        (player_id, is_dm)
+    });
+
+    if is_dm {
+        speaker.is_dm = true;
+        speaker.player_name = Some(campaign.dm_name.clone());
+        speaker.character_name = Some("DM".to_string());
+    } else if let Some((id, player_name, character_name)) = player_id
+        .as_ref()
+        .and_then(|id| campaign.players.iter().find(|(pid, _, _)| pid == id))
+    {
+        speaker.player_id = Some(id.clone());
+        speaker.player_name = Some(player_name.clone());
+        speaker.character_name = Some(character_name.clone());
+    }
+
+    speaker
+}
+
+async fn remember_mapping(
+    pool: &SqlitePool,
+    campaign_id: &str,
+    speaker: &SpeakerTrack,
+) -> Result<(), String> {
+    // Existing mappings are left alone so a manual correction is never
+    // overwritten by a later automatic match
+    sqlx::query(
+        "INSERT INTO speaker_mappings (id, campaign_id, speaker_name, player_id, is_dm) \
+         VALUES (?, ?, ?, ?, ?) \
+         ON CONFLICT(campaign_id, speaker_name) DO NOTHING",
+    )
+    .bind(uuid::Uuid::now_v7().to_string())
+    .bind(campaign_id)
+    .bind(&speaker.speaker_name)
+    .bind(&speaker.player_id)
+    .bind(speaker.is_dm)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to save speaker mapping: {}", e))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speaker(track_index: usize, speaker_name: &str) -> SpeakerTrack {
+        SpeakerTrack {
+            track_index,
+            speaker_name: speaker_name.to_string(),
+            player_id: None,
+            player_name: None,
+            character_name: None,
+            is_dm: false,
+        }
+    }
+
+    fn write_tracks(tracks_dir: &Path, count: usize) {
+        std::fs::create_dir_all(tracks_dir).unwrap();
+        for index in 0..count {
+            std::fs::write(
+                tracks_dir.join(audio_processor::track_file_name(index)),
+                b"",
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn speaker_labels_survive_replacing_the_tracks() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let session_dir =
+            std::env::temp_dir().join(format!("craig_speakers_{}_{}", std::process::id(), nanos));
+        let tracks_dir = session_dir.join("tracks");
+        write_tracks(&tracks_dir, 2);
+        save_speaker_tracks(&session_dir, &[speaker(0, "alice"), speaker(1, "bob")]).unwrap();
+
+        // Reprocessing swaps in a freshly written tracks directory
+        std::fs::remove_dir_all(&tracks_dir).unwrap();
+        write_tracks(&tracks_dir, 2);
+        let reprocessed = load_speaker_tracks(&session_dir).unwrap();
+
+        // A run without separate tracks leaves no speakers to transcribe
+        std::fs::remove_dir_all(&tracks_dir).unwrap();
+        let without_tracks = load_speaker_tracks(&session_dir).unwrap();
+        let _ = std::fs::remove_dir_all(&session_dir);
+
+        let names: Vec<&str> = reprocessed
+            .iter()
+            .map(|s| s.speaker_name.as_str())
+            .collect();
+        assert_eq!(names, ["alice", "bob"]);
+        assert!(without_tracks.is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+/// Days since 1970-01-01 for a proleptic Gregorian date
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// `(year, month, day)` of the day `days` after 1970-01-01
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// Unix seconds as `2024-05-01T19:02:11.000Z`, the format the frontend stores
+/// dates in
+pub fn iso_timestamp(secs: f64) -> String {
+    let secs = secs.max(0.0) as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let rest = secs.rem_euclid(86400);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.000Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epoch_is_day_zero() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(iso_timestamp(0.0), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn leap_years_follow_the_gregorian_rules() {
+        // 2024 is a leap year, 1900 isn't (century), 2000 is (every 400 years)
+        assert_eq!(
+            days_from_civil(2024, 3, 1) - days_from_civil(2024, 2, 28),
+            2
+        );
+        assert_eq!(
+            days_from_civil(1900, 3, 1) - days_from_civil(1900, 2, 28),
+            1
+        );
+        assert_eq!(
+            days_from_civil(2000, 3, 1) - days_from_civil(2000, 2, 28),
+            2
+        );
+        assert_eq!(
+            civil_from_days(days_from_civil(2024, 2, 28) + 1),
+            (2024, 2, 29)
+        );
+        assert_eq!(
+            civil_from_days(days_from_civil(2023, 2, 28) + 1),
+            (2023, 3, 1)
+        );
+    }
+
+    #[test]
+    fn month_and_year_boundaries() {
+        assert_eq!(
+            civil_from_days(days_from_civil(2024, 1, 31) + 1),
+            (2024, 2, 1)
+        );
+        assert_eq!(
+            civil_from_days(days_from_civil(2024, 4, 30) + 1),
+            (2024, 5, 1)
+        );
+        assert_eq!(
+            civil_from_days(days_from_civil(2023, 12, 31) + 1),
+            (2024, 1, 1)
+        );
+        assert_eq!(
+            iso_timestamp(days_from_civil(2024, 12, 31) as f64 * 86400.0 + 86399.9),
+            "2024-12-31T23:59:59.000Z"
+        );
+    }
+
+    #[test]
+    fn known_timestamps() {
+        // 2024-05-01T19:02:11Z
+        assert_eq!(iso_timestamp(1714590131.0), "2024-05-01T19:02:11.000Z");
+        assert_eq!(days_from_civil(2024, 5, 1), 19844);
+    }
+
+    #[test]
+    fn days_round_trip() {
+        for days in (-800_000..800_000).step_by(997) {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+}
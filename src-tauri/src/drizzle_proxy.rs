@@ -4,72 +4,183 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::{
     query::Query,
-    sqlite::{SqliteArguments, SqliteRow},
-    Column, Row, Sqlite, SqlitePool, TypeInfo,
+    sqlite::{
+        SqliteArguments, SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow,
+        SqliteSynchronous,
+    },
+    Column, Row, Sqlite, SqliteConnection, SqlitePool, TypeInfo,
 };
-use std::path::PathBuf;
+use std::path::Path;
+use std::time::Duration;
 use tauri::Manager;
-use tauri::{command, AppHandle};
+use tauri::{command, AppHandle, State};
+
+/// How long a connection waits for another one's write lock before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+/// SQLite only allows one writer at a time, so a handful of connections is
+/// plenty for concurrent reads while a background job writes
+const MAX_CONNECTIONS: u32 = 8;
+
+/// The app database, opened once at startup and shared by every command
+pub struct Database(SqlitePool);
+
+impl Database {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self(pool)
+    }
+}
 
 #[derive(Debug, Deserialize)]
 pub struct SqlQuery {
     pub sql: String,
     pub params: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub method: QueryMethod,
+}
+
+/// What Drizzle's sqlite-proxy driver wants back from a query
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMethod {
+    /// Only the effect of a statement
+    Run,
+    #[default]
+    All,
+    Values,
+    /// The first row, if there is one
+    Get,
 }
 
+/// Result of `run_sql`, shaped by the query's method
 #[derive(Debug, Serialize)]
-pub struct SqlRow {
-    pub columns: Vec<String>,
-    pub values: Vec<serde_json::Value>,
+#[serde(untagged, rename_all_fields = "camelCase")]
+pub enum SqlResult {
+    Run {
+        changes: u64,
+        last_insert_rowid: i64,
+    },
+    Row {
+        columns: Vec<String>,
+        row: Option<Vec<Value>>,
+    },
+    /// Rows as arrays of values, with the column names sent once
+    Rows {
+        columns: Vec<String>,
+        rows: Vec<Vec<Value>>,
+    },
 }
 
 #[command]
-pub async fn run_sql(app: AppHandle, query: SqlQuery) -> Result<Vec<SqlRow>, String> {
-    let db_path = get_app_db_path(&app)?;
-    let uri = format!("sqlite://{}", db_path.display());
+pub async fn run_sql(database: State<'_, Database>, query: SqlQuery) -> Result<SqlResult, String> {
+    let mut conn = database
+        .0
+        .acquire()
+        .await
+        .map_err(|e| format!("Failed to get a connection: {}", e))?;
+    execute(&mut conn, &query).await
+}
 
-    let pool = SqlitePool::connect(&uri)
+/// Runs Drizzle's batched queries in one transaction, so they all apply or
+/// none do
+#[command]
+pub async fn run_sql_batch(
+    database: State<'_, Database>,
+    queries: Vec<SqlQuery>,
+) -> Result<Vec<SqlResult>, String> {
+    let mut tx = database
+        .0
+        .begin()
         .await
-        .map_err(|e| format!("Failed to connect to DB: {}", e))?;
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
 
+    let mut results = Vec::with_capacity(queries.len());
+    for query in &queries {
+        results.push(execute(&mut tx, query).await?);
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    Ok(results)
+}
+
+async fn execute(conn: &mut SqliteConnection, query: &SqlQuery) -> Result<SqlResult, String> {
     let mut q = sqlx::query(&query.sql);
     for param in &query.params {
         q = bind_value(q, param);
     }
 
-    let rows = q
-        .fetch_all(&pool)
-        .await
-        .map_err(|e| format!("Query failed: {}", e))?;
-
-    let result = rows
-        .iter()
-        .map(|row| {
-            let columns = row
-                .columns()
-                .iter()
-                .map(|c| c.name().to_string())
-                .collect::<Vec<_>>();
-
-            let values = (0..row.len())
-                .map(|i| match row.try_get_raw(i) {
-                    Ok(_) => sqlx_value_to_json(row, i),
-                    Err(_) => Value::Null,
-                })
-                .collect::<Vec<_>>();
-
-            SqlRow { columns, values }
+    match query.method {
+        QueryMethod::Run => {
+            let result = q
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| format!("Query failed: {}", e))?;
+            Ok(SqlResult::Run {
+                changes: result.rows_affected(),
+                last_insert_rowid: result.last_insert_rowid(),
+            })
+        }
+        QueryMethod::Get => {
+            let row = q
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(|e| format!("Query failed: {}", e))?;
+            Ok(SqlResult::Row {
+                columns: row.as_ref().map(column_names).unwrap_or_default(),
+                row: row.as_ref().map(row_values),
+            })
+        }
+        QueryMethod::All | QueryMethod::Values => {
+            let rows = q
+                .fetch_all(&mut *conn)
+                .await
+                .map_err(|e| format!("Query failed: {}", e))?;
+            Ok(SqlResult::Rows {
+                columns: rows.first().map(column_names).unwrap_or_default(),
+                rows: rows.iter().map(row_values).collect(),
+            })
+        }
+    }
+}
+
+fn column_names(row: &SqliteRow) -> Vec<String> {
+    row.columns().iter().map(|c| c.name().to_string()).collect()
+}
+
+fn row_values(row: &SqliteRow) -> Vec<Value> {
+    (0..row.len())
+        .map(|i| match row.try_get_raw(i) {
+            Ok(_) => sqlx_value_to_json(row, i),
+            Err(_) => Value::Null,
         })
-        .collect();
+        .collect()
+}
+
+/// Opens the app database in `app_data_dir`. WAL lets the UI read while a
+/// background job writes, and foreign keys are enforced on every connection.
+pub async fn open(app_data_dir: &Path) -> Result<SqlitePool, String> {
+    let options = SqliteConnectOptions::new()
+        .filename(app_data_dir.join("database.db"))
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(BUSY_TIMEOUT)
+        .foreign_keys(true);
 
-    Ok(result)
+    SqlitePoolOptions::new()
+        .max_connections(MAX_CONNECTIONS)
+        .min_connections(1)
+        .acquire_timeout(BUSY_TIMEOUT * 3)
+        .idle_timeout(Duration::from_secs(10 * 60))
+        .connect_with(options)
+        .await
+        .map_err(|e| format!("Failed to connect to DB: {}", e))
 }
 
-fn get_app_db_path(app: &AppHandle) -> Result<PathBuf, String> {
-    app.path()
-        .app_data_dir()
-        .map(|p| p.join("database.db"))
-        .map_err(|_| "Could not resolve app data directory".to_string())
+/// The shared pool. Clones are cheap and all use the same connections.
+pub fn pool(app: &AppHandle) -> SqlitePool {
+    app.state::<Database>().0.clone()
 }
 
 fn bind_value<'q>(
@@ -0,0 +1,434 @@
+use serde::Serialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+use crate::audio_transcription;
+use crate::audio_utils;
+use crate::date_utils;
+use crate::drizzle_proxy;
+use crate::job_queue::{self, JobKind};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// A file whose size and modification time haven't changed for this long is
+/// done being written
+const STABLE_SECS: f64 = 60.0;
+/// Recordings that start within this long of the previous one ending belong
+/// to the same evening
+const GROUP_GAP_SECS: f64 = 3.0 * 3600.0;
+/// An evening becomes a session once nothing new has turned up for this long
+const SETTLE_SECS: f64 = 30.0 * 60.0;
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "mp3", "wav", "flac", "m4a", "aac", "ogg", "opus", "mp4", "mkv", "mov", "webm", "flv",
+];
+
+// Lifecycle of a row in `watched_files`
+const STATUS_EXISTING: &str = "existing";
+const STATUS_GROWING: &str = "growing";
+const STATUS_STABLE: &str = "stable";
+const STATUS_QUEUED: &str = "queued";
+const STATUS_PROCESSED: &str = "processed";
+
+/// Payload of the `watch-folder-session` event
+#[derive(Debug, Clone, Serialize)]
+pub struct DraftSession {
+    pub campaign_id: String,
+    pub session_id: String,
+    /// In recording order
+    pub file_paths: Vec<String>,
+    /// The `process_audio` job queued for the session
+    pub job_id: String,
+}
+
+struct WatchedFile {
+    id: String,
+    path: String,
+    size: i64,
+    modified_at: f64,
+    last_change_at: f64,
+    duration: Option<f64>,
+    status: String,
+}
+
+/// Polls every campaign's watch folder in the background. Everything the
+/// watcher knows lives in the `watched_folders` and `watched_files` tables, so
+/// a restart picks up where it left off.
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        if let Err(e) = tauri::async_runtime::block_on(scan(&app)) {
+            eprintln!("Watch folder scan failed: {}", e);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    });
+}
+
+async fn scan(app: &AppHandle) -> Result<(), String> {
+    let pool = drizzle_proxy::pool(app);
+
+    // A campaign whose watch folder was cleared starts over the next time it
+    // is given one
+    sqlx::query(
+        "DELETE FROM watched_folders WHERE NOT EXISTS (SELECT 1 FROM campaigns \
+         WHERE campaigns.id = watched_folders.campaign_id \
+         AND campaigns.watch_folder = watched_folders.path)",
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| format!("Failed to forget old watch folders: {}", e))?;
+
+    let campaigns = sqlx::query_as::<_, (String, String)>(
+        "SELECT id, watch_folder FROM campaigns \
+         WHERE watch_folder IS NOT NULL AND watch_folder != ''",
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| format!("Failed to load watch folders: {}", e))?;
+    if campaigns.is_empty() {
+        return Ok(());
+    }
+
+    let ffmpeg_path = audio_utils::get_ffmpeg_path(app)?;
+    for (campaign_id, folder) in campaigns {
+        // One unreachable folder (e.g. an unplugged drive) doesn't hold up the rest
+        match scan_folder(&pool, &ffmpeg_path, &campaign_id, &folder).await {
+            Ok(drafts) => {
+                for draft in drafts {
+                    if let Err(e) = job_queue::announce(app, &draft.job_id).await {
+                        eprintln!("Failed to announce job: {}", e);
+                    }
+                    if let Err(e) = app.emit("watch-folder-session", draft) {
+                        eprintln!("Failed to announce draft session: {}", e);
+                    }
+                }
+            }
+            Err(e) => eprintln!("Failed to scan {}: {}", folder, e),
+        }
+    }
+
+    Ok(())
+}
+
+async fn scan_folder(
+    pool: &SqlitePool,
+    ffmpeg_path: &Path,
+    campaign_id: &str,
+    folder: &str,
+) -> Result<Vec<DraftSession>, String> {
+    let now = unix_now();
+    let on_disk = list_media(Path::new(folder))?;
+    let mut known: HashMap<String, WatchedFile> = load_files(pool, campaign_id)
+        .await?
+        .into_iter()
+        .map(|file| (file.path.clone(), file))
+        .collect();
+
+    // Recordings already in the folder when watching it began are left alone
+    // rather than turned into sessions
+    let watching_since = watching_since(pool, campaign_id, folder, now).await?;
+
+    for (path, size, modified_at) in &on_disk {
+        match known.get_mut(path) {
+            None => {
+                let status = if *modified_at < watching_since {
+                    STATUS_EXISTING
+                } else {
+                    STATUS_GROWING
+                };
+                insert_file(pool, campaign_id, path, *size, *modified_at, now, status).await?;
+            }
+            Some(file) if file.status == STATUS_GROWING => {
+                if file.size != *size || file.modified_at != *modified_at {
+                    file.size = *size;
+                    file.modified_at = *modified_at;
+                    file.last_change_at = now;
+                } else if now - file.last_change_at >= STABLE_SECS {
+                    file.status = STATUS_STABLE.to_string();
+                    file.duration =
+                        audio_transcription::get_audio_duration(ffmpeg_path, Path::new(path))
+                            .map_err(|e| eprintln!("Could not read duration of {}: {}", path, e))
+                            .ok();
+                } else {
+                    continue;
+                }
+                update_file(pool, file).await?;
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Recordings deleted or moved away before they were queued are forgotten
+    for file in known.values() {
+        let pending = file.status == STATUS_GROWING || file.status == STATUS_STABLE;
+        if pending && !on_disk.iter().any(|(path, _, _)| *path == file.path) {
+            sqlx::query("DELETE FROM watched_files WHERE id = ?")
+                .bind(&file.id)
+                .execute(pool)
+                .await
+                .map_err(|e| format!("Failed to forget {}: {}", file.path, e))?;
+        }
+    }
+
+    // Nothing is grouped while a recording is still being written, since it
+    // may belong to the evening being assembled
+    let files = load_files(pool, campaign_id).await?;
+    if files.iter().any(|file| file.status == STATUS_GROWING) {
+        return Ok(Vec::new());
+    }
+
+    let mut stable: Vec<WatchedFile> = files
+        .into_iter()
+        .filter(|file| file.status == STATUS_STABLE)
+        .collect();
+    stable.sort_by(|a, b| a.started_at().total_cmp(&b.started_at()));
+
+    let mut drafts = Vec::new();
+    for group in group_by_evening(&stable) {
+        let last_modified = group.iter().map(|f| f.modified_at).fold(0.0, f64::max);
+        if now - last_modified >= SETTLE_SECS {
+            drafts.push(create_draft_session(pool, campaign_id, group).await?);
+        }
+    }
+
+    Ok(drafts)
+}
+
+impl WatchedFile {
+    /// Recordings are written as they go, so the file was last touched when
+    /// the recording stopped
+    fn started_at(&self) -> f64 {
+        self.modified_at - self.duration.unwrap_or(0.0)
+    }
+}
+
+fn group_by_evening(files: &[WatchedFile]) -> Vec<&[WatchedFile]> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for index in 1..=files.len() {
+        let split = index == files.len()
+            || files[index].started_at() - files[index - 1].modified_at > GROUP_GAP_SECS;
+        if split {
+            groups.push(&files[start..index]);
+            start = index;
+        }
+    }
+    groups
+}
+
+/// Creates a session numbered after the campaign's latest, hands it the
+/// group's files and queues their processing, all or nothing
+async fn create_draft_session(
+    pool: &SqlitePool,
+    campaign_id: &str,
+    group: &[WatchedFile],
+) -> Result<DraftSession, String> {
+    let session_id = uuid::Uuid::now_v7().to_string();
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    sqlx::query(
+        "INSERT INTO sessions (id, campaign_id, number, date, status) \
+         SELECT ?, ?, COALESCE(MAX(number), 0) + 1, ?, 'draft' \
+         FROM sessions WHERE campaign_id = ?",
+    )
+    .bind(&session_id)
+    .bind(campaign_id)
+    .bind(date_utils::iso_timestamp(group[0].started_at()))
+    .bind(campaign_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to create draft session: {}", e))?;
+
+    for file in group {
+        sqlx::query(
+            "UPDATE watched_files SET status = ?, session_id = ?, \
+             updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(STATUS_QUEUED)
+        .bind(&session_id)
+        .bind(&file.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to queue {}: {}", file.path, e))?;
+    }
+
+    let file_paths: Vec<String> = group.iter().map(|file| file.path.clone()).collect();
+    let job_id = job_queue::insert_job(
+        &mut tx,
+        JobKind::ProcessAudio,
+        Some(&session_id),
+        &json!({
+            "session_id": session_id,
+            "file_paths": file_paths,
+            "output_filename": "audio.mp3",
+        }),
+        job_queue::default_max_attempts(),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to create draft session: {}", e))?;
+
+    Ok(DraftSession {
+        campaign_id: campaign_id.to_string(),
+        session_id,
+        file_paths,
+        job_id,
+    })
+}
+
+/// Marks a draft session and its queued recordings as done once
+/// `process_audio_files` has run for it
+pub async fn mark_processed(pool: &SqlitePool, session_id: &str) -> Result<(), String> {
+    sqlx::query("UPDATE watched_files SET status = ?, updated_at = CURRENT_TIMESTAMP WHERE session_id = ? AND status = ?")
+        .bind(STATUS_PROCESSED)
+        .bind(session_id)
+        .bind(STATUS_QUEUED)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to update watched files: {}", e))?;
+
+    sqlx::query("UPDATE sessions SET status = 'ready' WHERE id = ? AND status = 'draft'")
+        .bind(session_id)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to update session: {}", e))?;
+
+    Ok(())
+}
+
+/// Media files directly inside `folder`, with their size and modification time
+fn list_media(folder: &Path) -> Result<Vec<(String, i64, f64)>, String> {
+    let entries = std::fs::read_dir(folder).map_err(|e| format!("Failed to read folder: {}", e))?;
+
+    Ok(entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let extension = path.extension()?.to_string_lossy().to_lowercase();
+            if !MEDIA_EXTENSIONS.contains(&extension.as_str()) {
+                return None;
+            }
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            let modified = metadata
+                .modified()
+                .ok()?
+                .duration_since(UNIX_EPOCH)
+                .ok()?
+                .as_secs_f64();
+            Some((
+                path.to_string_lossy().to_string(),
+                metadata.len() as i64,
+                modified,
+            ))
+        })
+        .collect())
+}
+
+/// When the campaign started watching `folder`, recording `now` if this is
+/// the first scan of it since the watch folder was set
+async fn watching_since(
+    pool: &SqlitePool,
+    campaign_id: &str,
+    folder: &str,
+    now: f64,
+) -> Result<f64, String> {
+    sqlx::query(
+        "INSERT INTO watched_folders (campaign_id, path, watching_since) VALUES (?, ?, ?) \
+         ON CONFLICT (campaign_id) DO UPDATE SET path = excluded.path, \
+         watching_since = excluded.watching_since WHERE path != excluded.path",
+    )
+    .bind(campaign_id)
+    .bind(folder)
+    .bind(now)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to record watch folder: {}", e))?;
+
+    sqlx::query_scalar("SELECT watching_since FROM watched_folders WHERE campaign_id = ?")
+        .bind(campaign_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| format!("Failed to load watch folder: {}", e))
+}
+
+async fn load_files(pool: &SqlitePool, campaign_id: &str) -> Result<Vec<WatchedFile>, String> {
+    let rows = sqlx::query_as::<_, (String, String, i64, f64, f64, Option<f64>, String)>(
+        "SELECT id, path, size, modified_at, last_change_at, duration, status \
+         FROM watched_files WHERE campaign_id = ?",
+    )
+    .bind(campaign_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to load watched files: {}", e))?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, path, size, modified_at, last_change_at, duration, status)| WatchedFile {
+                id,
+                path,
+                size,
+                modified_at,
+                last_change_at,
+                duration,
+                status,
+            },
+        )
+        .collect())
+}
+
+async fn insert_file(
+    pool: &SqlitePool,
+    campaign_id: &str,
+    path: &str,
+    size: i64,
+    modified_at: f64,
+    now: f64,
+    status: &str,
+) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO watched_files (id, campaign_id, path, size, modified_at, last_change_at, status) \
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(uuid::Uuid::now_v7().to_string())
+    .bind(campaign_id)
+    .bind(path)
+    .bind(size)
+    .bind(modified_at)
+    .bind(now)
+    .bind(status)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to record {}: {}", path, e))?;
+    Ok(())
+}
+
+async fn update_file(pool: &SqlitePool, file: &WatchedFile) -> Result<(), String> {
+    sqlx::query(
+        "UPDATE watched_files SET size = ?, modified_at = ?, last_change_at = ?, duration = ?, \
+         status = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    )
+    .bind(file.size)
+    .bind(file.modified_at)
+    .bind(file.last_change_at)
+    .bind(file.duration)
+    .bind(&file.status)
+    .bind(&file.id)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to update {}: {}", file.path, e))?;
+    Ok(())
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
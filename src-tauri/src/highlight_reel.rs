@@ -0,0 +1,253 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, Manager};
+
+use crate::audio_clip;
+use crate::audio_format::OutputOptions;
+use crate::audio_metadata::{self, Chapter};
+use crate::audio_processor;
+use crate::audio_transcription;
+use crate::audio_utils;
+use crate::drizzle_proxy;
+
+#[derive(Debug, Deserialize)]
+pub struct HighlightReelRequest {
+    /// Moments in the order they should play
+    pub moment_ids: Vec<String>,
+    /// How much neighbouring clips overlap
+    #[serde(default = "default_crossfade_secs")]
+    pub crossfade_secs: f64,
+    /// Read each moment's label aloud before it. Needs `api_key`.
+    #[serde(default)]
+    pub title_cards: bool,
+    /// OpenAI API key used for the spoken title cards
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub output: OutputOptions,
+    /// Defaults to `reels/{id}.{ext}` in app data
+    pub output_path: Option<String>,
+}
+
+fn default_crossfade_secs() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Serialize)]
+pub struct HighlightReelResponse {
+    pub output_path: String,
+    pub duration: f64,
+    /// One chapter per moment, named after its label
+    pub chapters: Vec<Chapter>,
+}
+
+struct Moment {
+    session_id: String,
+    start_time: f64,
+    end_time: f64,
+    label: String,
+}
+
+#[command]
+pub async fn create_highlight_reel(
+    app: AppHandle,
+    request: HighlightReelRequest,
+) -> Result<HighlightReelResponse, String> {
+    if request.moment_ids.is_empty() {
+        return Err("No moments selected".to_string());
+    }
+    let api_key = match (request.title_cards, &request.api_key) {
+        (true, Some(key)) if !key.is_empty() => Some(key.as_str()),
+        (true, _) => return Err("Spoken title cards need an OpenAI API key".to_string()),
+        (false, _) => None,
+    };
+
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?;
+
+    let pool = drizzle_proxy::pool(&app);
+    let moments = load_moments(&pool, &request.moment_ids).await?;
+
+    let ffmpeg_path = audio_utils::get_ffmpeg_path(&app)?;
+    let temp_dir = app_data_dir.join("temp_reel");
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    std::fs::create_dir_all(&temp_dir)
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+
+    let intermediate =
+        OutputOptions::intermediate(request.output.sample_rate, request.output.channels);
+
+    // Every part of the reel, with the moment it belongs to
+    let mut parts: Vec<(PathBuf, f64, usize)> = Vec::new();
+    for (index, moment) in moments.iter().enumerate() {
+        if let Some(api_key) = api_key {
+            let title_path = temp_dir.join(format!("title_{}.wav", index));
+            synthesize_title(&moment.label, api_key, &title_path).await?;
+            let duration = audio_transcription::get_audio_duration(&ffmpeg_path, &title_path)?;
+            parts.push((title_path, duration, index));
+        }
+
+        let session_dir = app_data_dir.join("sessions").join(&moment.session_id);
+        let source = audio_processor::session_audio_path(&session_dir);
+        let clip_path = temp_dir.join(format!("moment_{}.wav", index));
+        let duration = moment.end_time - moment.start_time;
+        audio_clip::extract_audio_segment(
+            &ffmpeg_path,
+            &source,
+            &clip_path,
+            moment.start_time,
+            duration,
+            Some((&[], &intermediate)),
+        )
+        .map_err(|e| format!("Failed to cut \"{}\": {}", moment.label, e))?;
+
+        // A moment near the end of a session may be shorter than tagged
+        let duration = audio_transcription::get_audio_duration(&ffmpeg_path, &clip_path)?;
+        parts.push((clip_path, duration, index));
+    }
+
+    // A crossfade can't be longer than half of the shortest part
+    let shortest = parts.iter().map(|p| p.1).fold(f64::MAX, f64::min);
+    let crossfade = request.crossfade_secs.clamp(0.0, shortest / 2.0);
+
+    // Each part starts where the previous one began to fade out
+    let mut chapters: Vec<Chapter> = Vec::new();
+    let mut position = 0.0;
+    for (index, (_, duration, moment)) in parts.iter().enumerate() {
+        if chapters.len() <= *moment {
+            if let Some(previous) = chapters.last_mut() {
+                previous.end = position;
+            }
+            chapters.push(Chapter {
+                start: position,
+                end: position,
+                title: moments[*moment].label.clone(),
+            });
+        }
+        position += duration;
+        if index < parts.len() - 1 {
+            position -= crossfade;
+        }
+    }
+    let total = position;
+    if let Some(last) = chapters.last_mut() {
+        last.end = total;
+    }
+
+    let output_path = match &request.output_path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let reels_dir = app_data_dir.join("reels");
+            std::fs::create_dir_all(&reels_dir)
+                .map_err(|e| format!("Failed to create reels directory: {}", e))?;
+            reels_dir.join(format!(
+                "{}.{}",
+                uuid::Uuid::now_v7(),
+                request.output.format.extension()
+            ))
+        }
+    };
+
+    let joined_path = temp_dir.join(format!("reel.{}", request.output.format.extension()));
+    let inputs: Vec<String> = parts
+        .iter()
+        .map(|(path, _, _)| path.to_string_lossy().to_string())
+        .collect();
+    audio_processor::concatenate_with_filter_graph(
+        &ffmpeg_path,
+        &inputs,
+        &vec![Vec::new(); inputs.len()],
+        &intermediate,
+        &[(joined_path.clone(), request.output.clone())],
+        crossfade,
+    )
+    .map_err(|e| format!("Failed to join highlight reel: {}", e))?;
+
+    let metadata_path = temp_dir.join("metadata.txt");
+    std::fs::write(
+        &metadata_path,
+        audio_metadata::ffmetadata(&[("title", "Highlights".to_string())], &chapters),
+    )
+    .map_err(|e| format!("Failed to write chapters: {}", e))?;
+    audio_metadata::apply_metadata(&ffmpeg_path, &joined_path, &metadata_path, &output_path)?;
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+
+    Ok(HighlightReelResponse {
+        output_path: output_path.to_string_lossy().to_string(),
+        duration: total,
+        chapters,
+    })
+}
+
+/// Loads the moments in the order their ids were given
+async fn load_moments(
+    pool: &sqlx::SqlitePool,
+    moment_ids: &[String],
+) -> Result<Vec<Moment>, String> {
+    let placeholders = vec!["?"; moment_ids.len()].join(", ");
+    let sql = format!(
+        "SELECT id, session_id, start_time, end_time, label FROM moments WHERE id IN ({})",
+        placeholders
+    );
+    let mut query = sqlx::query_as::<_, (String, String, f64, f64, String)>(&sql);
+    for id in moment_ids {
+        query = query.bind(id);
+    }
+    let rows = query
+        .fetch_all(pool)
+        .await
+        .map_err(|e| format!("Failed to load moments: {}", e))?;
+
+    moment_ids
+        .iter()
+        .map(|id| {
+            rows.iter()
+                .find(|row| &row.0 == id)
+                .map(|(_, session_id, start_time, end_time, label)| Moment {
+                    session_id: session_id.clone(),
+                    start_time: *start_time,
+                    end_time: *end_time,
+                    label: label.clone(),
+                })
+                .ok_or_else(|| format!("Moment {} not found", id))
+        })
+        .collect()
+}
+
+/// Reads a label aloud with OpenAI's text-to-speech and saves it as WAV
+async fn synthesize_title(label: &str, api_key: &str, output: &Path) -> Result<(), String> {
+    let client = reqwest::Client::new();
+    let response = client
+        .post("https://api.openai.com/v1/audio/speech")
+        .header("Authorization", format!("Bearer {}", api_key))
+        .json(&serde_json::json!({
+            "model": "tts-1",
+            "voice": "onyx",
+            "input": label,
+            "response_format": "wav",
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request to OpenAI API: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(format!(
+            "OpenAI API error (status {}): {}",
+            status.as_u16(),
+            error_text
+        ));
+    }
+
+    let audio = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read title card audio: {}", e))?;
+    std::fs::write(output, &audio).map_err(|e| format!("Failed to save title card: {}", e))
+}
@@ -0,0 +1,708 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{SqliteConnection, SqlitePool};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{command, AppHandle, Emitter, Event, EventId, Listener, Manager, State};
+
+use crate::audio_chapters;
+use crate::audio_processor;
+use crate::audio_transcription;
+use crate::drizzle_proxy;
+use crate::live_transcription;
+use crate::session_pipeline::{self, PipelineStage};
+
+/// Jobs that run at the same time. FFmpeg already uses every core, so more
+/// workers mostly help when one of them is waiting on the OpenAI API.
+const WORKERS: usize = 2;
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// A failed attempt is retried after this long, doubling with every further
+/// failure, so a job failing on the API doesn't burn its attempts in seconds
+const RETRY_DELAY_SECS: i64 = 30;
+const MAX_RETRY_DELAY_SECS: i64 = 15 * 60;
+
+// Lifecycle of a row in `jobs`
+const STATUS_QUEUED: &str = "queued";
+const STATUS_RUNNING: &str = "running";
+const STATUS_SUCCEEDED: &str = "succeeded";
+const STATUS_FAILED: &str = "failed";
+const STATUS_CANCELLED: &str = "cancelled";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    ProcessAudio,
+    TranscribeSpeakerTracks,
+    DetectChapters,
+    LiveTranscription,
+    SessionPipeline,
+}
+
+impl JobKind {
+    const ALL: [JobKind; 5] = [
+        JobKind::ProcessAudio,
+        JobKind::TranscribeSpeakerTracks,
+        JobKind::DetectChapters,
+        JobKind::LiveTranscription,
+        JobKind::SessionPipeline,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::ProcessAudio => "process_audio",
+            JobKind::TranscribeSpeakerTracks => "transcribe_speaker_tracks",
+            JobKind::DetectChapters => "detect_chapters",
+            JobKind::LiveTranscription => "live_transcription",
+            JobKind::SessionPipeline => "session_pipeline",
+        }
+    }
+
+    fn from_str(kind: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == kind)
+    }
+
+    /// Whether the job's request carries an OpenAI `api_key`
+    fn needs_api_key(&self) -> bool {
+        matches!(
+            self,
+            JobKind::TranscribeSpeakerTracks
+                | JobKind::LiveTranscription
+                | JobKind::SessionPipeline
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnqueueJobRequest {
+    pub kind: JobKind,
+    /// The request the job's command takes. An `api_key` in it is kept in
+    /// memory and never stored with the job.
+    pub payload: Value,
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: i64,
+}
+
+pub fn default_max_attempts() -> i64 {
+    3
+}
+
+/// A row of `jobs`, and the payload of the `job-updated` event
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub session_id: Option<String>,
+    pub kind: String,
+    pub status: String,
+    pub progress: f64,
+    pub attempts: i64,
+    pub max_attempts: i64,
+    pub error: Option<String>,
+    pub result: Option<Value>,
+}
+
+/// The fields of `audio-processing-progress` and `session-pipeline-progress`
+/// events that end up in a job's row
+#[derive(Debug, Deserialize)]
+struct ProgressEvent {
+    #[serde(default)]
+    job_id: Option<String>,
+    stage: String,
+    status: String,
+    #[serde(default)]
+    file_path: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// OpenAI key used by queued jobs. Only ever held in memory: after a restart
+/// jobs that need it wait until the frontend hands it over again.
+#[derive(Default)]
+pub struct JobCredentials(Mutex<Option<String>>);
+
+impl JobCredentials {
+    fn api_key(&self) -> Option<String> {
+        self.0.lock().ok()?.clone()
+    }
+
+    fn set(&self, api_key: String) {
+        if let Ok(mut current) = self.0.lock() {
+            *current = Some(api_key).filter(|key| !key.is_empty());
+        }
+    }
+}
+
+/// Adds a job to the queue. It runs in the background; watch `job-updated`
+/// events (or the `jobs` table) for its progress and result.
+#[command]
+pub async fn enqueue_job(
+    app: AppHandle,
+    credentials: State<'_, JobCredentials>,
+    request: EnqueueJobRequest,
+) -> Result<Job, String> {
+    let mut payload = request.payload;
+    let object = payload
+        .as_object_mut()
+        .ok_or("Job payload must be an object")?;
+    if let Some(Value::String(api_key)) = object.remove("api_key") {
+        credentials.set(api_key);
+    }
+    let session_id = object
+        .get("session_id")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+
+    // Catch a malformed request now rather than when a worker picks it up
+    validate_payload(request.kind, &payload)?;
+
+    let pool = drizzle_proxy::pool(&app);
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|e| format!("Failed to queue job: {}", e))?;
+    let id = insert_job(
+        &mut conn,
+        request.kind,
+        session_id.as_deref(),
+        &payload,
+        request.max_attempts,
+    )
+    .await?;
+    drop(conn);
+
+    notify(&app, &pool, &id).await
+}
+
+/// Adds a queued job on `conn`, so it can be part of a caller's transaction.
+/// Workers pick it up on their next poll; call `announce` once it's committed.
+pub async fn insert_job(
+    conn: &mut SqliteConnection,
+    kind: JobKind,
+    session_id: Option<&str>,
+    payload: &Value,
+    max_attempts: i64,
+) -> Result<String, String> {
+    let id = uuid::Uuid::now_v7().to_string();
+    sqlx::query(
+        "INSERT INTO jobs (id, session_id, kind, status, payload, max_attempts) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&id)
+    .bind(session_id)
+    .bind(kind.as_str())
+    .bind(STATUS_QUEUED)
+    .bind(payload.to_string())
+    .bind(max_attempts.max(1))
+    .execute(conn)
+    .await
+    .map_err(|e| format!("Failed to queue job: {}", e))?;
+    Ok(id)
+}
+
+/// Tells the UI about a job added with `insert_job`
+pub async fn announce(app: &AppHandle, id: &str) -> Result<Job, String> {
+    notify(app, &drizzle_proxy::pool(app), id).await
+}
+
+/// Cancels a job. A queued job never starts. A running one isn't interrupted:
+/// its current attempt carries on to the end (files it writes included), then
+/// its outcome is discarded and it isn't retried. `finished_at` is only set
+/// once that attempt is over, and until then the job can't be retried.
+#[command]
+pub async fn cancel_job(app: AppHandle, job_id: String) -> Result<Job, String> {
+    let pool = drizzle_proxy::pool(&app);
+    sqlx::query(
+        "UPDATE jobs SET status = ?, \
+         finished_at = CASE WHEN status = ? THEN CURRENT_TIMESTAMP END, \
+         updated_at = CURRENT_TIMESTAMP WHERE id = ? AND status IN (?, ?)",
+    )
+    .bind(STATUS_CANCELLED)
+    .bind(STATUS_QUEUED)
+    .bind(&job_id)
+    .bind(STATUS_QUEUED)
+    .bind(STATUS_RUNNING)
+    .execute(&pool)
+    .await
+    .map_err(|e| format!("Failed to cancel job: {}", e))?;
+
+    notify(&app, &pool, &job_id).await
+}
+
+/// Puts a failed or cancelled job back in the queue with fresh attempts
+#[command]
+pub async fn retry_job(app: AppHandle, job_id: String) -> Result<Job, String> {
+    let pool = drizzle_proxy::pool(&app);
+    sqlx::query(
+        "UPDATE jobs SET status = ?, attempts = 0, progress = 0, error = NULL, \
+         run_after = NULL, finished_at = NULL, updated_at = CURRENT_TIMESTAMP \
+         WHERE id = ? AND status IN (?, ?) AND finished_at IS NOT NULL",
+    )
+    .bind(STATUS_QUEUED)
+    .bind(&job_id)
+    .bind(STATUS_FAILED)
+    .bind(STATUS_CANCELLED)
+    .execute(&pool)
+    .await
+    .map_err(|e| format!("Failed to retry job: {}", e))?;
+
+    notify(&app, &pool, &job_id).await
+}
+
+/// Hands the OpenAI key to the queue, e.g. once it's been read from the
+/// Stronghold vault after startup
+#[command]
+pub fn set_job_credentials(credentials: State<'_, JobCredentials>, api_key: String) {
+    credentials.set(api_key);
+}
+
+/// Starts the worker pool. Jobs that were running when the app closed are
+/// queued again first, so they resume from the start of their step. The
+/// interrupted attempt isn't held against them.
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        if let Err(e) = tauri::async_runtime::block_on(requeue_interrupted(&app)) {
+            eprintln!("Failed to requeue interrupted jobs: {}", e);
+        }
+
+        for _ in 0..WORKERS {
+            let app = app.clone();
+            std::thread::spawn(move || loop {
+                match tauri::async_runtime::block_on(run_next(&app)) {
+                    Ok(true) => continue,
+                    Ok(false) => {}
+                    Err(e) => eprintln!("Job worker failed: {}", e),
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            });
+        }
+    });
+}
+
+async fn requeue_interrupted(app: &AppHandle) -> Result<(), String> {
+    let pool = drizzle_proxy::pool(app);
+    let interrupted = sqlx::query_as::<_, (String,)>(
+        "UPDATE jobs SET status = ?, attempts = MAX(attempts - 1, 0), \
+         updated_at = CURRENT_TIMESTAMP, \
+         logs = logs || 'Interrupted by app restart' || char(10) \
+         WHERE status = ? RETURNING id",
+    )
+    .bind(STATUS_QUEUED)
+    .bind(STATUS_RUNNING)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| format!("Failed to requeue jobs: {}", e))?;
+
+    // Jobs cancelled while running whose attempt the restart cut short
+    sqlx::query(
+        "UPDATE jobs SET finished_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP \
+         WHERE status = ? AND finished_at IS NULL",
+    )
+    .bind(STATUS_CANCELLED)
+    .execute(&pool)
+    .await
+    .map_err(|e| format!("Failed to close cancelled jobs: {}", e))?;
+
+    for (id,) in interrupted {
+        eprintln!("Resuming interrupted job {}", id);
+        notify(app, &pool, &id).await?;
+    }
+    Ok(())
+}
+
+/// Claims and runs the oldest runnable job. Returns whether there was one.
+async fn run_next(app: &AppHandle) -> Result<bool, String> {
+    let pool = drizzle_proxy::pool(app);
+    let api_key = app.state::<JobCredentials>().api_key();
+
+    // Without a key, jobs that need one stay queued until it's provided
+    let skipped = if api_key.is_some() {
+        String::new()
+    } else {
+        let kinds: Vec<String> = JobKind::ALL
+            .iter()
+            .filter(|kind| kind.needs_api_key())
+            .map(|kind| format!("'{}'", kind.as_str()))
+            .collect();
+        format!("AND kind NOT IN ({})", kinds.join(", "))
+    };
+
+    // A single statement, so two workers can never claim the same job. Jobs
+    // of a session that already has one running wait for it, since they read
+    // and write the same session files.
+    let sql = format!(
+        "UPDATE jobs SET status = ?, attempts = attempts + 1, progress = 0, \
+         started_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP \
+         WHERE id = (SELECT id FROM jobs WHERE status = ? {} \
+         AND (run_after IS NULL OR run_after <= CURRENT_TIMESTAMP) \
+         AND (session_id IS NULL OR session_id NOT IN \
+         (SELECT session_id FROM jobs WHERE status = ? AND session_id IS NOT NULL)) \
+         ORDER BY id LIMIT 1) \
+         RETURNING id, kind, payload, attempts, max_attempts",
+        skipped
+    );
+    let claimed = sqlx::query_as::<_, (String, String, String, i64, i64)>(&sql)
+        .bind(STATUS_RUNNING)
+        .bind(STATUS_QUEUED)
+        .bind(STATUS_RUNNING)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| format!("Failed to claim job: {}", e))?;
+    let Some((id, kind, payload, attempts, max_attempts)) = claimed else {
+        return Ok(false);
+    };
+
+    append_log(&pool, &id, &format!("Attempt {} started", attempts)).await?;
+    notify(app, &pool, &id).await?;
+
+    let outcome = match JobKind::from_str(&kind) {
+        Some(kind) => {
+            let listeners = forward_progress(app, &id, kind, &payload);
+            let outcome = run_job(app, &id, kind, &payload, api_key).await;
+            for listener in listeners {
+                app.unlisten(listener);
+            }
+            outcome
+        }
+        None => Err(format!("Unknown job kind \"{}\"", kind)),
+    };
+
+    // The work of a job cancelled meanwhile still ran, but its outcome is
+    // dropped. Only a job that is still running is updated below, so a
+    // cancellation landing after this check sticks as well.
+    if status(&pool, &id).await? == STATUS_CANCELLED {
+        sqlx::query(
+            "UPDATE jobs SET finished_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP, \
+             logs = logs || 'Cancelled; outcome discarded' || char(10) WHERE id = ?",
+        )
+        .bind(&id)
+        .execute(&pool)
+        .await
+        .map_err(|e| format!("Failed to record job outcome: {}", e))?;
+        notify(app, &pool, &id).await?;
+        return Ok(true);
+    }
+
+    let update = match outcome {
+        Ok(result) => sqlx::query(
+            "UPDATE jobs SET status = ?, progress = 1, result = ?, error = NULL, \
+             finished_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP, \
+             logs = logs || 'Finished' || char(10) WHERE id = ? AND status = ?",
+        )
+        .bind(STATUS_SUCCEEDED)
+        .bind(result.to_string())
+        .bind(&id)
+        .bind(STATUS_RUNNING),
+        Err(e) => {
+            eprintln!("Job {} ({}) failed: {}", id, kind, e);
+            // Failed attempts go back in the queue, after a delay, until they
+            // run out
+            let retry = attempts < max_attempts;
+            let delay = retry_delay_secs(attempts);
+            let line = if retry {
+                format!("Attempt {} failed: {}; retrying in {}s", attempts, e, delay)
+            } else {
+                format!("Attempt {} failed: {}", attempts, e)
+            };
+            sqlx::query(
+                "UPDATE jobs SET status = ?, error = ?, \
+                 run_after = CASE WHEN ? THEN datetime('now', ?) END, \
+                 finished_at = CASE WHEN ? THEN NULL ELSE CURRENT_TIMESTAMP END, \
+                 updated_at = CURRENT_TIMESTAMP, logs = logs || ? || char(10) \
+                 WHERE id = ? AND status = ?",
+            )
+            .bind(if retry { STATUS_QUEUED } else { STATUS_FAILED })
+            .bind(e.clone())
+            .bind(retry)
+            .bind(format!("+{} seconds", delay))
+            .bind(retry)
+            .bind(line)
+            .bind(&id)
+            .bind(STATUS_RUNNING)
+        }
+    };
+    update
+        .execute(&pool)
+        .await
+        .map_err(|e| format!("Failed to record job outcome: {}", e))?;
+
+    notify(app, &pool, &id).await?;
+    Ok(true)
+}
+
+/// Wait before retrying a job whose attempt number `attempts` just failed
+fn retry_delay_secs(attempts: i64) -> i64 {
+    let doublings = (attempts - 1).clamp(0, 16) as u32;
+    RETRY_DELAY_SECS
+        .saturating_mul(2i64.pow(doublings))
+        .min(MAX_RETRY_DELAY_SECS)
+}
+
+async fn run_job(
+    app: &AppHandle,
+    id: &str,
+    kind: JobKind,
+    payload: &str,
+    api_key: Option<String>,
+) -> Result<Value, String> {
+    let mut payload: Value =
+        serde_json::from_str(payload).map_err(|e| format!("Invalid job payload: {}", e))?;
+    if let Some(object) = payload.as_object_mut() {
+        // Progress events of the run carry the job's id, see `forward_progress`
+        object.insert("job_id".to_string(), Value::String(id.to_string()));
+        if let (true, Some(api_key)) = (kind.needs_api_key(), api_key) {
+            object.insert("api_key".to_string(), Value::String(api_key));
+        }
+    }
+
+    let app = app.clone();
+    match kind {
+        JobKind::ProcessAudio => {
+            let request = parse(payload)?;
+            to_value(audio_processor::process_audio_files(app, request).await?)
+        }
+        JobKind::TranscribeSpeakerTracks => {
+            let request: audio_transcription::SpeakerTranscriptionRequest = parse(payload)?;
+            let session_dir = app
+                .path()
+                .app_data_dir()
+                .map_err(|_| "Could not resolve app data directory")?
+                .join("sessions")
+                .join(&request.session_id);
+            let response = audio_transcription::transcribe_speaker_tracks(app, request).await?;
+            // Nobody may be around to save it, so the job writes the transcript itself
+            std::fs::write(session_dir.join("transcript.txt"), &response.text)
+                .map_err(|e| format!("Failed to save transcript: {}", e))?;
+            to_value(response)
+        }
+        JobKind::DetectChapters => {
+            let request = parse(payload)?;
+            to_value(audio_chapters::detect_chapters(app, request).await?)
+        }
+        JobKind::LiveTranscription => {
+            let request = parse(payload)?;
+            to_value(live_transcription::start_live_transcription(app, request).await?)
+        }
+        JobKind::SessionPipeline => {
+            let request = parse(payload)?;
+            to_value(session_pipeline::run_session_pipeline(app, request).await?)
+        }
+    }
+}
+
+/// Mirrors the progress events of a running job into its row, so progress and
+/// logs are kept even when no window is listening. Events are matched by the
+/// job id `run_job` puts in the request, since other jobs (or a direct
+/// command call) may be working on the same session. Returns the listeners to
+/// remove once the job is over.
+fn forward_progress(app: &AppHandle, id: &str, kind: JobKind, payload: &str) -> Vec<EventId> {
+    let file_count = serde_json::from_str::<Value>(payload)
+        .ok()
+        .and_then(|payload| payload["file_paths"].as_array().map(Vec::len))
+        .unwrap_or(0);
+
+    // Per-file steps of audio processing: analyze, then normalize
+    let file_steps = Mutex::new(0usize);
+    let audio_listener = {
+        let (app, id) = (app.clone(), id.to_string());
+        app.clone()
+            .listen("audio-processing-progress", move |event: Event| {
+                let Some(event) = parse_progress(&event, &id) else {
+                    return;
+                };
+                let mut line = format!(
+                    "{} {}: {}",
+                    event.stage,
+                    event.file_path.as_deref().unwrap_or_default(),
+                    event.status
+                );
+                if let Some(error) = &event.error {
+                    line.push_str(&format!(" ({})", error));
+                }
+
+                // Inside a pipeline, the pipeline's stages drive the progress
+                let progress =
+                    (kind == JobKind::ProcessAudio && event.status != "started").then(|| {
+                        let mut steps = file_steps.lock().unwrap();
+                        *steps += 1;
+                        (*steps as f64 / (2 * file_count.max(1)) as f64).min(1.0)
+                    });
+                record_progress(&app, &id, progress, line);
+            })
+    };
+    let pipeline_listener = {
+        let (app, id) = (app.clone(), id.to_string());
+        app.clone()
+            .listen("session-pipeline-progress", move |event: Event| {
+                let Some(event) = parse_progress(&event, &id) else {
+                    return;
+                };
+                let position = PipelineStage::ALL
+                    .iter()
+                    .position(|stage| stage.name() == event.stage);
+                let completed = matches!(event.status.as_str(), "done" | "skipped");
+                let progress = position.map(|position| {
+                    (position + usize::from(completed)) as f64 / PipelineStage::ALL.len() as f64
+                });
+                record_progress(
+                    &app,
+                    &id,
+                    progress,
+                    format!("Stage {}: {}", event.stage, event.status),
+                );
+            })
+    };
+
+    vec![audio_listener, pipeline_listener]
+}
+
+fn parse_progress(event: &Event, job_id: &str) -> Option<ProgressEvent> {
+    serde_json::from_str::<ProgressEvent>(event.payload())
+        .ok()
+        .filter(|progress| progress.job_id.as_deref() == Some(job_id))
+}
+
+/// Event handlers can't wait on the database, so the update runs on its own
+fn record_progress(app: &AppHandle, id: &str, progress: Option<f64>, line: String) {
+    let (app, id) = (app.clone(), id.to_string());
+    tauri::async_runtime::spawn(async move {
+        let pool = drizzle_proxy::pool(&app);
+        let update = sqlx::query(
+            "UPDATE jobs SET progress = MAX(progress, COALESCE(?, 0)), \
+             logs = logs || ? || char(10), updated_at = CURRENT_TIMESTAMP \
+             WHERE id = ? AND status = ?",
+        )
+        .bind(progress)
+        .bind(&line)
+        .bind(&id)
+        .bind(STATUS_RUNNING)
+        .execute(&pool)
+        .await;
+        match update {
+            Ok(_) => {
+                let _ = notify(&app, &pool, &id).await;
+            }
+            Err(e) => eprintln!("Failed to record progress of job {}: {}", id, e),
+        }
+    });
+}
+
+/// Checks that a payload (without its `api_key`) matches the job's request
+fn validate_payload(kind: JobKind, payload: &Value) -> Result<(), String> {
+    let mut payload = payload.clone();
+    if let (true, Some(object)) = (kind.needs_api_key(), payload.as_object_mut()) {
+        object.insert("api_key".to_string(), Value::String(String::new()));
+    }
+    match kind {
+        JobKind::ProcessAudio => parse::<audio_processor::ProcessAudioRequest>(payload).map(drop),
+        JobKind::TranscribeSpeakerTracks => {
+            parse::<audio_transcription::SpeakerTranscriptionRequest>(payload).map(drop)
+        }
+        JobKind::DetectChapters => {
+            parse::<audio_chapters::DetectChaptersRequest>(payload).map(drop)
+        }
+        JobKind::LiveTranscription => {
+            parse::<live_transcription::LiveTranscriptionRequest>(payload).map(drop)
+        }
+        JobKind::SessionPipeline => parse::<session_pipeline::PipelineRequest>(payload).map(drop),
+    }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(payload: Value) -> Result<T, String> {
+    serde_json::from_value(payload).map_err(|e| format!("Invalid job payload: {}", e))
+}
+
+fn to_value<T: Serialize>(response: T) -> Result<Value, String> {
+    serde_json::to_value(response).map_err(|e| format!("Failed to serialize job result: {}", e))
+}
+
+async fn status(pool: &SqlitePool, id: &str) -> Result<String, String> {
+    sqlx::query_scalar("SELECT status FROM jobs WHERE id = ?")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| format!("Failed to load job: {}", e))
+}
+
+async fn append_log(pool: &SqlitePool, id: &str, line: &str) -> Result<(), String> {
+    sqlx::query(
+        "UPDATE jobs SET logs = logs || ? || char(10), updated_at = CURRENT_TIMESTAMP \
+         WHERE id = ?",
+    )
+    .bind(line)
+    .bind(id)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to write job log: {}", e))?;
+    Ok(())
+}
+
+/// Reads a job back and tells the UI about its current state
+async fn notify(app: &AppHandle, pool: &SqlitePool, id: &str) -> Result<Job, String> {
+    let (id, session_id, kind, status, progress, attempts, max_attempts, error, result) =
+        sqlx::query_as::<
+            _,
+            (
+                String,
+                Option<String>,
+                String,
+                String,
+                f64,
+                i64,
+                i64,
+                Option<String>,
+                Option<String>,
+            ),
+        >(
+            "SELECT id, session_id, kind, status, progress, attempts, max_attempts, error, result \
+             FROM jobs WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("Failed to load job: {}", e))?
+        .ok_or_else(|| format!("Job {} not found", id))?;
+
+    let job = Job {
+        id,
+        session_id,
+        kind,
+        status,
+        progress,
+        attempts,
+        max_attempts,
+        error,
+        result: result.and_then(|result| serde_json::from_str(&result).ok()),
+    };
+    if let Err(e) = app.emit("job-updated", job.clone()) {
+        eprintln!("Failed to announce job update: {}", e);
+    }
+    Ok(job)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_up_to_a_cap() {
+        assert_eq!(retry_delay_secs(1), 30);
+        assert_eq!(retry_delay_secs(2), 60);
+        assert_eq!(retry_delay_secs(3), 120);
+        assert_eq!(retry_delay_secs(6), 900);
+        assert_eq!(retry_delay_secs(1000), MAX_RETRY_DELAY_SECS);
+        assert_eq!(retry_delay_secs(0), RETRY_DELAY_SECS);
+    }
+
+    #[test]
+    fn progress_events_carry_the_job_id() {
+        let event: ProgressEvent = serde_json::from_str(
+            r#"{"session_id":"s","job_id":"j","stage":"encode","status":"done"}"#,
+        )
+        .unwrap();
+        assert_eq!(event.job_id.as_deref(), Some("j"));
+
+        // Events of a direct command call have no job
+        let event: ProgressEvent =
+            serde_json::from_str(r#"{"session_id":"s","stage":"encode","status":"done"}"#).unwrap();
+        assert_eq!(event.job_id, None);
+    }
+}
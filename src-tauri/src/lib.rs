@@ -1,9 +1,30 @@
 use std::fs;
 use tauri::Manager;
+mod audio_alignment;
+mod audio_cache;
+mod audio_chapters;
+mod audio_clip;
+mod audio_filters;
+mod audio_format;
+mod audio_metadata;
+mod audio_ordering;
 mod audio_processor;
+mod audio_quality;
+mod audio_recovery;
+mod audio_silence;
 mod audio_transcription;
 mod audio_utils;
+mod audio_waveform;
+mod craig_import;
+mod date_utils;
 mod drizzle_proxy;
+mod folder_watcher;
+mod highlight_reel;
+mod job_queue;
+mod live_transcription;
+mod media_protocol;
+mod session_pipeline;
+mod session_plan;
 include!(concat!(env!("OUT_DIR"), "/generated_migrations.rs"));
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -29,13 +50,50 @@ pub fn run() {
             let salt_path = app_data_dir.join("salt.txt");
             app.handle()
                 .plugin(tauri_plugin_stronghold::Builder::with_argon2(&salt_path).build())?;
+
+            let pool = tauri::async_runtime::block_on(drizzle_proxy::open(&app_data_dir))?;
+            app.manage(drizzle_proxy::Database::new(pool));
+            // Processing runs without a cache rather than not at all
+            match audio_cache::AudioCache::open(&app_data_dir) {
+                Ok(cache) => {
+                    app.manage(cache);
+                }
+                Err(e) => eprintln!("Audio cache unavailable: {}", e),
+            }
+
+            folder_watcher::start(app.handle().clone());
+            app.manage(job_queue::JobCredentials::default());
+            job_queue::start(app.handle().clone());
             Ok(())
         })
         .plugin(tauri_plugin_opener::init())
+        .register_asynchronous_uri_scheme_protocol(media_protocol::SCHEME, media_protocol::handle)
         .invoke_handler(tauri::generate_handler![
             drizzle_proxy::run_sql,
+            drizzle_proxy::run_sql_batch,
+            audio_alignment::align_recordings,
+            audio_cache::clear_audio_cache,
+            audio_cache::get_audio_cache_info,
             audio_processor::process_audio_files,
-            audio_transcription::transcribe_audio
+            audio_processor::map_to_original_time,
+            session_plan::plan_session,
+            session_pipeline::run_session_pipeline,
+            session_pipeline::get_notes_system_prompt,
+            audio_quality::analyze_recording_quality,
+            audio_waveform::get_waveform,
+            audio_chapters::detect_chapters,
+            audio_metadata::tag_session_audio_files,
+            audio_clip::extract_clip,
+            highlight_reel::create_highlight_reel,
+            audio_transcription::transcribe_audio,
+            audio_transcription::transcribe_speaker_tracks,
+            live_transcription::start_live_transcription,
+            job_queue::enqueue_job,
+            job_queue::cancel_job,
+            job_queue::retry_job,
+            job_queue::set_job_credentials,
+            craig_import::import_craig_archive,
+            craig_import::set_speaker_mapping
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
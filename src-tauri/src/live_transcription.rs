@@ -0,0 +1,346 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tauri::{command, AppHandle, Emitter, Manager};
+
+use crate::audio_clip;
+use crate::audio_format::{AudioFormat, OutputOptions};
+use crate::audio_silence::{self, Silence};
+use crate::audio_transcription::{self, TranscriptSegment};
+use crate::audio_utils;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// The last few seconds of a file that is still being written may be a
+/// half-flushed cluster or fragment, so chunks never end this close to it
+const TAIL_MARGIN_SECS: f64 = 5.0;
+/// Leftovers shorter than this aren't worth an API call
+const MIN_FINAL_CHUNK_SECS: f64 = 0.5;
+
+#[derive(Debug, Deserialize)]
+pub struct LiveTranscriptionRequest {
+    pub session_id: String,
+    /// Recording that is still being written, e.g. OBS's MKV or fragmented MP4
+    pub file_path: String,
+    pub api_key: String,
+    /// Chunks end at the first usable pause after this much audio
+    #[serde(default = "default_min_chunk_secs")]
+    pub min_chunk_secs: f64,
+    /// Cut here even if nobody has paused
+    #[serde(default = "default_max_chunk_secs")]
+    pub max_chunk_secs: f64,
+    #[serde(default = "default_silence_threshold_db")]
+    pub silence_threshold_db: f64,
+    #[serde(default = "default_min_silence_secs")]
+    pub min_silence_secs: f64,
+    /// The recording is finished once it hasn't grown for this long
+    #[serde(default = "default_idle_secs")]
+    pub idle_secs: f64,
+}
+
+fn default_min_chunk_secs() -> f64 {
+    60.0
+}
+
+fn default_max_chunk_secs() -> f64 {
+    600.0
+}
+
+fn default_silence_threshold_db() -> f64 {
+    -40.0
+}
+
+fn default_min_silence_secs() -> f64 {
+    0.7
+}
+
+fn default_idle_secs() -> f64 {
+    60.0
+}
+
+#[derive(Debug, Serialize)]
+pub struct LiveTranscriptionResponse {
+    pub text: String,
+    pub segments: Vec<TranscriptSegment>,
+    /// How much of the recording was transcribed
+    pub duration: f64,
+}
+
+/// Payload of the `live-transcription-progress` event, sent after every chunk
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveTranscriptionProgress {
+    pub session_id: String,
+    pub transcribed_until: f64,
+    /// Only the segments of the chunk that was just transcribed
+    pub segments: Vec<TranscriptSegment>,
+    pub finished: bool,
+}
+
+/// Kept next to the transcript so a restarted run continues after the last
+/// chunk instead of transcribing (and appending) everything again
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LiveState {
+    file_path: String,
+    transcribed_until: f64,
+    segments: Vec<TranscriptSegment>,
+}
+
+/// Follows a recording while it is being written, transcribing it one
+/// silence-bounded chunk at a time and appending each chunk to the session
+/// transcript. Returns once the file has stopped growing and the rest of it
+/// has been transcribed. Timestamps are relative to the start of the recording.
+#[command]
+pub async fn start_live_transcription(
+    app: AppHandle,
+    request: LiveTranscriptionRequest,
+) -> Result<LiveTranscriptionResponse, String> {
+    if request.min_chunk_secs <= 0.0 || request.max_chunk_secs < request.min_chunk_secs {
+        return Err("Chunk length must be positive and the maximum at least the minimum".into());
+    }
+
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?;
+    let session_dir = app_data_dir.join("sessions").join(&request.session_id);
+    std::fs::create_dir_all(&session_dir)
+        .map_err(|e| format!("Failed to create session directory: {}", e))?;
+    let transcript_path = session_dir.join("transcript.txt");
+    let state_path = session_dir.join("live_transcript.json");
+
+    let recording = PathBuf::from(&request.file_path);
+    let ffmpeg_path = audio_utils::get_ffmpeg_path(&app)?;
+    let temp_dir = app_data_dir.join("temp_live").join(&request.session_id);
+    std::fs::create_dir_all(&temp_dir)
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+
+    let mut state = match load_state(&state_path) {
+        Some(state) if state.file_path == request.file_path => {
+            eprintln!(
+                "Resuming live transcription of {} at {:.1}s",
+                request.file_path, state.transcribed_until
+            );
+            state
+        }
+        _ => {
+            std::fs::write(&transcript_path, "")
+                .map_err(|e| format!("Failed to reset transcript: {}", e))?;
+            LiveState {
+                file_path: request.file_path.clone(),
+                ..LiveState::default()
+            }
+        }
+    };
+
+    let chunk_options = OutputOptions {
+        format: AudioFormat::Mp3,
+        bitrate_kbps: 64,
+        sample_rate: 16000,
+        channels: 1,
+    };
+    let idle = Duration::from_secs_f64(request.idle_secs.max(0.0));
+    let mut last_size = None;
+    let mut last_growth = Instant::now();
+    let mut chunk_index = 0;
+
+    loop {
+        let size = std::fs::metadata(&recording)
+            .map_err(|e| format!("Failed to read {}: {}", request.file_path, e))?
+            .len();
+        if last_size != Some(size) {
+            last_size = Some(size);
+            last_growth = Instant::now();
+        }
+        let finished = last_growth.elapsed() >= idle;
+
+        let limit = request.max_chunk_secs + TAIL_MARGIN_SECS;
+        let (silences, available) = detect_silences_from(
+            &ffmpeg_path,
+            &recording,
+            state.transcribed_until,
+            limit,
+            request.silence_threshold_db,
+            request.min_silence_secs,
+        )?;
+        // Decoding stops at `limit`, so only a shorter result means the rest of
+        // a finished recording fits in one chunk
+        let at_end = finished && available < limit;
+
+        let cut = if at_end {
+            Some(available).filter(|&end| end >= MIN_FINAL_CHUNK_SECS)
+        } else {
+            choose_cut(
+                &silences,
+                available,
+                request.min_chunk_secs,
+                request.max_chunk_secs,
+            )
+        };
+
+        if let Some(length) = cut {
+            let offset = state.transcribed_until;
+            let chunk_path = temp_dir.join(format!("chunk_{}.mp3", chunk_index));
+            chunk_index += 1;
+            audio_clip::extract_audio_segment(
+                &ffmpeg_path,
+                &recording,
+                &chunk_path,
+                offset,
+                length,
+                Some((&[], &chunk_options)),
+            )
+            .map_err(|e| format!("Failed to cut chunk at {:.1}s: {}", offset, e))?;
+
+            let chunk_data = std::fs::read(&chunk_path)
+                .map_err(|e| format!("Failed to read chunk file {:?}: {}", chunk_path, e))?;
+            let _ = std::fs::remove_file(&chunk_path);
+            let segments: Vec<TranscriptSegment> = audio_transcription::transcribe_chunk_segments(
+                &chunk_data,
+                AudioFormat::Mp3,
+                &request.api_key,
+            )
+            .await
+            .map_err(|e| format!("Failed to transcribe chunk at {:.1}s: {}", offset, e))?
+            .into_iter()
+            .map(|segment| TranscriptSegment {
+                start: segment.start + offset,
+                end: segment.end + offset,
+                ..segment
+            })
+            .collect();
+
+            append_transcript(&transcript_path, &segments)?;
+            state.transcribed_until = offset + length;
+            state.segments.extend(segments.iter().cloned());
+            save_state(&state_path, &state)?;
+
+            let _ = app.emit(
+                "live-transcription-progress",
+                LiveTranscriptionProgress {
+                    session_id: request.session_id.clone(),
+                    transcribed_until: state.transcribed_until,
+                    segments,
+                    finished: at_end,
+                },
+            );
+            if at_end {
+                break;
+            }
+            // More audio may already be waiting, so look again right away
+            continue;
+        }
+
+        if at_end {
+            let _ = app.emit(
+                "live-transcription-progress",
+                LiveTranscriptionProgress {
+                    session_id: request.session_id.clone(),
+                    transcribed_until: state.transcribed_until,
+                    segments: Vec::new(),
+                    finished: true,
+                },
+            );
+            break;
+        }
+
+        let _ = tauri::async_runtime::spawn_blocking(|| std::thread::sleep(POLL_INTERVAL)).await;
+    }
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    let text = std::fs::read_to_string(&transcript_path)
+        .map_err(|e| format!("Failed to read transcript: {}", e))?;
+
+    Ok(LiveTranscriptionResponse {
+        text,
+        duration: state.transcribed_until,
+        segments: state.segments,
+    })
+}
+
+/// Runs silence detection on up to `limit` seconds of the recording from
+/// `offset` on. Returns the silences and how much audio could be decoded,
+/// both relative to `offset`. A file that is still being written usually ends
+/// mid-packet, so FFmpeg complaining about the tail isn't an error as long as
+/// it decoded something.
+fn detect_silences_from(
+    ffmpeg_path: &Path,
+    input: &Path,
+    offset: f64,
+    limit: f64,
+    threshold_db: f64,
+    min_duration: f64,
+) -> Result<(Vec<Silence>, f64), String> {
+    let filter = format!("silencedetect=noise={}dB:d={}", threshold_db, min_duration);
+    let output = Command::new(ffmpeg_path)
+        .args(["-ss", &format!("{:.3}", offset)])
+        .arg("-i")
+        .arg(input)
+        .args(["-t", &format!("{:.3}", limit)])
+        .args(["-vn", "-af", &filter, "-f", "null", "-"])
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let available = match audio_silence::parse_last_time(&stderr) {
+        Some(time) => time,
+        None if output.status.success() => 0.0,
+        None => return Err(format!("FFmpeg silence detection failed: {}", stderr)),
+    };
+
+    Ok((
+        audio_silence::parse_silencedetect_output(&stderr, available),
+        available,
+    ))
+}
+
+/// Where the next chunk should end, relative to its start: in the middle of
+/// the last pause that is at least `min_chunk` in, or at `max_chunk` if
+/// nobody paused. `None` while there isn't enough audio yet.
+fn choose_cut(silences: &[Silence], available: f64, min_chunk: f64, max_chunk: f64) -> Option<f64> {
+    let settled = (available - TAIL_MARGIN_SECS).min(max_chunk);
+
+    // A silence still running at the end of the decoded audio may not be over
+    let pause = silences
+        .iter()
+        .rev()
+        .filter(|silence| silence.end <= settled)
+        .map(|silence| (silence.start + silence.end) / 2.0)
+        .find(|&middle| middle >= min_chunk);
+
+    pause.or((settled >= max_chunk).then_some(max_chunk))
+}
+
+fn append_transcript(path: &Path, segments: &[TranscriptSegment]) -> Result<(), String> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open transcript: {}", e))?;
+    for segment in segments {
+        let text = segment.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        writeln!(
+            file,
+            "[{}] {}",
+            audio_transcription::format_timestamp(segment.start),
+            text
+        )
+        .map_err(|e| format!("Failed to append to transcript: {}", e))?;
+    }
+    Ok(())
+}
+
+fn load_state(path: &Path) -> Option<LiveState> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save_state(path: &Path, state: &LiveState) -> Result<(), String> {
+    let content = serde_json::to_string(state)
+        .map_err(|e| format!("Failed to serialize live transcription state: {}", e))?;
+    std::fs::write(path, content)
+        .map_err(|e| format!("Failed to save live transcription state: {}", e))
+}
@@ -0,0 +1,295 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tauri::http::{header, Method, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, Runtime, UriSchemeContext, UriSchemeResponder};
+
+use crate::audio_format::AudioFormat;
+use crate::audio_processor;
+
+pub const SCHEME: &str = "loremonger";
+
+/// Open-ended range requests (`bytes=0-`) are answered with at most this much,
+/// so seeking in a long recording never reads the whole file
+const MAX_CHUNK_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Serves session media from app data, e.g. `loremonger://localhost/session/{id}/audio`
+/// (`http://loremonger.localhost/...` on Windows). Supported assets:
+///
+/// - `session/{id}/audio`: the main session audio
+/// - `session/{id}/archive`: the archive rendition, if one was made
+/// - `session/{id}/tracks/{n}`: a kept speaker track
+pub fn handle<R: Runtime>(
+    ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = ctx.app_handle().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let response = serve(&app, &request).unwrap_or_else(|(status, message)| {
+            eprintln!("Media request {} failed: {}", request.uri(), message);
+            plain_response(status, message)
+        });
+        responder.respond(response);
+    });
+}
+
+fn serve<R: Runtime>(
+    app: &AppHandle<R>,
+    request: &Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, (StatusCode, String)> {
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        return Err((
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Only GET and HEAD are supported".to_string(),
+        ));
+    }
+
+    let app_data_dir = app.path().app_data_dir().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Could not resolve app data directory".to_string(),
+        )
+    })?;
+    let path = resolve_asset(&app_data_dir, request.uri().path())?;
+
+    let mut file = File::open(&path)
+        .map_err(|_| (StatusCode::NOT_FOUND, "Media file not found".to_string()))?;
+    let size = file
+        .metadata()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .len();
+    let content_type = path
+        .extension()
+        .and_then(|ext| AudioFormat::from_extension(&ext.to_string_lossy()))
+        .map(|format| format.mime_type())
+        .unwrap_or("application/octet-stream");
+
+    let range = match request.headers().get(header::RANGE) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| parse_range(value, size))
+                .ok_or_else(|| {
+                    (
+                        StatusCode::RANGE_NOT_SATISFIABLE,
+                        format!("Unsatisfiable range for a {} byte file", size),
+                    )
+                })?,
+        ),
+        None => None,
+    };
+
+    let (start, end) = range.unwrap_or((0, size.saturating_sub(1)));
+    let end = if range.is_some() {
+        end.min(start + MAX_CHUNK_BYTES - 1)
+    } else {
+        end
+    };
+    let length = if size == 0 { 0 } else { end - start + 1 };
+
+    let mut body = Vec::new();
+    if request.method() == Method::GET && length > 0 {
+        file.seek(SeekFrom::Start(start))
+            .and_then(|_| file.take(length).read_to_end(&mut body))
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    let mut builder = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_LENGTH, length.to_string())
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+    builder = match range {
+        Some(_) => builder.status(StatusCode::PARTIAL_CONTENT).header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end, size),
+        ),
+        None => builder.status(StatusCode::OK),
+    };
+
+    builder
+        .body(body)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Maps a request path to a file inside the session's directory. Anything
+/// that isn't one of the known assets, or resolves outside the session
+/// directory, is rejected.
+fn resolve_asset(app_data_dir: &Path, uri_path: &str) -> Result<PathBuf, (StatusCode, String)> {
+    let not_found = || (StatusCode::NOT_FOUND, "Unknown media path".to_string());
+
+    // `convertFileSrc` percent-encodes the slashes along with everything else
+    let decoded = percent_decode(uri_path).ok_or_else(not_found)?;
+    let segments: Vec<&str> = decoded.split('/').filter(|s| !s.is_empty()).collect();
+
+    let (session_id, asset) = match segments.as_slice() {
+        ["session", id, rest @ ..] => (*id, rest),
+        _ => return Err(not_found()),
+    };
+    if session_id.is_empty()
+        || !session_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(not_found());
+    }
+
+    let session_dir = app_data_dir.join("sessions").join(session_id);
+    let path = match asset {
+        ["audio"] => audio_processor::session_audio_path(&session_dir),
+        ["archive"] => audio_processor::load_manifest(&session_dir)
+            .ok()
+            .and_then(|manifest| manifest.archive_path)
+            .map(PathBuf::from)
+            .ok_or_else(not_found)?,
+        ["tracks", index] => {
+            let index: usize = index.parse().map_err(|_| not_found())?;
+            session_dir
+                .join("tracks")
+                .join(audio_processor::track_file_name(index))
+        }
+        _ => return Err(not_found()),
+    };
+
+    // Manifest paths come from disk, so make sure they still point inside
+    // the session
+    let session_dir = session_dir.canonicalize().map_err(|_| not_found())?;
+    let path = path.canonicalize().map_err(|_| not_found())?;
+    if !path.starts_with(&session_dir) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Path is outside the session directory".to_string(),
+        ));
+    }
+
+    Ok(path)
+}
+
+/// Parses a single `bytes=` range. Returns inclusive (start, end), or `None`
+/// if it can't be satisfied.
+fn parse_range(value: &str, size: u64) -> Option<(u64, u64)> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    // Multiple ranges aren't supported; serving the first is allowed
+    let spec = spec.split(',').next()?.trim();
+    let (start, end) = spec.split_once('-')?;
+
+    if size == 0 {
+        return None;
+    }
+    let last = size - 1;
+
+    let range = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            if suffix == 0 {
+                return None;
+            }
+            (size.saturating_sub(suffix), last)
+        }
+        (start, "") => (start.parse().ok()?, last),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(last)),
+    };
+
+    (range.0 <= range.1 && range.0 <= last).then_some(range)
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            // `from_str_radix` would also take a sign, as in `%+1`
+            let hex = bytes.get(index + 1..index + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+fn plain_response(status: StatusCode, message: String) -> Response<Vec<u8>> {
+    let mut response = Response::new(message.into_bytes());
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_closed_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=100-100", 1000), Some((100, 100)));
+        // The end is clamped to the last byte
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some((900, 999)));
+    }
+
+    #[test]
+    fn parses_open_ended_ranges() {
+        assert_eq!(parse_range("bytes=0-", 1000), Some((0, 999)));
+        assert_eq!(parse_range("bytes=999-", 1000), Some((999, 999)));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        // A suffix longer than the file is the whole file
+        assert_eq!(parse_range("bytes=-5000", 1000), Some((0, 999)));
+        assert_eq!(parse_range("bytes=-0", 1000), None);
+    }
+
+    #[test]
+    fn serves_the_first_of_multiple_ranges() {
+        assert_eq!(parse_range("bytes=0-9, 20-29", 1000), Some((0, 9)));
+    }
+
+    #[test]
+    fn rejects_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=1000-1100", 1000), None);
+        assert_eq!(parse_range("bytes=500-100", 1000), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+    }
+
+    #[test]
+    fn rejects_malformed_ranges() {
+        assert_eq!(parse_range("items=0-99", 1000), None);
+        assert_eq!(parse_range("bytes=abc-", 1000), None);
+        assert_eq!(parse_range("bytes=5", 1000), None);
+        assert_eq!(parse_range("bytes=-", 1000), None);
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(
+            percent_decode("session%2Fabc-123%2Faudio").as_deref(),
+            Some("session/abc-123/audio")
+        );
+        assert_eq!(percent_decode("plain").as_deref(), Some("plain"));
+        assert_eq!(percent_decode("%e2%9c%93").as_deref(), Some("\u{2713}"));
+    }
+
+    #[test]
+    fn rejects_broken_percent_escapes() {
+        assert_eq!(percent_decode("abc%2"), None);
+        assert_eq!(percent_decode("abc%zz"), None);
+        assert_eq!(percent_decode("abc%+1"), None);
+        assert_eq!(percent_decode("%ff"), None);
+    }
+}
@@ -0,0 +1,672 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, Emitter, Manager};
+
+use crate::audio_chapters;
+use crate::audio_processor::{self, ProcessAudioRequest};
+use crate::audio_quality::{self, QualityRequest, Severity};
+use crate::audio_transcription;
+use crate::audio_utils;
+use crate::craig_import;
+use crate::drizzle_proxy;
+
+const PIPELINE_DIR: &str = "pipeline";
+const NOTES_FILE: &str = "notes.md";
+
+/// Stages in the order they run. Each one's output is checkpointed in
+/// `sessions/{id}/pipeline/{stage}.json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineStage {
+    Probe,
+    Process,
+    Transcribe,
+    PostProcess,
+    Notes,
+    Vault,
+}
+
+impl PipelineStage {
+    pub const ALL: [PipelineStage; 6] = [
+        PipelineStage::Probe,
+        PipelineStage::Process,
+        PipelineStage::Transcribe,
+        PipelineStage::PostProcess,
+        PipelineStage::Notes,
+        PipelineStage::Vault,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PipelineStage::Probe => "probe",
+            PipelineStage::Process => "process",
+            PipelineStage::Transcribe => "transcribe",
+            PipelineStage::PostProcess => "post_process",
+            PipelineStage::Notes => "notes",
+            PipelineStage::Vault => "vault",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PipelineRequest {
+    pub session_id: String,
+    /// Recordings to process. Only needed until the process stage has
+    /// completed once.
+    #[serde(default)]
+    pub file_paths: Vec<String>,
+    /// OpenAI API key, used for transcription and notes
+    pub api_key: String,
+    /// Settings for the process stage. Its `session_id` and `file_paths` are
+    /// taken from this request.
+    #[serde(default)]
+    pub process: ProcessAudioRequest,
+    /// Run this stage and every later one again, even if they completed before
+    pub force_from: Option<PipelineStage>,
+    #[serde(default = "default_notes_model")]
+    pub notes_model: String,
+    /// Background job this run belongs to, repeated in its progress events
+    #[serde(default)]
+    pub job_id: Option<String>,
+}
+
+fn default_notes_model() -> String {
+    "gpt-5-nano".to_string()
+}
+
+#[derive(Debug, Serialize)]
+pub struct PipelineResponse {
+    pub stages: Vec<StageOutcome>,
+    pub transcript_path: String,
+    pub notes_path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageOutcome {
+    pub stage: PipelineStage,
+    /// Completed by an earlier run and not run again
+    pub skipped: bool,
+    pub output: Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotesPromptRequest {
+    pub dm_name: String,
+    pub players: Vec<NotesPlayer>,
+    /// Date of the notes, `YYYY-MM-DD`
+    pub date: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotesPlayer {
+    pub character_name: String,
+    pub player_name: String,
+}
+
+/// Payload of the `session-pipeline-progress` event
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineProgress {
+    pub session_id: String,
+    pub job_id: Option<String>,
+    pub stage: PipelineStage,
+    pub status: &'static str,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    stage: PipelineStage,
+    output: Value,
+}
+
+/// Everything a stage needs. Stages hand data to later ones through the
+/// session directory, so a skipped stage's artifacts are picked up as-is.
+struct PipelineContext {
+    app: AppHandle,
+    pool: SqlitePool,
+    session_id: String,
+    job_id: Option<String>,
+    session_dir: PathBuf,
+    api_key: String,
+}
+
+/// Runs a session from recordings to notes in the vault: probe, process,
+/// transcribe, post-process, generate notes and write them out. Stages that
+/// completed in an earlier run are skipped unless `force_from` says otherwise,
+/// so a failed run can simply be started again.
+#[command]
+pub async fn run_session_pipeline(
+    app: AppHandle,
+    request: PipelineRequest,
+) -> Result<PipelineResponse, String> {
+    let session_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?
+        .join("sessions")
+        .join(&request.session_id);
+    let pipeline_dir = session_dir.join(PIPELINE_DIR);
+    std::fs::create_dir_all(&pipeline_dir)
+        .map_err(|e| format!("Failed to create pipeline directory: {}", e))?;
+
+    let context = PipelineContext {
+        pool: drizzle_proxy::pool(&app),
+        app,
+        session_id: request.session_id.clone(),
+        job_id: request.job_id.clone(),
+        session_dir,
+        api_key: request.api_key.clone(),
+    };
+    let max_concurrency = request.process.max_concurrency;
+    let mut process = Some(request.process);
+
+    let mut stages = Vec::new();
+    // Once a stage has run again, every later checkpoint is stale
+    let mut rerun = false;
+    for stage in PipelineStage::ALL {
+        let forced = rerun || request.force_from.is_some_and(|from| stage >= from);
+        let checkpoint = if forced {
+            None
+        } else {
+            load_checkpoint(&pipeline_dir, stage)
+        };
+
+        let outcome = match checkpoint {
+            Some(output) => {
+                report(&context, stage, "skipped");
+                StageOutcome {
+                    stage,
+                    skipped: true,
+                    output,
+                }
+            }
+            None => {
+                report(&context, stage, "running");
+                let result = match stage {
+                    PipelineStage::Probe => {
+                        probe(&context, &request.file_paths, max_concurrency).await
+                    }
+                    PipelineStage::Process => {
+                        let settings = process.take().unwrap_or_default();
+                        run_process(&context, &request.file_paths, settings).await
+                    }
+                    PipelineStage::Transcribe => transcribe(&context).await,
+                    PipelineStage::PostProcess => post_process(&context).await,
+                    PipelineStage::Notes => generate_notes(&context, &request.notes_model).await,
+                    PipelineStage::Vault => write_to_vault(&context).await,
+                };
+                let output = match result {
+                    Ok(output) => output,
+                    Err(e) => {
+                        report(&context, stage, "failed");
+                        return Err(format!("Pipeline stage {} failed: {}", stage.name(), e));
+                    }
+                };
+                save_checkpoint(&pipeline_dir, stage, &output)?;
+                report(&context, stage, "done");
+                rerun = true;
+                StageOutcome {
+                    stage,
+                    skipped: false,
+                    output,
+                }
+            }
+        };
+        stages.push(outcome);
+    }
+
+    let notes_path = stages
+        .last()
+        .and_then(|outcome| outcome.output.get("notes_path"))
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+
+    Ok(PipelineResponse {
+        stages,
+        transcript_path: context
+            .session_dir
+            .join("transcript.txt")
+            .to_string_lossy()
+            .to_string(),
+        notes_path,
+    })
+}
+
+/// Checks the recordings before hours of processing, and stops on anything
+/// the quality preflight considers an error
+async fn probe(
+    context: &PipelineContext,
+    file_paths: &[String],
+    max_concurrency: Option<usize>,
+) -> Result<Value, String> {
+    if file_paths.is_empty() {
+        return Err("No recordings to process".to_string());
+    }
+
+    let report = audio_quality::analyze_recording_quality(
+        context.app.clone(),
+        QualityRequest {
+            file_paths: file_paths.to_vec(),
+            max_concurrency,
+        },
+    )
+    .await?;
+
+    if report.worst == Some(Severity::Error) {
+        let problems: Vec<String> = report
+            .inputs
+            .iter()
+            .flat_map(|input| {
+                input
+                    .warnings
+                    .iter()
+                    .filter(|warning| warning.severity == Severity::Error)
+                    .map(move |warning| format!("{}: {}", input.path, warning.message))
+            })
+            .collect();
+        return Err(problems.join("; "));
+    }
+
+    to_value(report)
+}
+
+async fn run_process(
+    context: &PipelineContext,
+    file_paths: &[String],
+    settings: ProcessAudioRequest,
+) -> Result<Value, String> {
+    let output_filename = if settings.output_filename.is_empty() {
+        "audio.mp3".to_string()
+    } else {
+        settings.output_filename.clone()
+    };
+    let request = ProcessAudioRequest {
+        file_paths: file_paths.to_vec(),
+        output_filename,
+        session_id: context.session_id.clone(),
+        job_id: context.job_id.clone(),
+        ..settings
+    };
+
+    to_value(audio_processor::process_audio_files(context.app.clone(), request).await?)
+}
+
+/// Transcribes each speaker track when the session came from a multi-track
+/// recording, and the session audio otherwise. Saves `transcript.txt`.
+async fn transcribe(context: &PipelineContext) -> Result<Value, String> {
+    let has_tracks = !craig_import::load_speaker_tracks(&context.session_dir)
+        .unwrap_or_default()
+        .is_empty();
+
+    let (text, segments) = if has_tracks {
+        let response = audio_transcription::transcribe_speaker_tracks(
+            context.app.clone(),
+            audio_transcription::SpeakerTranscriptionRequest {
+                session_id: context.session_id.clone(),
+                api_key: context.api_key.clone(),
+            },
+        )
+        .await?;
+        (response.text, response.segments)
+    } else {
+        let ffmpeg_path = audio_utils::get_ffmpeg_path(&context.app)?;
+        let audio_path = audio_processor::session_audio_path(&context.session_dir);
+        let temp_dir = context.session_dir.join(PIPELINE_DIR).join("temp");
+        std::fs::create_dir_all(&temp_dir)
+            .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+        let segments = audio_transcription::transcribe_file_segments(
+            &ffmpeg_path,
+            &audio_path,
+            &context.api_key,
+            &temp_dir,
+        )
+        .await;
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let segments = segments?;
+
+        let text = segments
+            .iter()
+            .map(|segment| segment.text.trim())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        (text, segments)
+    };
+
+    let transcript_path = context.session_dir.join("transcript.txt");
+    std::fs::write(&transcript_path, &text)
+        .map_err(|e| format!("Failed to save transcript: {}", e))?;
+
+    Ok(serde_json::json!({
+        "transcript_path": transcript_path.to_string_lossy(),
+        "word_count": word_count(&text),
+        "segments": segments,
+    }))
+}
+
+/// Records the session's length and word count and finds its chapters
+async fn post_process(context: &PipelineContext) -> Result<Value, String> {
+    let ffmpeg_path = audio_utils::get_ffmpeg_path(&context.app)?;
+    let audio_path = audio_processor::session_audio_path(&context.session_dir);
+    let duration = audio_transcription::get_audio_duration(&ffmpeg_path, &audio_path)?;
+    let transcript = read_transcript(context)?;
+    let word_count = word_count(&transcript);
+
+    sqlx::query(
+        "UPDATE sessions SET duration = ?, word_count = ?, updated_at = CURRENT_TIMESTAMP \
+         WHERE id = ?",
+    )
+    .bind(duration)
+    .bind(word_count)
+    .bind(&context.session_id)
+    .execute(&context.pool)
+    .await
+    .map_err(|e| format!("Failed to update session: {}", e))?;
+
+    let chapters_request = serde_json::from_value(serde_json::json!({
+        "session_id": context.session_id,
+    }))
+    .map_err(|e| format!("Invalid chapter request: {}", e))?;
+    let chapters = audio_chapters::detect_chapters(context.app.clone(), chapters_request).await?;
+
+    Ok(serde_json::json!({
+        "duration": duration,
+        "word_count": word_count,
+        "chapters": chapters,
+    }))
+}
+
+/// Writes session notes from the transcript with the same prompt the app uses
+async fn generate_notes(context: &PipelineContext, model: &str) -> Result<Value, String> {
+    let transcript = read_transcript(context)?;
+
+    let (dm_name, today) = sqlx::query_as::<_, (String, String)>(
+        "SELECT c.dm_name, date('now', 'localtime') \
+         FROM sessions s JOIN campaigns c ON c.id = s.campaign_id WHERE s.id = ?",
+    )
+    .bind(&context.session_id)
+    .fetch_optional(&context.pool)
+    .await
+    .map_err(|e| format!("Failed to load campaign: {}", e))?
+    .ok_or_else(|| format!("Session {} not found", context.session_id))?;
+
+    let players: Vec<NotesPlayer> = sqlx::query_as::<_, (String, String)>(
+        "SELECT p.character_name, p.player_name FROM players p \
+         JOIN sessions s ON s.campaign_id = p.campaign_id WHERE s.id = ?",
+    )
+    .bind(&context.session_id)
+    .fetch_all(&context.pool)
+    .await
+    .map_err(|e| format!("Failed to load players: {}", e))?
+    .into_iter()
+    .map(|(character_name, player_name)| NotesPlayer {
+        character_name,
+        player_name,
+    })
+    .collect();
+
+    let system_prompt = notes_system_prompt(&dm_name, &players, &today);
+    let notes = complete_chat(
+        &context.api_key,
+        model,
+        &system_prompt,
+        &format!(
+            "Here is the session transcript you should use to generate the notes:\n    {}",
+            transcript
+        ),
+    )
+    .await?;
+
+    let notes_path = context.session_dir.join(PIPELINE_DIR).join(NOTES_FILE);
+    std::fs::write(&notes_path, &notes).map_err(|e| format!("Failed to save notes: {}", e))?;
+
+    Ok(serde_json::json!({
+        "notes_path": notes_path.to_string_lossy(),
+        "note_word_count": note_length(&notes),
+    }))
+}
+
+/// Instructions for writing session notes, shared with the app's own notes
+/// generation so both produce the same notes
+#[command]
+pub fn get_notes_system_prompt(request: NotesPromptRequest) -> String {
+    notes_system_prompt(&request.dm_name, &request.players, &request.date)
+}
+
+fn notes_system_prompt(dm_name: &str, players: &[NotesPlayer], today: &str) -> String {
+    let players = players
+        .iter()
+        .map(|player| {
+            format!(
+                "{} (played by: {})",
+                player.character_name, player.player_name
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"You are a helpful assistant that generates session notes for a Dungeons and Dragons game. Your goal is to capture the important details of the session. Write summary descriptions of characters and places in the session, as well as a timeline of events. Ignore any information or dialog that is not related to in-world events (e.g. discussions about the recording equipment, campaign platform, etc.).
+
+## Players
+The campaign players consist of the following characters:
+{players}
+The Dungeon Master name is {dm_name}.
+
+If the transcript is clearly referring to the same character or player but with slightly different spelling, use the character name from the list of players. Bear in mind that the transcript is a real-time transcription of the session, so it may contain some errors and typos on names.
+
+## Note Style
+- The notes should be in markdown format with bold/italic/table/list/quote formatting where appropriate.
+- Do not include any other text than the notes.
+- Use a neutral tone and keep it concise while retaining important details.
+
+The notes should include the following structure:
+```markdown
+---
+type: session
+date: {today}
+---
+## Session Summary
+## Characters
+### Players
+### NPCs
+## Locations
+## Timeline of Events
+## Summary
+### Story Hooks
+### Key Clues, Lore, & Items of Interest
+### Next Steps
+```"#
+    )
+}
+
+async fn complete_chat(
+    api_key: &str,
+    model: &str,
+    system_prompt: &str,
+    prompt: &str,
+) -> Result<String, String> {
+    let client = reqwest::Client::new();
+    let response = client
+        .post("https://api.openai.com/v1/chat/completions")
+        .header("Authorization", format!("Bearer {}", api_key))
+        .json(&serde_json::json!({
+            "model": model,
+            "messages": [
+                { "role": "system", "content": system_prompt },
+                { "role": "user", "content": prompt },
+            ],
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request to OpenAI API: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(format!(
+            "OpenAI API error (status {}): {}",
+            status.as_u16(),
+            error_text
+        ));
+    }
+
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
+    body["choices"][0]["message"]["content"]
+        .as_str()
+        .map(str::to_string)
+        .filter(|notes| !notes.trim().is_empty())
+        .ok_or_else(|| "OpenAI returned no notes".to_string())
+}
+
+/// Copies the notes to the campaign's vault session folder, or its output
+/// directory, named by the campaign's naming convention. Without either they
+/// stay in the session directory.
+async fn write_to_vault(context: &PipelineContext) -> Result<Value, String> {
+    let notes_source = context.session_dir.join(PIPELINE_DIR).join(NOTES_FILE);
+    let notes = std::fs::read_to_string(&notes_source)
+        .map_err(|e| format!("Failed to read generated notes: {}", e))?;
+
+    let (
+        number,
+        name,
+        campaign_name,
+        output_directory,
+        naming_convention,
+        vault_path,
+        vault_sessions,
+        date,
+        time,
+    ) = sqlx::query_as::<
+        _,
+        (
+            i64,
+            Option<String>,
+            String,
+            Option<String>,
+            String,
+            Option<String>,
+            Option<String>,
+            String,
+            String,
+        ),
+    >(
+        "SELECT s.number, s.name, c.name, c.output_directory, c.naming_convention, \
+             v.vault_path, v.session_dir, \
+             strftime('%Y-%m-%d', 'now', 'localtime'), strftime('%H-%M', 'now', 'localtime') \
+             FROM sessions s JOIN campaigns c ON c.id = s.campaign_id \
+             LEFT JOIN campaign_vaults v ON v.campaign_id = c.id WHERE s.id = ?",
+    )
+    .bind(&context.session_id)
+    .fetch_optional(&context.pool)
+    .await
+    .map_err(|e| format!("Failed to load campaign: {}", e))?
+    .ok_or_else(|| format!("Session {} not found", context.session_id))?;
+
+    // Same placeholders as the app's output directory and naming convention
+    let fill = |template: &str| {
+        template
+            .replace("{campaignName}", &campaign_name)
+            .replace("{sessionNumber}", &number.to_string())
+            .replace("{sessionName}", name.as_deref().unwrap_or_default())
+            .replace("{currentDate}", &date)
+            .replace("{currentTime}", &time)
+    };
+
+    let directory = match (vault_path, output_directory) {
+        (Some(vault), _) => Path::new(&vault).join(vault_sessions.unwrap_or_default()),
+        (None, Some(output)) if !output.trim().is_empty() => PathBuf::from(fill(&output)),
+        _ => context.session_dir.clone(),
+    };
+    let file_name = if naming_convention.trim().is_empty() {
+        format!("{}-{}_notes.md", date, time)
+    } else if naming_convention.contains(".md") {
+        fill(&naming_convention)
+    } else {
+        format!("{}.md", fill(&naming_convention))
+    };
+
+    std::fs::create_dir_all(&directory)
+        .map_err(|e| format!("Failed to create {}: {}", directory.display(), e))?;
+    let notes_path = directory.join(file_name);
+    std::fs::write(&notes_path, &notes)
+        .map_err(|e| format!("Failed to write {}: {}", notes_path.display(), e))?;
+
+    sqlx::query(
+        "UPDATE sessions SET file_path = ?, note_word_count = ?, \
+         updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    )
+    .bind(notes_path.to_string_lossy().to_string())
+    .bind(note_length(&notes))
+    .bind(&context.session_id)
+    .execute(&context.pool)
+    .await
+    .map_err(|e| format!("Failed to update session: {}", e))?;
+
+    Ok(serde_json::json!({ "notes_path": notes_path.to_string_lossy() }))
+}
+
+/// What the app stores as `note_word_count`: the length of the notes as
+/// JavaScript counts it, in UTF-16 code units
+fn note_length(notes: &str) -> i64 {
+    notes.encode_utf16().count() as i64
+}
+
+/// What the app stores as `word_count`: the transcript split on spaces
+fn word_count(text: &str) -> i64 {
+    text.split(' ').count() as i64
+}
+
+fn read_transcript(context: &PipelineContext) -> Result<String, String> {
+    std::fs::read_to_string(context.session_dir.join("transcript.txt"))
+        .map_err(|e| format!("Failed to read transcript: {}", e))
+}
+
+fn report(context: &PipelineContext, stage: PipelineStage, status: &'static str) {
+    let _ = context.app.emit(
+        "session-pipeline-progress",
+        PipelineProgress {
+            session_id: context.session_id.clone(),
+            job_id: context.job_id.clone(),
+            stage,
+            status,
+        },
+    );
+}
+
+fn checkpoint_path(pipeline_dir: &Path, stage: PipelineStage) -> PathBuf {
+    pipeline_dir.join(format!("{}.json", stage.name()))
+}
+
+fn load_checkpoint(pipeline_dir: &Path, stage: PipelineStage) -> Option<Value> {
+    let content = std::fs::read_to_string(checkpoint_path(pipeline_dir, stage)).ok()?;
+    let checkpoint: Checkpoint = serde_json::from_str(&content).ok()?;
+    Some(checkpoint.output).filter(|_| checkpoint.stage == stage)
+}
+
+fn save_checkpoint(
+    pipeline_dir: &Path,
+    stage: PipelineStage,
+    output: &Value,
+) -> Result<(), String> {
+    let checkpoint = Checkpoint {
+        stage,
+        output: output.clone(),
+    };
+    let content = serde_json::to_string_pretty(&checkpoint)
+        .map_err(|e| format!("Failed to serialize checkpoint: {}", e))?;
+    std::fs::write(checkpoint_path(pipeline_dir, stage), content)
+        .map_err(|e| format!("Failed to save {} checkpoint: {}", stage.name(), e))
+}
+
+fn to_value<T: Serialize>(output: T) -> Result<Value, String> {
+    serde_json::to_value(output).map_err(|e| format!("Failed to serialize stage output: {}", e))
+}
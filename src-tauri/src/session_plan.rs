@@ -0,0 +1,470 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+use tauri::{command, AppHandle, Manager};
+
+use crate::audio_format::OutputOptions;
+use crate::audio_ordering::InputOrder;
+use crate::audio_processor::{self, ConcatStrategy, ProcessAudioRequest, TrackLayout};
+use crate::audio_silence;
+use crate::audio_transcription;
+use crate::audio_utils;
+
+/// How many times faster than real time one FFmpeg pass over an input runs.
+/// Deliberately on the slow side; laptops on battery are much slower than
+/// desktops.
+const FFMPEG_SPEED: f64 = 150.0;
+/// How many times faster than real time a transcription chunk comes back
+const TRANSCRIPTION_SPEED: f64 = 15.0;
+/// Probing an input's header
+const PROBE_SECS: f64 = 0.5;
+/// Kept free on top of the estimate, since sizes are approximate
+const DISK_MARGIN: f64 = 1.1;
+
+#[derive(Debug, Deserialize)]
+pub struct PlanRequest {
+    /// The request that would be passed to `process_audio_files`
+    pub process: ProcessAudioRequest,
+    /// Leave out to plan processing only
+    pub transcription: Option<TranscriptionPlan>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TranscriptionPlan {
+    #[serde(default = "default_provider")]
+    pub provider: String,
+    #[serde(default = "default_model")]
+    pub model: String,
+    /// Transcribe each kept speaker track instead of the mixed session audio
+    #[serde(default)]
+    pub per_speaker: bool,
+    /// Prices to estimate with. Defaults to the built-in table.
+    pub prices: Option<Vec<TranscriptionPrice>>,
+}
+
+fn default_provider() -> String {
+    "openai".to_string()
+}
+
+fn default_model() -> String {
+    "whisper-1".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionPrice {
+    pub provider: String,
+    pub model: String,
+    pub usd_per_minute: f64,
+}
+
+/// List prices when this was written; pass `prices` to override them
+fn default_prices() -> Vec<TranscriptionPrice> {
+    [
+        ("openai", "whisper-1", 0.006),
+        ("openai", "gpt-4o-transcribe", 0.006),
+        ("openai", "gpt-4o-mini-transcribe", 0.003),
+    ]
+    .into_iter()
+    .map(|(provider, model, usd_per_minute)| TranscriptionPrice {
+        provider: provider.to_string(),
+        model: model.to_string(),
+        usd_per_minute,
+    })
+    .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanResponse {
+    pub inputs: Vec<PlannedInput>,
+    /// Length of the session audio that would be produced
+    pub duration: f64,
+    pub steps: Vec<PlanStep>,
+    /// Peak space used by working files while processing
+    pub temporary_bytes: u64,
+    /// Space taken by the session audio, archive and speaker tracks
+    pub output_bytes: u64,
+    /// What processing needs free, with a margin for estimation error
+    pub required_bytes: u64,
+    /// Free space on the disk holding app data
+    pub available_bytes: Option<u64>,
+    /// `process_audio_files` refuses to start when this is false
+    pub enough_disk_space: bool,
+    pub transcription: Option<TranscriptionEstimate>,
+    /// Rough wall-clock time for every step together
+    pub estimated_secs: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlannedInput {
+    pub path: String,
+    pub size_bytes: u64,
+    /// Missing when FFmpeg couldn't read the file's header
+    pub duration: Option<f64>,
+    pub is_video: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanStep {
+    /// Matches the `stage` of progress events where there is one
+    pub name: &'static str,
+    pub description: String,
+    pub estimated_secs: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TranscriptionEstimate {
+    pub provider: String,
+    pub model: String,
+    /// Uploads, counting each speaker track separately
+    pub chunks: usize,
+    pub minutes: f64,
+    /// Missing when the price table has no entry for the model
+    pub usd_per_minute: Option<f64>,
+    pub estimated_cost_usd: Option<f64>,
+}
+
+/// Works out what processing (and optionally transcribing) a session would
+/// involve without doing any of it: the steps, how long they may take, the
+/// disk space they need and what transcription would cost. All figures are
+/// estimates from the input headers.
+#[command]
+pub async fn plan_session(app: AppHandle, request: PlanRequest) -> Result<PlanResponse, String> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|_| "Could not resolve app data directory")?;
+    let ffmpeg_path = audio_utils::get_ffmpeg_path(&app)?;
+
+    let process = &request.process;
+    let inputs = probe_inputs(&ffmpeg_path, &process.file_paths);
+    let duration = output_duration(process, &inputs);
+    let disk = DiskEstimate::new(process, &inputs, duration, &app_data_dir);
+
+    let mut steps = processing_steps(process, &inputs, duration);
+    let transcription = request.transcription.as_ref().map(|plan| {
+        let estimate = estimate_transcription(process, &inputs, duration, plan);
+        steps.push(PlanStep {
+            name: "transcribe",
+            description: format!(
+                "Transcribe {:.0} minutes in {} upload{} with {} {}",
+                estimate.minutes,
+                estimate.chunks,
+                plural(estimate.chunks),
+                plan.provider,
+                plan.model
+            ),
+            estimated_secs: estimate.minutes * 60.0 / TRANSCRIPTION_SPEED,
+        });
+        estimate
+    });
+
+    Ok(PlanResponse {
+        inputs,
+        duration,
+        estimated_secs: steps.iter().map(|step| step.estimated_secs).sum(),
+        steps,
+        temporary_bytes: disk.temporary_bytes,
+        output_bytes: disk.output_bytes,
+        required_bytes: disk.required_bytes(),
+        available_bytes: disk.available_bytes,
+        enough_disk_space: disk.is_enough(),
+        transcription,
+    })
+}
+
+/// Fails when the disk holding app data doesn't have room for processing
+/// `request`. A disk whose free space can't be read is given the benefit of
+/// the doubt.
+pub fn check_disk_space(
+    ffmpeg_path: &Path,
+    app_data_dir: &Path,
+    request: &ProcessAudioRequest,
+) -> Result<(), String> {
+    let inputs = probe_inputs(ffmpeg_path, &request.file_paths);
+    let duration = output_duration(request, &inputs);
+    let disk = DiskEstimate::new(request, &inputs, duration, app_data_dir);
+
+    match disk.available_bytes {
+        Some(available) if !disk.is_enough() => Err(format!(
+            "Not enough disk space: processing needs about {} but only {} is free",
+            format_bytes(disk.required_bytes()),
+            format_bytes(available)
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn probe_inputs(ffmpeg_path: &Path, file_paths: &[String]) -> Vec<PlannedInput> {
+    file_paths
+        .iter()
+        .map(|path| {
+            let header = Command::new(ffmpeg_path).arg("-i").arg(path).output().ok();
+            PlannedInput {
+                path: path.clone(),
+                size_bytes: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+                duration: header.and_then(|output| {
+                    audio_silence::parse_duration(&String::from_utf8_lossy(&output.stderr))
+                }),
+                is_video: audio_processor::is_video_file(Path::new(path)),
+            }
+        })
+        .collect()
+}
+
+/// Sequential inputs add up; simultaneous tracks run side by side. Gap
+/// filling and silence trimming aren't accounted for.
+fn output_duration(request: &ProcessAudioRequest, inputs: &[PlannedInput]) -> f64 {
+    let durations = inputs.iter().filter_map(|input| input.duration);
+    match request.layout {
+        TrackLayout::Sequential => durations.sum(),
+        TrackLayout::Simultaneous => durations.fold(0.0, f64::max),
+    }
+}
+
+struct DiskEstimate {
+    temporary_bytes: u64,
+    output_bytes: u64,
+    available_bytes: Option<u64>,
+}
+
+impl DiskEstimate {
+    /// Temporary space assumes the per-file path, which the single-pass join
+    /// falls back to when it fails
+    fn new(
+        request: &ProcessAudioRequest,
+        inputs: &[PlannedInput],
+        duration: f64,
+        app_data_dir: &Path,
+    ) -> Self {
+        let intermediate = audio_processor::intermediate_format(request);
+        let extracted_video = OutputOptions::intermediate(44100, 2);
+
+        let mut temporary_bytes: u64 = inputs
+            .iter()
+            .filter_map(|input| input.duration.map(|d| (input, d)))
+            .map(|(input, duration)| {
+                let video = if input.is_video {
+                    extracted_video.estimated_bytes(duration)
+                } else {
+                    0
+                };
+                intermediate.estimated_bytes(duration) + video
+            })
+            .sum();
+        // The joined master, and its trimmed copy
+        temporary_bytes += intermediate.estimated_bytes(duration);
+        if request.silence_trim.is_some() {
+            temporary_bytes += intermediate.estimated_bytes(duration);
+        }
+
+        let mut output_bytes = request.output.estimated_bytes(duration);
+        if let Some(archive) = &request.archive {
+            output_bytes += archive.estimated_bytes(duration);
+        }
+        if request.layout == TrackLayout::Simultaneous {
+            output_bytes += track_bytes(inputs);
+        }
+
+        Self {
+            temporary_bytes,
+            output_bytes,
+            available_bytes: fs2::available_space(app_data_dir)
+                .map_err(|e| eprintln!("Could not read free disk space: {}", e))
+                .ok(),
+        }
+    }
+
+    fn required_bytes(&self) -> u64 {
+        ((self.temporary_bytes + self.output_bytes) as f64 * DISK_MARGIN) as u64
+    }
+
+    fn is_enough(&self) -> bool {
+        self.available_bytes
+            .is_none_or(|available| available >= self.required_bytes())
+    }
+}
+
+/// Kept speaker tracks are always encoded with the default options
+fn track_bytes(inputs: &[PlannedInput]) -> u64 {
+    let options = OutputOptions::default();
+    inputs
+        .iter()
+        .filter_map(|input| input.duration)
+        .map(|duration| options.estimated_bytes(duration))
+        .sum()
+}
+
+/// Mirrors the steps `process_audio_files` takes for this request
+fn processing_steps(
+    request: &ProcessAudioRequest,
+    inputs: &[PlannedInput],
+    duration: f64,
+) -> Vec<PlanStep> {
+    let count = inputs.len();
+    let total_input: f64 = inputs.iter().filter_map(|input| input.duration).sum();
+    let concurrency = request.max_concurrency.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    }) as f64;
+    let per_file_secs = total_input / FFMPEG_SPEED / concurrency.min(count.max(1) as f64);
+    let one_pass_secs = duration / FFMPEG_SPEED;
+
+    let mut steps = Vec::new();
+    let sequential = request.layout == TrackLayout::Sequential;
+
+    if sequential && count > 1 && (request.order != InputOrder::AsGiven || request.fill_gaps) {
+        steps.push(PlanStep {
+            name: "order",
+            description: if request.fill_gaps {
+                "Order inputs by recording time and fill the gaps between them".to_string()
+            } else {
+                "Order inputs by recording time".to_string()
+            },
+            estimated_secs: count as f64 * PROBE_SECS,
+        });
+    }
+
+    if request.profile.is_some() {
+        steps.push(PlanStep {
+            name: "analyze",
+            description: format!("Measure {} input{} for clean-up", count, plural(count)),
+            estimated_secs: per_file_secs,
+        });
+    }
+
+    let single_pass = sequential && request.concat_strategy == ConcatStrategy::FilterGraph;
+    if single_pass {
+        steps.push(PlanStep {
+            name: "join",
+            description: format!(
+                "Decode, filter and join {} input{} in one pass",
+                count,
+                plural(count)
+            ),
+            estimated_secs: per_file_secs.max(one_pass_secs),
+        });
+    } else {
+        let videos = inputs.iter().filter(|input| input.is_video).count();
+        steps.push(PlanStep {
+            name: "normalize",
+            description: if videos > 0 {
+                format!(
+                    "Convert {} input{} to a common format, extracting audio from {} video{}",
+                    count,
+                    plural(count),
+                    videos,
+                    plural(videos)
+                )
+            } else {
+                format!(
+                    "Convert {} input{} to a common format",
+                    count,
+                    plural(count)
+                )
+            },
+            estimated_secs: per_file_secs,
+        });
+        steps.push(PlanStep {
+            name: "join",
+            description: if sequential {
+                "Join the inputs end to end".to_string()
+            } else {
+                format!("Mix {} speaker track{}", count, plural(count))
+            },
+            estimated_secs: one_pass_secs,
+        });
+    }
+
+    if request.silence_trim.is_some() {
+        steps.push(PlanStep {
+            name: "trim_silences",
+            description: "Shorten long silences".to_string(),
+            estimated_secs: 2.0 * one_pass_secs,
+        });
+    }
+
+    // The single pass encodes straight to the renditions unless there's a
+    // master to trim first
+    if !single_pass || request.silence_trim.is_some() {
+        let renditions = 1 + request.archive.is_some() as usize;
+        steps.push(PlanStep {
+            name: "encode",
+            description: format!("Encode {} rendition{}", renditions, plural(renditions)),
+            estimated_secs: renditions as f64 * one_pass_secs,
+        });
+    }
+
+    if !sequential {
+        steps.push(PlanStep {
+            name: "keep_tracks",
+            description: format!("Keep {} speaker track{}", count, plural(count)),
+            estimated_secs: total_input / FFMPEG_SPEED,
+        });
+    }
+
+    steps
+}
+
+fn estimate_transcription(
+    request: &ProcessAudioRequest,
+    inputs: &[PlannedInput],
+    duration: f64,
+    plan: &TranscriptionPlan,
+) -> TranscriptionEstimate {
+    let track_options = OutputOptions::default();
+    let (chunks, seconds) = if plan.per_speaker && request.layout == TrackLayout::Simultaneous {
+        inputs.iter().filter_map(|input| input.duration).fold(
+            (0, 0.0),
+            |(chunks, seconds), duration| {
+                let size = track_options.estimated_bytes(duration) as usize;
+                (
+                    chunks + audio_transcription::chunk_count(size),
+                    seconds + duration,
+                )
+            },
+        )
+    } else {
+        let size = request.output.estimated_bytes(duration) as usize;
+        (audio_transcription::chunk_count(size), duration)
+    };
+
+    let minutes = seconds / 60.0;
+    let usd_per_minute = plan
+        .prices
+        .clone()
+        .unwrap_or_else(default_prices)
+        .into_iter()
+        .find(|price| {
+            price.provider.eq_ignore_ascii_case(&plan.provider)
+                && price.model.eq_ignore_ascii_case(&plan.model)
+        })
+        .map(|price| price.usd_per_minute);
+
+    TranscriptionEstimate {
+        provider: plan.provider.clone(),
+        model: plan.model.clone(),
+        chunks,
+        minutes,
+        usd_per_minute,
+        estimated_cost_usd: usd_per_minute.map(|price| price * minutes),
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= GB {
+        format!("{:.1} GB", bytes as f64 / GB)
+    } else {
+        format!("{:.0} MB", bytes as f64 / MB)
+    }
+}
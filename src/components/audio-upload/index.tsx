@@ -127,7 +127,11 @@ export function AudioUpload() {
         tag: "pre-process",
       });
 
-      const outputPath = await invoke<string>("process_audio_files", {
+      const { output_path: outputPath } = await invoke<{
+        output_path: string;
+        archive_path: string | null;
+        manifest_path: string;
+      }>("process_audio_files", {
         request: {
           file_paths: filePaths,
           output_filename: `/audio.mp3`,
@@ -1,4 +1,5 @@
 import { createOpenAI } from "@ai-sdk/openai";
+import { invoke } from "@tauri-apps/api/core";
 import { generateText } from "ai";
 import { format } from "date-fns";
 import { toast } from "sonner";
@@ -21,6 +22,8 @@ export async function getCachedOpenaiApiKey() {
       const apiKey = await getRecord(store, "openai-api-key");
       cachedApiKey = apiKey;
       apiKeyPromise = null;
+      // Background jobs only ever get the key in memory
+      await invoke("set_job_credentials", { apiKey });
       toast.success("OpenAI API key loaded", { id: toastId });
       return apiKey;
     })().catch((error) => {
@@ -55,43 +58,17 @@ export async function generateNotes(
     ${transcript}
     `;
 
-    const systemPrompt = `
-  You are a helpful assistant that generates session notes for a Dungeons and Dragons game. Your goal is to capture the important details of the session. Write summary descriptions of characters and places in the session, as well as a timeline of events. Ignore any information or dialog that is not related to in-world events (e.g. discussions about the recording equipment, campaign platform, etc.).
-
-  ## Players
-  The campaign players consist of the following characters: 
-  ${players
-    .map(
-      (player) => `${player.characterName} (played by: ${player.playerName})`
-    )
-    .join("\n")}
-  The Dungeon Master name is ${dmName}.
-
-  If the transcript is clearly referring to the same character or player but with slightly different spelling, use the character name from the list of players. Bear in mind that the transcript is a real-time transcription of the session, so it may contain some errors and typos on names. 
-
-  ## Note Style
-  - The notes should be in markdown format with bold/italic/table/list/quote formatting where appropriate. 
-  - Do not include any other text than the notes.
-  - Use a neutral tone and keep it concise while retaining important details.
-
-  The notes should include the following structure:
-  \`\`\`markdown
-  ---
-  type: session
-  date: ${format(new Date(), "yyyy-MM-dd")}
-  ---
-  ## Session Summary
-  ## Characters
-  ### Players
-  ### NPCs
-  ## Locations
-  ## Timeline of Events
-  ## Summary
-  ### Story Hooks
-  ### Key Clues, Lore, & Items of Interest
-  ### Next Steps
-  \`\`\`
-  `;
+    // The prompt lives in Rust, so the session pipeline writes the same notes
+    const systemPrompt = await invoke<string>("get_notes_system_prompt", {
+      request: {
+        dm_name: dmName,
+        players: players.map((player) => ({
+          character_name: player.characterName,
+          player_name: player.playerName,
+        })),
+        date: format(new Date(), "yyyy-MM-dd"),
+      },
+    });
 
     const { text, usage } = await generateText({
       model: aiClient("gpt-5-nano"),
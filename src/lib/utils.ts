@@ -1,3 +1,4 @@
+import { convertFileSrc } from "@tauri-apps/api/core";
 import { appDataDir, documentDir } from "@tauri-apps/api/path";
 import { save } from "@tauri-apps/plugin-dialog";
 import { create, writeFile } from "@tauri-apps/plugin-fs";
@@ -144,6 +145,15 @@ export async function saveLogs(logs: Progress[], sessionId: string) {
   await writeFile(logsPath, new TextEncoder().encode(data));
 }
 
+export type SessionMediaAsset = "audio" | "archive" | `tracks/${number}`;
+
+export function getSessionMediaUrl(
+  sessionId: string,
+  asset: SessionMediaAsset = "audio"
+) {
+  return convertFileSrc(`session/${sessionId}/${asset}`, "loremonger");
+}
+
 export function formatDuration(duration: number) {
   const hours = Math.floor(duration / 3600);
   const minutes = Math.floor((duration % 3600) / 60);
@@ -1,10 +1,21 @@
 import { createCollection, eq as eqDb } from "@tanstack/db";
 import { queryCollectionOptions } from "@tanstack/query-db-collection";
 import { useLiveQuery } from "@tanstack/react-db";
-import { desc, eq } from "drizzle-orm";
+import { desc, eq, inArray } from "drizzle-orm";
 import { queryClient } from "~/server/collections";
 import db from "~/server/db";
-import { campaigns } from "~/server/db/schema";
+import {
+  campaignVaults,
+  campaigns,
+  chapters,
+  jobs,
+  moments,
+  players,
+  sessions,
+  speakerMappings,
+  watchedFiles,
+  watchedFolders,
+} from "~/server/db/schema";
 
 const campaignsCollection = createCollection(
   queryCollectionOptions({
@@ -34,7 +45,32 @@ const campaignsCollection = createCollection(
     },
     onDelete: async ({ transaction }) => {
       const { original } = transaction.mutations[0];
-      await db.delete(campaigns).where(eq(campaigns.id, original.id));
+      // Foreign keys are enforced, so everything in the campaign goes first,
+      // all in one transaction
+      const campaignSessions = db
+        .select({ id: sessions.id })
+        .from(sessions)
+        .where(eq(sessions.campaignId, original.id));
+      await db.batch([
+        db.delete(moments).where(inArray(moments.sessionId, campaignSessions)),
+        db
+          .delete(chapters)
+          .where(inArray(chapters.sessionId, campaignSessions)),
+        db.delete(jobs).where(inArray(jobs.sessionId, campaignSessions)),
+        db.delete(watchedFiles).where(eq(watchedFiles.campaignId, original.id)),
+        db
+          .delete(watchedFolders)
+          .where(eq(watchedFolders.campaignId, original.id)),
+        db.delete(sessions).where(eq(sessions.campaignId, original.id)),
+        db
+          .delete(speakerMappings)
+          .where(eq(speakerMappings.campaignId, original.id)),
+        db.delete(players).where(eq(players.campaignId, original.id)),
+        db
+          .delete(campaignVaults)
+          .where(eq(campaignVaults.campaignId, original.id)),
+        db.delete(campaigns).where(eq(campaigns.id, original.id)),
+      ]);
     },
   })
 );
@@ -0,0 +1,43 @@
+import { createCollection, eq as eqDb } from "@tanstack/db";
+import { queryCollectionOptions } from "@tanstack/query-db-collection";
+import { useLiveQuery } from "@tanstack/react-db";
+import { eq } from "drizzle-orm";
+import { queryClient } from "~/server/collections";
+import db from "~/server/db";
+import { chapters } from "~/server/db/schema";
+
+const chaptersCollection = createCollection(
+  queryCollectionOptions({
+    queryKey: ["chapters"],
+    queryClient: queryClient,
+    queryFn: async () => {
+      return await db.query.chapters.findMany();
+    },
+    getKey: (chapter) => chapter.id,
+    onInsert: async ({ transaction }) => {
+      const { modified: newChapter } = transaction.mutations[0];
+      await db.insert(chapters).values(newChapter);
+    },
+    onUpdate: async ({ transaction }) => {
+      const { original, modified } = transaction.mutations[0];
+      await db
+        .update(chapters)
+        .set(modified)
+        .where(eq(chapters.id, original.id));
+    },
+    onDelete: async ({ transaction }) => {
+      const { original } = transaction.mutations[0];
+      await db.delete(chapters).where(eq(chapters.id, original.id));
+    },
+  })
+);
+
+export const useChapters = (sessionId: string | undefined) => {
+  return useLiveQuery((q) =>
+    q
+      .from({ chapters: chaptersCollection })
+      .where(({ chapters }) => eqDb(chapters.sessionId, sessionId))
+  );
+};
+
+export default chaptersCollection;
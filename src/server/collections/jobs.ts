@@ -0,0 +1,33 @@
+import { createCollection, eq as eqDb } from "@tanstack/db";
+import { queryCollectionOptions } from "@tanstack/query-db-collection";
+import { useLiveQuery } from "@tanstack/react-db";
+import { listen } from "@tauri-apps/api/event";
+import { queryClient } from "~/server/collections";
+import db from "~/server/db";
+
+// Jobs are written by the Rust job queue, so this collection is read-only and
+// refreshed whenever the queue reports a change
+const jobsCollection = createCollection(
+  queryCollectionOptions({
+    queryKey: ["jobs"],
+    queryClient: queryClient,
+    queryFn: async () => {
+      return await db.query.jobs.findMany();
+    },
+    getKey: (job) => job.id,
+  })
+);
+
+listen("job-updated", () => {
+  jobsCollection.utils.refetch();
+});
+
+export const useJobs = (sessionId: string | undefined) => {
+  return useLiveQuery((q) =>
+    q
+      .from({ jobs: jobsCollection })
+      .where(({ jobs }) => eqDb(jobs.sessionId, sessionId))
+  );
+};
+
+export default jobsCollection;
@@ -0,0 +1,43 @@
+import { createCollection, eq as eqDb } from "@tanstack/db";
+import { queryCollectionOptions } from "@tanstack/query-db-collection";
+import { useLiveQuery } from "@tanstack/react-db";
+import { eq } from "drizzle-orm";
+import { queryClient } from "~/server/collections";
+import db from "~/server/db";
+import { moments } from "~/server/db/schema";
+
+const momentsCollection = createCollection(
+  queryCollectionOptions({
+    queryKey: ["moments"],
+    queryClient: queryClient,
+    queryFn: async () => {
+      return await db.query.moments.findMany();
+    },
+    getKey: (moment) => moment.id,
+    onInsert: async ({ transaction }) => {
+      const { modified: newMoment } = transaction.mutations[0];
+      await db.insert(moments).values(newMoment);
+    },
+    onUpdate: async ({ transaction }) => {
+      const { original, modified } = transaction.mutations[0];
+      await db
+        .update(moments)
+        .set(modified)
+        .where(eq(moments.id, original.id));
+    },
+    onDelete: async ({ transaction }) => {
+      const { original } = transaction.mutations[0];
+      await db.delete(moments).where(eq(moments.id, original.id));
+    },
+  })
+);
+
+export const useMoments = (sessionId: string | undefined) => {
+  return useLiveQuery((q) =>
+    q
+      .from({ moments: momentsCollection })
+      .where(({ moments }) => eqDb(moments.sessionId, sessionId))
+  );
+};
+
+export default momentsCollection;
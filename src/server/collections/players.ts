@@ -4,7 +4,7 @@ import { eq as dbEq, useLiveQuery } from "@tanstack/react-db";
 import { eq } from "drizzle-orm";
 import { queryClient } from "~/server/collections";
 import db from "~/server/db";
-import { players } from "~/server/db/schema";
+import { players, speakerMappings } from "~/server/db/schema";
 
 const playersCollection = createCollection(
   queryCollectionOptions({
@@ -24,7 +24,13 @@ const playersCollection = createCollection(
     },
     onDelete: async ({ transaction }) => {
       const { original } = transaction.mutations[0];
-      await db.delete(players).where(eq(players.id, original.id));
+      // A speaker mapped to a removed player has to be mapped again
+      await db.batch([
+        db
+          .delete(speakerMappings)
+          .where(eq(speakerMappings.playerId, original.id)),
+        db.delete(players).where(eq(players.id, original.id)),
+      ]);
     },
   })
 );
@@ -1,10 +1,17 @@
 import { createCollection, eq as eqDb } from "@tanstack/db";
 import { queryCollectionOptions } from "@tanstack/query-db-collection";
 import { useLiveQuery } from "@tanstack/react-db";
+import { listen } from "@tauri-apps/api/event";
 import { eq } from "drizzle-orm";
 import { queryClient } from "~/server/collections";
 import db from "~/server/db";
-import { sessions } from "~/server/db/schema";
+import {
+  chapters,
+  jobs,
+  moments,
+  sessions,
+  watchedFiles,
+} from "~/server/db/schema";
 
 const sessionsCollection = createCollection(
   queryCollectionOptions({
@@ -27,11 +34,33 @@ const sessionsCollection = createCollection(
     },
     onDelete: async ({ transaction }) => {
       const { original } = transaction.mutations[0];
-      await db.delete(sessions).where(eq(sessions.id, original.id));
+      // Foreign keys are enforced, so rows pointing at the session go first,
+      // all in one transaction
+      await db.batch([
+        db.delete(moments).where(eq(moments.sessionId, original.id)),
+        db.delete(chapters).where(eq(chapters.sessionId, original.id)),
+        db.delete(jobs).where(eq(jobs.sessionId, original.id)),
+        db
+          .update(watchedFiles)
+          .set({ sessionId: null })
+          .where(eq(watchedFiles.sessionId, original.id)),
+        db.delete(sessions).where(eq(sessions.id, original.id)),
+      ]);
     },
   })
 );
 
+// The watch folder creates draft sessions and queues their processing, which
+// marks them ready once it succeeds
+listen("watch-folder-session", () => {
+  sessionsCollection.utils.refetch();
+});
+listen<{ kind: string; status: string }>("job-updated", ({ payload }) => {
+  if (payload.kind === "process_audio" && payload.status === "succeeded") {
+    sessionsCollection.utils.refetch();
+  }
+});
+
 export const useSessions = (campaignId: string | undefined) => {
   return useLiveQuery((q) =>
     q
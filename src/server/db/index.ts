@@ -2,25 +2,42 @@ import { invoke } from "@tauri-apps/api/core";
 import { drizzle } from "drizzle-orm/sqlite-proxy";
 import * as schema from "./schema";
 
-type Row = {
-  columns: string[];
-  values: string[];
+type SqlResult =
+  | { changes: number; lastInsertRowid: number }
+  | { columns: string[]; row: unknown[] | null }
+  | { columns: string[]; rows: unknown[][] };
+
+// Drizzle reads a missing row as `undefined`
+const toProxyResult = (result: SqlResult) => {
+  if ("changes" in result) {
+    return { rows: [], ...result };
+  }
+  if ("row" in result) {
+    return (result.row ? { rows: result.row } : {}) as { rows: unknown[] };
+  }
+  return { rows: result.rows };
 };
+
 const db = drizzle(
   async (sql, params, method) => {
     try {
-      const rows = await invoke<Row[]>("run_sql", {
-        query: { sql, params },
+      const result = await invoke<SqlResult>("run_sql", {
+        query: { sql, params, method },
       });
-      if (rows.length === 0 && method === "get") {
-        return {} as { rows: string[] };
-      }
-      return method === "get"
-        ? { rows: rows[0].values }
-        : { rows: rows.map((r) => r.values) };
+      return toProxyResult(result);
+    } catch (e: unknown) {
+      console.error("Error from sqlite proxy server: ", e);
+      throw e;
+    }
+  },
+  // `db.batch` runs its queries in one transaction
+  async (queries) => {
+    try {
+      const results = await invoke<SqlResult[]>("run_sql_batch", { queries });
+      return results.map(toProxyResult);
     } catch (e: unknown) {
       console.error("Error from sqlite proxy server: ", e);
-      return { rows: [] };
+      throw e;
     }
   },
   {
@@ -1,5 +1,12 @@
 import { relations, sql } from "drizzle-orm";
-import { integer, real, sqliteTable, text } from "drizzle-orm/sqlite-core";
+import {
+  index,
+  integer,
+  real,
+  sqliteTable,
+  text,
+  uniqueIndex,
+} from "drizzle-orm/sqlite-core";
 
 export const campaigns = sqliteTable("campaigns", {
   id: text("id").primaryKey(),
@@ -11,6 +18,7 @@ export const campaigns = sqliteTable("campaigns", {
     .notNull()
     .default("{currentDate}-{currentTime}_notes.md"),
   customSystemPrompt: text("custom_system_prompt"),
+  watchFolder: text("watch_folder"),
   createdAt: text("created_at").default(sql`CURRENT_TIMESTAMP`),
   updatedAt: text("updated_at").default(sql`CURRENT_TIMESTAMP`),
 });
@@ -19,6 +27,9 @@ export const campaignRelations = relations(campaigns, ({ many, one }) => ({
   players: many(players),
   sessions: many(sessions),
   vault: one(campaignVaults),
+  speakerMappings: many(speakerMappings),
+  watchedFolder: one(watchedFolders),
+  watchedFiles: many(watchedFiles),
 }));
 
 export const players = sqliteTable("players", {
@@ -34,11 +45,12 @@ export const players = sqliteTable("players", {
     .notNull(),
 });
 
-export const playerRelations = relations(players, ({ one }) => ({
+export const playerRelations = relations(players, ({ one, many }) => ({
   campaign: one(campaigns, {
     fields: [players.campaignId],
     references: [campaigns.id],
   }),
+  speakerMappings: many(speakerMappings),
 }));
 
 export const sessions = sqliteTable("sessions", {
@@ -53,6 +65,7 @@ export const sessions = sqliteTable("sessions", {
   noteWordCount: integer("note_word_count"),
   filePath: text("file_path"),
   date: text("date").notNull(),
+  status: text("status").notNull().default("ready"),
   createdAt: text("created_at")
     .default(sql`CURRENT_TIMESTAMP`)
     .notNull(),
@@ -61,11 +74,14 @@ export const sessions = sqliteTable("sessions", {
     .notNull(),
 });
 
-export const sessionRelations = relations(sessions, ({ one }) => ({
+export const sessionRelations = relations(sessions, ({ one, many }) => ({
   campaign: one(campaigns, {
     fields: [sessions.campaignId],
     references: [campaigns.id],
   }),
+  moments: many(moments),
+  chapters: many(chapters),
+  jobs: many(jobs),
 }));
 
 export const campaignVaults = sqliteTable("campaign_vaults", {
@@ -93,3 +109,190 @@ export const campaignVaultRelations = relations(campaignVaults, ({ one }) => ({
     references: [campaigns.id],
   }),
 }));
+
+export const speakerMappings = sqliteTable(
+  "speaker_mappings",
+  {
+    id: text("id").primaryKey(),
+    campaignId: text("campaign_id")
+      .references(() => campaigns.id)
+      .notNull(),
+    speakerName: text("speaker_name").notNull(),
+    playerId: text("player_id").references(() => players.id),
+    isDm: integer("is_dm", { mode: "boolean" }).notNull().default(false),
+    createdAt: text("created_at")
+      .default(sql`CURRENT_TIMESTAMP`)
+      .notNull(),
+    updatedAt: text("updated_at")
+      .default(sql`CURRENT_TIMESTAMP`)
+      .notNull(),
+  },
+  (table) => [
+    uniqueIndex("speaker_mappings_campaign_speaker_unique").on(
+      table.campaignId,
+      table.speakerName
+    ),
+  ]
+);
+
+export const speakerMappingRelations = relations(
+  speakerMappings,
+  ({ one }) => ({
+    campaign: one(campaigns, {
+      fields: [speakerMappings.campaignId],
+      references: [campaigns.id],
+    }),
+    player: one(players, {
+      fields: [speakerMappings.playerId],
+      references: [players.id],
+    }),
+  })
+);
+
+export const moments = sqliteTable(
+  "moments",
+  {
+    id: text("id").primaryKey(),
+    sessionId: text("session_id")
+      .references(() => sessions.id)
+      .notNull(),
+    startTime: real("start_time").notNull(),
+    endTime: real("end_time").notNull(),
+    label: text("label").notNull(),
+    createdAt: text("created_at")
+      .default(sql`CURRENT_TIMESTAMP`)
+      .notNull(),
+    updatedAt: text("updated_at")
+      .default(sql`CURRENT_TIMESTAMP`)
+      .notNull(),
+  },
+  (table) => [index("moments_session_id_idx").on(table.sessionId)]
+);
+
+export const momentRelations = relations(moments, ({ one }) => ({
+  session: one(sessions, {
+    fields: [moments.sessionId],
+    references: [sessions.id],
+  }),
+}));
+
+export const chapters = sqliteTable(
+  "chapters",
+  {
+    id: text("id").primaryKey(),
+    sessionId: text("session_id")
+      .references(() => sessions.id)
+      .notNull(),
+    startTime: real("start_time").notNull(),
+    endTime: real("end_time").notNull(),
+    kind: text("kind").notNull(),
+    title: text("title").notNull(),
+    confidence: real("confidence"),
+    createdAt: text("created_at")
+      .default(sql`CURRENT_TIMESTAMP`)
+      .notNull(),
+    updatedAt: text("updated_at")
+      .default(sql`CURRENT_TIMESTAMP`)
+      .notNull(),
+  },
+  (table) => [index("chapters_session_id_idx").on(table.sessionId)]
+);
+
+export const chapterRelations = relations(chapters, ({ one }) => ({
+  session: one(sessions, {
+    fields: [chapters.sessionId],
+    references: [sessions.id],
+  }),
+}));
+
+export const watchedFolders = sqliteTable("watched_folders", {
+  campaignId: text("campaign_id")
+    .primaryKey()
+    .references(() => campaigns.id),
+  path: text("path").notNull(),
+  watchingSince: real("watching_since").notNull(),
+});
+
+export const watchedFolderRelations = relations(watchedFolders, ({ one }) => ({
+  campaign: one(campaigns, {
+    fields: [watchedFolders.campaignId],
+    references: [campaigns.id],
+  }),
+}));
+
+export const watchedFiles = sqliteTable(
+  "watched_files",
+  {
+    id: text("id").primaryKey(),
+    campaignId: text("campaign_id")
+      .references(() => campaigns.id)
+      .notNull(),
+    path: text("path").notNull(),
+    size: integer("size").notNull(),
+    modifiedAt: real("modified_at").notNull(),
+    lastChangeAt: real("last_change_at").notNull(),
+    duration: real("duration"),
+    status: text("status").notNull(),
+    sessionId: text("session_id").references(() => sessions.id),
+    createdAt: text("created_at")
+      .default(sql`CURRENT_TIMESTAMP`)
+      .notNull(),
+    updatedAt: text("updated_at")
+      .default(sql`CURRENT_TIMESTAMP`)
+      .notNull(),
+  },
+  (table) => [
+    uniqueIndex("watched_files_campaign_id_path_unique").on(
+      table.campaignId,
+      table.path
+    ),
+  ]
+);
+
+export const watchedFileRelations = relations(watchedFiles, ({ one }) => ({
+  campaign: one(campaigns, {
+    fields: [watchedFiles.campaignId],
+    references: [campaigns.id],
+  }),
+  session: one(sessions, {
+    fields: [watchedFiles.sessionId],
+    references: [sessions.id],
+  }),
+}));
+
+export const jobs = sqliteTable(
+  "jobs",
+  {
+    id: text("id").primaryKey(),
+    sessionId: text("session_id").references(() => sessions.id),
+    kind: text("kind").notNull(),
+    status: text("status").notNull().default("queued"),
+    payload: text("payload").notNull(),
+    result: text("result"),
+    error: text("error"),
+    progress: real("progress").notNull().default(0),
+    attempts: integer("attempts").notNull().default(0),
+    maxAttempts: integer("max_attempts").notNull().default(3),
+    logs: text("logs").notNull().default(""),
+    runAfter: text("run_after"),
+    startedAt: text("started_at"),
+    finishedAt: text("finished_at"),
+    createdAt: text("created_at")
+      .default(sql`CURRENT_TIMESTAMP`)
+      .notNull(),
+    updatedAt: text("updated_at")
+      .default(sql`CURRENT_TIMESTAMP`)
+      .notNull(),
+  },
+  (table) => [
+    index("jobs_status_idx").on(table.status),
+    index("jobs_session_id_idx").on(table.sessionId),
+  ]
+);
+
+export const jobRelations = relations(jobs, ({ one }) => ({
+  session: one(sessions, {
+    fields: [jobs.sessionId],
+    references: [sessions.id],
+  }),
+}));